@@ -0,0 +1,494 @@
+pub mod camera;
+pub mod header;
+pub mod huc1;
+pub mod huc3;
+pub mod infrared;
+pub mod mapper;
+pub mod mbc5;
+pub mod mbc7;
+pub mod mmm01;
+pub mod rom_only;
+pub mod rtc_footer;
+pub mod save_file;
+
+use std::{any::Any, fs, path::Path};
+
+use crate::{emulator_error::EmulatorError, patch, power_on::PowerOnFill};
+
+use self::{
+    camera::PocketCamera,
+    header::{CartridgeHeader, MapperKind},
+    huc1::HuC1,
+    huc3::HuC3,
+    infrared::InfraredPort,
+    mapper::Mapper,
+    mbc5::Mbc5,
+    mbc7::Mbc7,
+    mmm01::Mmm01,
+    rom_only::RomOnly,
+    save_file::SaveFile,
+};
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// A cartridge with a save file attached writes its battery data back when
+/// it is dropped, so shutting down never loses progress.
+pub struct Cartridge {
+    header: CartridgeHeader,
+    mapper: Box<dyn Mapper>,
+    save_file: Option<SaveFile>,
+}
+
+impl Cartridge {
+    pub fn build(rom: Vec<u8>) -> Result<Cartridge, EmulatorError> {
+        let header = CartridgeHeader::detect(&rom)?;
+        let cartridge_type = header.cartridge_type;
+        let ram_size = if cartridge_type.has_ram {
+            header.ram_size
+        } else {
+            0
+        };
+
+        let mapper: Box<dyn Mapper> = match cartridge_type.mapper {
+            MapperKind::RomOnly => Box::new(RomOnly::new(rom, ram_size)),
+            MapperKind::Mbc5 => Box::new(Mbc5::new(rom, ram_size, cartridge_type.has_rumble)),
+            MapperKind::Mmm01 => Box::new(Mmm01::new(rom, ram_size)),
+            MapperKind::Mbc7 => Box::new(Mbc7::new(rom)),
+            MapperKind::PocketCamera => Box::new(PocketCamera::new(rom, ram_size)),
+            MapperKind::HuC1 => Box::new(HuC1::new(rom, ram_size)),
+            MapperKind::HuC3 => Box::new(HuC3::new(rom, ram_size)),
+        };
+
+        Ok(Cartridge::with_mapper(header, mapper))
+    }
+
+    /// Builds a cartridge around a mapper the header doesn't describe, such
+    /// as a bootleg or homebrew controller. The header still decides whether
+    /// the battery data is kept.
+    pub fn with_mapper(header: CartridgeHeader, mapper: Box<dyn Mapper>) -> Cartridge {
+        Cartridge {
+            header,
+            mapper,
+            save_file: None,
+        }
+    }
+
+    /// Reads a ROM from disk and applies the IPS, BPS or UPS patch sitting
+    /// next to it, if any. Then fills the cartridge RAM as `fill` describes
+    /// and, for cartridges with a battery, restores the `.sav` file.
+    pub fn open(path: &Path, fill: &PowerOnFill) -> Result<Cartridge, EmulatorError> {
+        let mut rom = fs::read(path)?;
+        if let Some(patch_path) = patch::find_patch(path) {
+            rom = patch::apply_patch(&rom, &fs::read(patch_path)?)?;
+        }
+
+        let mut cartridge = Cartridge::build(rom)?;
+        cartridge.power_on(fill);
+        cartridge.attach_save_file(SaveFile::new(path.with_extension("sav")))?;
+        Ok(cartridge)
+    }
+
+    /// Loads the battery data from the save file, if it exists, and keeps
+    /// the file up to date from then on. Ignored when there is no battery.
+    pub fn attach_save_file(&mut self, mut save_file: SaveFile) -> Result<(), EmulatorError> {
+        if !self.header.cartridge_type.has_battery {
+            return Ok(());
+        }
+        if let Some(data) = save_file.load()? {
+            self.load_battery_data(&data);
+        }
+        self.save_file = Some(save_file);
+        Ok(())
+    }
+
+    /// Sets the cartridge RAM to its power-on contents. Battery data loaded
+    /// afterwards replaces them.
+    pub fn power_on(&mut self, fill: &PowerOnFill) {
+        self.mapper.power_on(fill);
+    }
+
+    pub fn save_file_mut(&mut self) -> Option<&mut SaveFile> {
+        self.save_file.as_mut()
+    }
+
+    /// Writes the battery data to the save file right away, unless it
+    /// hasn't changed since the last write
+    pub fn flush_save(&mut self) -> Result<(), EmulatorError> {
+        let mapper = &self.mapper;
+        match self.save_file.as_mut() {
+            Some(save_file) if self.header.cartridge_type.has_battery => {
+                save_file.write_state(&mapper.battery_state(), || mapper.battery_data())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// Reads from the ROM area, 0x0000-0x7FFF
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.mapper.read_rom(address)
+    }
+
+    /// Writes to the ROM area, which land on the controller's registers
+    pub fn write_rom(&mut self, address: u16, new_value: u8) {
+        self.mapper.write_control(address, new_value);
+    }
+
+    /// Reads from the external RAM area, 0xA000-0xBFFF
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mapper.read_ram(address)
+    }
+
+    pub fn write_ram(&mut self, address: u16, new_value: u8) {
+        self.mapper.write_ram(address, new_value);
+    }
+
+    pub fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        self.mapper.poke_ram(bank, address, new_value);
+    }
+
+    /// Errors from periodic flushes are dropped, the next flush tries again
+    pub fn tick(&mut self, cycles: u32) {
+        self.mapper.tick(cycles);
+
+        let flush_due = self
+            .save_file
+            .as_mut()
+            .is_some_and(|save_file| save_file.tick(cycles));
+        if flush_due {
+            let _ = self.flush_save();
+        }
+    }
+
+    /// Contents that survive a power cycle, `None` when the cartridge has no battery
+    pub fn battery_data(&self) -> Option<Vec<u8>> {
+        if !self.header.cartridge_type.has_battery {
+            return None;
+        }
+        Some(self.mapper.battery_data())
+    }
+
+    pub fn load_battery_data(&mut self, data: &[u8]) {
+        self.mapper.load_battery_data(data);
+    }
+
+    /// Gives access to the mapper as its concrete type, `None` when the
+    /// cartridge uses a different one
+    pub fn mapper_mut<T: Mapper>(&mut self) -> Option<&mut T> {
+        let mapper: &mut dyn Any = self.mapper.as_mut();
+        mapper.downcast_mut::<T>()
+    }
+
+    /// IR port of the Hudson cartridges that have one
+    pub fn infrared_mut(&mut self) -> Option<&mut InfraredPort> {
+        let mapper: &mut dyn Any = self.mapper.as_mut();
+        if mapper.is::<HuC1>() {
+            return mapper.downcast_mut::<HuC1>().map(HuC1::infrared_mut);
+        }
+        mapper.downcast_mut::<HuC3>().map(HuC3::infrared_mut)
+    }
+}
+
+impl Drop for Cartridge {
+    fn drop(&mut self) {
+        let _ = self.flush_save();
+    }
+}
+
+/// Translates an address inside a banked window into an index of `data`,
+/// wrapping bank numbers that go past the end of the chip.
+pub(crate) fn bank_offset(len: usize, bank_size: usize, bank: usize, address: u16) -> usize {
+    (bank * bank_size + address as usize % bank_size) % len
+}
+
+/// Writes into any bank of a RAM chip, for `Mapper::poke_ram`
+pub(crate) fn poke_banked_ram(ram: &mut [u8], bank: u8, address: u16, new_value: u8) {
+    if ram.is_empty() {
+        return;
+    }
+    let bank_size = RAM_BANK_SIZE.min(ram.len());
+    let offset = bank_offset(ram.len(), bank_size, bank as usize, address);
+    ram[offset] = new_value;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn rom_with_type(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let banks = 2 << rom_size;
+        let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+        for bank in 0..banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        rom[0x0147] = cartridge_type;
+        rom[0x0148] = rom_size;
+        rom[0x0149] = ram_size;
+        rom
+    }
+
+    /// Wisdom Tree style bootleg: any write to the ROM area switches the
+    /// whole 32 KiB window to the bank in the low bits of the address
+    struct WisdomTree {
+        rom: Vec<u8>,
+        bank: usize,
+    }
+
+    impl Mapper for WisdomTree {
+        fn read_rom(&self, address: u16) -> u8 {
+            self.rom[(self.bank * 2 * ROM_BANK_SIZE + address as usize) % self.rom.len()]
+        }
+
+        fn write_control(&mut self, address: u16, _new_value: u8) {
+            self.bank = address as usize & 0xFF;
+        }
+
+        fn read_ram(&self, _address: u16) -> u8 {
+            0xFF
+        }
+
+        fn write_ram(&mut self, _address: u16, _new_value: u8) {}
+    }
+
+    #[test]
+    fn should_build_mbc5_cartridge_from_header() {
+        let mut cartridge = Cartridge::build(rom_with_type(0x1E, 0x02, 0x03)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x05);
+
+        assert_eq!(cartridge.read_rom(0x4000), 0x05);
+        assert!(cartridge.mapper_mut::<Mbc5>().is_some());
+    }
+
+    #[test]
+    fn should_not_give_ram_to_cartridges_without_it() {
+        let mut cartridge = Cartridge::build(rom_with_type(0x19, 0x02, 0x03)).unwrap();
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn should_ignore_register_writes_on_rom_only_cartridges() {
+        let mut cartridge = Cartridge::build(rom_with_type(0x00, 0x00, 0x00)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x00);
+
+        assert_eq!(cartridge.read_rom(0x4000), 0x01);
+        assert!(cartridge.mapper_mut::<Mbc5>().is_none());
+    }
+
+    #[test]
+    fn should_expose_battery_data_only_with_battery() {
+        let mut with_battery = Cartridge::build(rom_with_type(0x1B, 0x02, 0x02)).unwrap();
+        let without_battery = Cartridge::build(rom_with_type(0x1A, 0x02, 0x02)).unwrap();
+
+        with_battery.load_battery_data(&[0x42; 0x10]);
+
+        assert_eq!(with_battery.battery_data().unwrap()[0x0F], 0x42);
+        assert_eq!(with_battery.battery_data().unwrap().len(), 0x2000);
+        assert_eq!(without_battery.battery_data(), None);
+    }
+
+    #[test]
+    fn should_use_eeprom_as_mbc7_battery_data() {
+        let mut cartridge = Cartridge::build(rom_with_type(0x22, 0x02, 0x00)).unwrap();
+
+        cartridge.load_battery_data(&[0x12; 0x100]);
+
+        assert!(cartridge.mapper_mut::<Mbc7>().is_some());
+        assert_eq!(cartridge.battery_data(), Some(vec![0x12; 0x100]));
+    }
+
+    #[test]
+    fn should_build_mmm01_from_menu_header() {
+        let mut rom = rom_with_type(0x01, 0x03, 0x00);
+        let menu_start = rom.len() - 0x8000;
+        rom[menu_start + 0x0147] = 0x0B;
+        rom[menu_start + 0x0148] = 0x03;
+
+        let cartridge = Cartridge::build(rom).unwrap();
+
+        assert_eq!(cartridge.read_rom(0x0000), 14);
+        assert_eq!(cartridge.read_rom(0x4000), 15);
+    }
+
+    #[test]
+    fn should_capture_host_image_loaded_from_pgm() {
+        let mut pgm = b"P5 128 112 255\n".to_vec();
+        pgm.extend_from_slice(&[0xFF; 128 * 112]);
+        let image = camera::pgm::parse_pgm(&pgm).unwrap();
+        let mut cartridge = Cartridge::build(rom_with_type(0xFC, 0x02, 0x04)).unwrap();
+        cartridge
+            .mapper_mut::<PocketCamera>()
+            .unwrap()
+            .set_image(&image)
+            .unwrap();
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x10);
+        cartridge.write_ram(0xA000, 0x01);
+        cartridge.tick(200_000);
+        cartridge.write_rom(0x4000, 0x00);
+
+        // Thresholds are all zero so the white picture comes out as shade 0
+        assert_eq!(cartridge.read_ram(0xA100), 0x00);
+        assert!(!cartridge.mapper_mut::<PocketCamera>().unwrap().capturing());
+    }
+
+    #[test]
+    fn should_loop_infrared_back_between_cartridges() {
+        let mut sender = Cartridge::build(rom_with_type(0xFF, 0x02, 0x02)).unwrap();
+        let mut receiver = Cartridge::build(rom_with_type(0xFE, 0x02, 0x03)).unwrap();
+        sender.write_rom(0x0000, 0x0E);
+        receiver.write_rom(0x0000, 0x0E);
+
+        sender.write_ram(0xA000, 0x01);
+        let led_on = sender.infrared_mut().unwrap().led_on();
+        receiver.infrared_mut().unwrap().set_light_detected(led_on);
+
+        assert_eq!(receiver.read_ram(0xA000), 0xC1);
+    }
+
+    #[test]
+    fn should_keep_huc3_clock_in_battery_data() {
+        let mut cartridge = Cartridge::build(rom_with_type(0xFE, 0x02, 0x03)).unwrap();
+        cartridge
+            .mapper_mut::<HuC3>()
+            .unwrap()
+            .clock_mut()
+            .set(61, 2);
+
+        let data = cartridge.battery_data().unwrap();
+
+        assert_eq!(data.len(), 0x8000 + rtc_footer::RTC_FOOTER_SIZE);
+        assert_eq!(
+            &data[0x8000..0x8010],
+            &[0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn should_dispatch_to_custom_mapper() {
+        let rom = rom_with_type(0x00, 0x03, 0x00);
+        let header = CartridgeHeader::build(&rom).unwrap();
+        let mapper = WisdomTree { rom, bank: 0 };
+        let mut cartridge = Cartridge::with_mapper(header, Box::new(mapper));
+
+        cartridge.write_rom(0x0003, 0x00);
+
+        assert_eq!(cartridge.read_rom(0x0000), 6);
+        assert_eq!(cartridge.read_rom(0x4000), 7);
+        assert_eq!(cartridge.mapper_mut::<WisdomTree>().unwrap().bank, 3);
+        assert!(cartridge.mapper_mut::<Mbc5>().is_none());
+    }
+
+    #[test]
+    fn should_restore_and_flush_save_next_to_rom() {
+        let dir = save_file::tests::scratch_dir("cartridge-save");
+        let rom_path = dir.join("game.gb");
+        fs::write(&rom_path, rom_with_type(0x1B, 0x02, 0x02)).unwrap();
+        fs::write(dir.join("game.sav"), [0x42; 0x10]).unwrap();
+
+        let mut cartridge = Cartridge::open(&rom_path, &PowerOnFill::default()).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        assert_eq!(cartridge.read_ram(0xA00F), 0x42);
+
+        cartridge.write_ram(0xA000, 0x24);
+        drop(cartridge);
+
+        let save = fs::read(dir.join("game.sav")).unwrap();
+        assert_eq!(save.len(), 0x2000);
+        assert_eq!(&save[..2], &[0x24, 0x42]);
+    }
+
+    #[test]
+    fn should_flush_save_periodically() {
+        let dir = save_file::tests::scratch_dir("periodic-save");
+        let mut cartridge = Cartridge::build(rom_with_type(0x1B, 0x02, 0x02)).unwrap();
+        let mut save_file = SaveFile::new(dir.join("game.sav"));
+        save_file.set_flush_interval(Some(1000));
+        cartridge.attach_save_file(save_file).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x24);
+
+        cartridge.tick(999);
+        assert!(!dir.join("game.sav").exists());
+        cartridge.tick(1);
+
+        assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 0x24);
+    }
+
+    #[test]
+    fn should_not_rewrite_huc3_save_while_nothing_changes() {
+        let dir = save_file::tests::scratch_dir("huc3-periodic-save");
+        let path = dir.join("game.sav");
+        let mut cartridge = Cartridge::build(rom_with_type(0xFE, 0x02, 0x03)).unwrap();
+        let mut save_file = SaveFile::new(path.clone());
+        save_file.set_flush_interval(Some(save_file::CYCLES_PER_SECOND));
+        cartridge.attach_save_file(save_file).unwrap();
+
+        cartridge.tick(save_file::CYCLES_PER_SECOND as u32);
+        assert_eq!(
+            fs::read(&path).unwrap().len(),
+            0x8000 + rtc_footer::RTC_FOOTER_SIZE
+        );
+        fs::write(&path, b"untouched").unwrap();
+        cartridge.tick(save_file::CYCLES_PER_SECOND as u32);
+        cartridge.tick(save_file::CYCLES_PER_SECOND as u32);
+
+        assert_eq!(fs::read(&path).unwrap(), b"untouched");
+    }
+
+    #[test]
+    fn should_not_create_save_without_battery() {
+        let dir = save_file::tests::scratch_dir("no-battery-save");
+        let rom_path = dir.join("game.gb");
+        fs::write(&rom_path, rom_with_type(0x1A, 0x02, 0x02)).unwrap();
+
+        let mut cartridge = Cartridge::open(&rom_path, &PowerOnFill::default()).unwrap();
+        cartridge.flush_save().unwrap();
+        drop(cartridge);
+
+        assert!(!dir.join("game.sav").exists());
+    }
+
+    #[test]
+    fn should_fill_cartridge_ram_before_loading_save() {
+        let dir = save_file::tests::scratch_dir("power-on-save");
+        let rom_path = dir.join("game.gb");
+        fs::write(&rom_path, rom_with_type(0x1B, 0x02, 0x02)).unwrap();
+        fs::write(dir.join("game.sav"), [0x42; 0x10]).unwrap();
+        let fill = PowerOnFill::new(crate::power_on::FillPattern::Ones, 0);
+
+        let mut cartridge = Cartridge::open(&rom_path, &fill).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+
+        assert_eq!(cartridge.read_ram(0xA00F), 0x42);
+        assert_eq!(cartridge.read_ram(0xA010), 0xFF);
+    }
+
+    #[test]
+    fn should_apply_patch_next_to_rom() {
+        let dir = save_file::tests::scratch_dir("patched-rom");
+        let rom_path = dir.join("game.gb");
+        fs::write(&rom_path, rom_with_type(0x00, 0x00, 0x00)).unwrap();
+        // Turns the cartridge into an MBC5 one and marks bank 0
+        fs::write(
+            dir.join("game.ips"),
+            b"PATCH\x00\x01\x47\x00\x01\x19\x00\x00\x00\x00\x01\x42EOF",
+        )
+        .unwrap();
+
+        let cartridge = Cartridge::open(&rom_path, &PowerOnFill::default()).unwrap();
+
+        assert_eq!(cartridge.header().cartridge_type.mapper, MapperKind::Mbc5);
+        assert_eq!(cartridge.read_rom(0x0000), 0x42);
+    }
+}
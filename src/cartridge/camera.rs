@@ -0,0 +1,267 @@
+pub mod pgm;
+pub mod sensor;
+
+use crate::{
+    emulator_error::EmulatorError,
+    power_on::{MemoryRegion, PowerOnFill},
+};
+
+use self::{
+    pgm::GrayImage,
+    sensor::{IMAGE_HEIGHT, IMAGE_WIDTH, REGISTER_COUNT},
+};
+
+use super::{bank_offset, mapper::Mapper, poke_banked_ram, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// Selecting this RAM bank maps the sensor registers instead of RAM
+const REGISTER_BANK_BIT: u8 = 0x10;
+/// Captures land in RAM bank 0 right after the 0x100 bytes of game state
+const IMAGE_RAM_OFFSET: usize = 0x100;
+
+/// Game Boy Camera (Pocket Camera) cartridge: an MBC with 128 KiB of RAM
+/// plus the M64282FP image sensor, whose registers replace RAM bank 0x10.
+/// Pictures come from an image supplied by the host.
+pub struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    registers: [u8; REGISTER_COUNT],
+    /// Cycles left until the capture in progress completes
+    capture_countdown: Option<u32>,
+    image: Vec<u8>,
+}
+
+impl PocketCamera {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> PocketCamera {
+        PocketCamera {
+            rom,
+            ram: vec![0x00; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers: [0x00; REGISTER_COUNT],
+            capture_countdown: None,
+            image: vec![0x00; IMAGE_WIDTH * IMAGE_HEIGHT],
+        }
+    }
+
+    /// Sets the 128x112 grayscale picture in front of the sensor
+    pub fn set_image(&mut self, image: &GrayImage) -> Result<(), EmulatorError> {
+        if image.width != IMAGE_WIDTH || image.height != IMAGE_HEIGHT {
+            return Err(EmulatorError::InvalidImageSize(image.width, image.height));
+        }
+        self.image.copy_from_slice(&image.pixels);
+        Ok(())
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.capture_countdown.is_some()
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn write_register(&mut self, register: usize, new_value: u8) {
+        if register >= REGISTER_COUNT {
+            return;
+        }
+        if register == 0 {
+            // Captures can't be cancelled once started
+            let start = new_value & 0x01 != 0;
+            if start && self.capture_countdown.is_none() {
+                self.capture_countdown = Some(sensor::capture_cycles(&self.registers));
+            }
+            self.registers[0] = (new_value & 0x07) | self.capture_countdown.is_some() as u8;
+        } else {
+            self.registers[register] = new_value;
+        }
+    }
+
+    fn finish_capture(&mut self) {
+        self.capture_countdown = None;
+        self.registers[0] &= !0x01;
+        if self.ram.len() < IMAGE_RAM_OFFSET + sensor::TILE_DATA_SIZE {
+            return;
+        }
+
+        let shades = sensor::capture(&self.image, &self.registers);
+        let tiles = sensor::encode_tiles(&shades);
+        self.ram[IMAGE_RAM_OFFSET..IMAGE_RAM_OFFSET + tiles.len()].copy_from_slice(&tiles);
+    }
+
+    fn registers_mapped(&self) -> bool {
+        self.ram_bank & REGISTER_BANK_BIT != 0
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank_size = RAM_BANK_SIZE.min(self.ram.len());
+        bank_offset(self.ram.len(), bank_size, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for PocketCamera {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = new_value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = new_value & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = new_value & 0x1F,
+            _ => (),
+        }
+    }
+
+    /// RAM can be read even while disabled, only writes need enabling
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.registers_mapped() {
+            // Only the capture register can be read back
+            return match address & 0x7F {
+                0x00 => self.registers[0],
+                _ => 0x00,
+            };
+        }
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if self.registers_mapped() {
+            self.write_register((address & 0x7F) as usize, new_value);
+            return;
+        }
+        if !self.ram_enabled || self.ram.is_empty() {
+            return;
+        }
+        let offset = self.ram_offset(address);
+        self.ram[offset] = new_value;
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        if let Some(countdown) = self.capture_countdown {
+            if countdown <= cycles {
+                self.finish_capture();
+            } else {
+                self.capture_countdown = Some(countdown - cycles);
+            }
+        }
+    }
+
+    fn battery_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> PocketCamera {
+        let mut camera = PocketCamera::new(vec![0x00; 4 * ROM_BANK_SIZE], 16 * RAM_BANK_SIZE);
+        camera.write_control(0x0000, 0x0A);
+        camera.write_control(0x4000, 0x10);
+        // N set, unity gain and exposure, thresholds 0x40/0x80/0xC0
+        camera.write_ram(0xA001, 0x84);
+        camera.write_ram(0xA002, 0x10);
+        for register in 0..16 {
+            let address = 0xA006 + register * 3;
+            camera.write_ram(address, 0x40);
+            camera.write_ram(address + 1, 0x80);
+            camera.write_ram(address + 2, 0xC0);
+        }
+        camera
+    }
+
+    fn left_half_white() -> GrayImage {
+        let pixels = (0..IMAGE_WIDTH * IMAGE_HEIGHT)
+            .map(|index| if index % IMAGE_WIDTH < 64 { 0xFF } else { 0x00 })
+            .collect();
+        GrayImage {
+            width: IMAGE_WIDTH,
+            height: IMAGE_HEIGHT,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn should_store_capture_in_ram_after_capture_time() {
+        let mut camera = camera();
+        camera.set_image(&left_half_white()).unwrap();
+
+        camera.write_ram(0xA000, 0x01);
+        assert_eq!(camera.read_ram(0xA000) & 0x01, 0x01);
+
+        // Base time plus 64 cycles per exposure step
+        camera.tick(129_792 + 0x1000 * 64 - 1);
+        assert!(camera.capturing());
+        camera.tick(1);
+        assert!(!camera.capturing());
+        assert_eq!(camera.read_ram(0xA000) & 0x01, 0x00);
+
+        camera.write_control(0x4000, 0x00);
+        // First tile is white, tile 8 starts the black half
+        assert_eq!(camera.read_ram(0xA100), 0x00);
+        assert_eq!(camera.read_ram(0xA101), 0x00);
+        assert_eq!(camera.read_ram(0xA180), 0xFF);
+        assert_eq!(camera.read_ram(0xA181), 0xFF);
+    }
+
+    #[test]
+    fn should_only_expose_capture_register() {
+        let mut camera = camera();
+
+        assert_eq!(camera.read_ram(0xA001), 0x00);
+        camera.write_ram(0xA000, 0x06);
+        assert_eq!(camera.read_ram(0xA080), 0x06);
+    }
+
+    #[test]
+    fn should_keep_ram_readable_while_disabled() {
+        let mut camera = camera();
+        camera.write_control(0x4000, 0x01);
+        camera.write_ram(0xA000, 0x42);
+
+        camera.write_control(0x0000, 0x00);
+        camera.write_ram(0xA000, 0x24);
+
+        assert_eq!(camera.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn should_reject_images_of_wrong_size() {
+        let mut camera = camera();
+        let image = GrayImage {
+            width: 160,
+            height: 144,
+            pixels: vec![0x00; 160 * 144],
+        };
+
+        assert_eq!(
+            camera.set_image(&image),
+            Err(EmulatorError::InvalidImageSize(160, 144))
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use crate::emulator_error::EmulatorError;
+
+/// 8 bit grayscale image, 0 is black and 255 is white
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrayImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Parses a binary (P5) or plain (P2) portable graymap. Samples are
+/// rescaled to 0-255 whatever the file's maximum value is.
+pub fn parse_pgm(bytes: &[u8]) -> Result<GrayImage, EmulatorError> {
+    let mut cursor = 0;
+    let magic = next_token(bytes, &mut cursor)?;
+    let binary = match magic {
+        b"P5" => true,
+        b"P2" => false,
+        _ => return Err(EmulatorError::InvalidPgm),
+    };
+    let width = next_number(bytes, &mut cursor)?;
+    let height = next_number(bytes, &mut cursor)?;
+    let max_value = next_number(bytes, &mut cursor)?;
+    if max_value == 0 || max_value > 0xFFFF {
+        return Err(EmulatorError::InvalidPgm);
+    }
+
+    let count = width.checked_mul(height).ok_or(EmulatorError::InvalidPgm)?;
+    let samples: Vec<usize> = if binary {
+        // A single whitespace byte separates the header from the raster
+        let raster = bytes.get(cursor + 1..).ok_or(EmulatorError::InvalidPgm)?;
+        let sample_size = if max_value > 0xFF { 2 } else { 1 };
+        if count
+            .checked_mul(sample_size)
+            .is_none_or(|length| raster.len() < length)
+        {
+            return Err(EmulatorError::InvalidPgm);
+        }
+        raster
+            .chunks_exact(sample_size)
+            .take(count)
+            .map(|sample| {
+                sample
+                    .iter()
+                    .fold(0, |value, byte| value << 8 | *byte as usize)
+            })
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| next_number(bytes, &mut cursor))
+            .collect::<Result<_, _>>()?
+    };
+
+    let pixels = samples
+        .into_iter()
+        .map(|sample| (sample.min(max_value) * 0xFF / max_value) as u8)
+        .collect();
+
+    Ok(GrayImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn next_token<'a>(bytes: &'a [u8], cursor: &mut usize) -> Result<&'a [u8], EmulatorError> {
+    while *cursor < bytes.len() {
+        match bytes[*cursor] {
+            b'#' => {
+                while *cursor < bytes.len() && bytes[*cursor] != b'\n' {
+                    *cursor += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *cursor += 1,
+            _ => break,
+        }
+    }
+
+    let start = *cursor;
+    while *cursor < bytes.len() && !bytes[*cursor].is_ascii_whitespace() {
+        *cursor += 1;
+    }
+
+    if start == *cursor {
+        Err(EmulatorError::InvalidPgm)
+    } else {
+        Ok(&bytes[start..*cursor])
+    }
+}
+
+fn next_number(bytes: &[u8], cursor: &mut usize) -> Result<usize, EmulatorError> {
+    std::str::from_utf8(next_token(bytes, cursor)?)
+        .ok()
+        .and_then(|token| token.parse().ok())
+        .ok_or(EmulatorError::InvalidPgm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_binary_pgm() {
+        let mut bytes = b"P5\n# made by hand\n2 2\n255\n".to_vec();
+        bytes.extend_from_slice(&[0x00, 0x40, 0x80, 0xFF]);
+
+        let image = parse_pgm(&bytes).unwrap();
+
+        assert_eq!(image.width, 2);
+        assert_eq!(image.height, 2);
+        assert_eq!(image.pixels, vec![0x00, 0x40, 0x80, 0xFF]);
+    }
+
+    #[test]
+    fn should_parse_plain_pgm_and_rescale() {
+        let image = parse_pgm(b"P2 3 1 15\n0 15\n5").unwrap();
+
+        assert_eq!(image.pixels, vec![0x00, 0xFF, 0x55]);
+    }
+
+    #[test]
+    fn should_parse_sixteen_bit_samples() {
+        let mut bytes = b"P5 1 1 65535\n".to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xFF]);
+
+        let image = parse_pgm(&bytes).unwrap();
+
+        assert_eq!(image.pixels, vec![0xFF]);
+    }
+
+    #[test]
+    fn should_reject_other_formats() {
+        assert_eq!(
+            parse_pgm(b"P6 1 1 255\n\0\0\0"),
+            Err(EmulatorError::InvalidPgm)
+        );
+    }
+
+    #[test]
+    fn should_reject_truncated_raster() {
+        assert_eq!(
+            parse_pgm(b"P5 2 2 255\n\0\0"),
+            Err(EmulatorError::InvalidPgm)
+        );
+    }
+
+    #[test]
+    fn should_reject_oversized_header() {
+        for header in [
+            b"P5 4294967296 4294967296 255\n\0".as_slice(),
+            b"P5 9223372036854775808 1 65535\n\0\0",
+            b"P2 4294967296 4294967296 255\n0",
+        ] {
+            assert_eq!(parse_pgm(header), Err(EmulatorError::InvalidPgm));
+        }
+    }
+}
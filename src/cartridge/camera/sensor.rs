@@ -0,0 +1,217 @@
+pub const IMAGE_WIDTH: usize = 128;
+pub const IMAGE_HEIGHT: usize = 112;
+pub const REGISTER_COUNT: usize = 0x36;
+/// Size of the captured picture once stored as 16 x 14 tiles
+pub const TILE_DATA_SIZE: usize = IMAGE_WIDTH * IMAGE_HEIGHT / 4;
+
+const GAIN_REGISTER: usize = 0x01;
+const EXPOSURE_HIGH_REGISTER: usize = 0x02;
+const EXPOSURE_LOW_REGISTER: usize = 0x03;
+const EDGE_REGISTER: usize = 0x04;
+const DITHER_MATRIX_START: usize = 0x06;
+
+const N_BIT: u8 = 0b1000_0000;
+const INVERT_BIT: u8 = 0b0000_1000;
+/// Exposure register value that leaves the input brightness untouched
+const UNITY_EXPOSURE: f64 = 0x1000 as f64;
+
+/// Output gain selected by bits 0-4 of register 1
+const GAIN_VALUES: [f64; 32] = [
+    0.8809390, 0.9149149, 0.9457498, 0.9739758, 1.0000000, 1.0241412, 1.0466537, 1.0677433,
+    1.0875793, 1.1240310, 1.1568911, 1.1868043, 1.2142561, 1.2396208, 1.2743837, 1.3157323,
+    1.3525190, 1.3856512, 1.4157897, 1.4434309, 1.4689574, 1.4926697, 1.5148087, 1.5355703,
+    1.5551159, 1.5735801, 1.5910762, 1.6077008, 1.6235366, 1.6386550, 1.6531183, 1.6669808,
+];
+/// Edge enhancement strength selected by bits 4-6 of register 4
+const EDGE_RATIOS: [f64; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// Clock cycles between starting a capture and the picture being in RAM
+pub fn capture_cycles(registers: &[u8; REGISTER_COUNT]) -> u32 {
+    let exposure = exposure(registers) as u32;
+    let n_penalty = if registers[GAIN_REGISTER] & N_BIT != 0 {
+        0
+    } else {
+        2048
+    };
+    129_792 + n_penalty + exposure * 64
+}
+
+/// Runs the image through the M64282FP processing chain: gain, exposure,
+/// optional inversion and edge enhancement, and finally the 4x4 dither
+/// matrix that turns every pixel into one of four shades, 0 being white.
+pub fn capture(image: &[u8], registers: &[u8; REGISTER_COUNT]) -> Vec<u8> {
+    let gain = GAIN_VALUES[(registers[GAIN_REGISTER] & 0x1F) as usize];
+    let exposure = exposure(registers) as f64 / UNITY_EXPOSURE;
+    let invert = registers[EDGE_REGISTER] & INVERT_BIT != 0;
+    let edge_ratio = EDGE_RATIOS[((registers[EDGE_REGISTER] >> 4) & 0x07) as usize];
+    let (horizontal_edges, vertical_edges) = match (registers[GAIN_REGISTER] >> 5) & 0x03 {
+        0b01 => (true, false),
+        0b10 => (false, true),
+        0b11 => (true, true),
+        _ => (false, false),
+    };
+
+    let sample = |x: isize, y: isize| -> f64 {
+        let x = x.clamp(0, IMAGE_WIDTH as isize - 1) as usize;
+        let y = y.clamp(0, IMAGE_HEIGHT as isize - 1) as usize;
+        let pixel = image[y * IMAGE_WIDTH + x];
+        let pixel = if invert { 0xFF - pixel } else { pixel };
+        pixel as f64 * gain * exposure
+    };
+
+    let mut shades = Vec::with_capacity(IMAGE_WIDTH * IMAGE_HEIGHT);
+    for y in 0..IMAGE_HEIGHT as isize {
+        for x in 0..IMAGE_WIDTH as isize {
+            let center = sample(x, y);
+            let mut color = center;
+            if horizontal_edges {
+                color += (2.0 * center - sample(x - 1, y) - sample(x + 1, y)) * edge_ratio;
+            }
+            if vertical_edges {
+                color += (2.0 * center - sample(x, y - 1) - sample(x, y + 1)) * edge_ratio;
+            }
+
+            let matrix = DITHER_MATRIX_START + (((x & 3) + (y & 3) * 4) * 3) as usize;
+            let shade = if color < registers[matrix] as f64 {
+                3
+            } else if color < registers[matrix + 1] as f64 {
+                2
+            } else if color < registers[matrix + 2] as f64 {
+                1
+            } else {
+                0
+            };
+            shades.push(shade);
+        }
+    }
+    shades
+}
+
+/// Packs the shades into 2bpp tiles, 16 per row, the way the camera
+/// leaves them in cartridge RAM
+pub fn encode_tiles(shades: &[u8]) -> Vec<u8> {
+    let mut tiles = vec![0x00; TILE_DATA_SIZE];
+    for (index, shade) in shades.iter().enumerate() {
+        let (x, y) = (index % IMAGE_WIDTH, index / IMAGE_WIDTH);
+        let tile = (y / 8) * (IMAGE_WIDTH / 8) + x / 8;
+        let row = tile * 16 + (y % 8) * 2;
+        let bit = 7 - (x % 8);
+        tiles[row] |= (shade & 0x01) << bit;
+        tiles[row + 1] |= ((shade >> 1) & 0x01) << bit;
+    }
+    tiles
+}
+
+fn exposure(registers: &[u8; REGISTER_COUNT]) -> u16 {
+    (registers[EXPOSURE_HIGH_REGISTER] as u16) << 8 | registers[EXPOSURE_LOW_REGISTER] as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Unity gain and exposure with thresholds at 0x40, 0x80 and 0xC0 everywhere
+    fn plain_registers() -> [u8; REGISTER_COUNT] {
+        let mut registers = [0x00; REGISTER_COUNT];
+        registers[GAIN_REGISTER] = 0x04;
+        registers[EXPOSURE_HIGH_REGISTER] = 0x10;
+        for threshold in 0..16 {
+            let base = DITHER_MATRIX_START + threshold * 3;
+            registers[base..base + 3].copy_from_slice(&[0x40, 0x80, 0xC0]);
+        }
+        registers
+    }
+
+    #[rstest]
+    #[case(0x00, 3)]
+    #[case(0x50, 2)]
+    #[case(0x90, 1)]
+    #[case(0xFF, 0)]
+    fn should_dither_flat_image(#[case] pixel: u8, #[case] expected_shade: u8) {
+        let image = vec![pixel; IMAGE_WIDTH * IMAGE_HEIGHT];
+
+        let shades = capture(&image, &plain_registers());
+
+        assert!(shades.iter().all(|shade| *shade == expected_shade));
+    }
+
+    #[test]
+    fn should_scale_with_exposure() {
+        let image = vec![0x60; IMAGE_WIDTH * IMAGE_HEIGHT];
+        let mut registers = plain_registers();
+        registers[EXPOSURE_HIGH_REGISTER] = 0x20;
+
+        let shades = capture(&image, &registers);
+
+        assert_eq!(shades[0], 0);
+    }
+
+    #[test]
+    fn should_invert_output() {
+        let image = vec![0x00; IMAGE_WIDTH * IMAGE_HEIGHT];
+        let mut registers = plain_registers();
+        registers[EDGE_REGISTER] = INVERT_BIT;
+
+        let shades = capture(&image, &registers);
+
+        assert_eq!(shades[0], 0);
+    }
+
+    #[test]
+    fn should_use_dither_matrix_per_position() {
+        let image = vec![0x50; IMAGE_WIDTH * IMAGE_HEIGHT];
+        let mut registers = plain_registers();
+        // Pixel (1, 0) uses the second threshold triplet
+        registers[DITHER_MATRIX_START + 3..DITHER_MATRIX_START + 6]
+            .copy_from_slice(&[0x10, 0x20, 0x30]);
+
+        let shades = capture(&image, &registers);
+
+        assert_eq!(&shades[0..2], &[2, 0]);
+        assert_eq!(shades[5], 0);
+    }
+
+    #[test]
+    fn should_enhance_edges() {
+        let mut image = vec![0x80; IMAGE_WIDTH * IMAGE_HEIGHT];
+        image[IMAGE_WIDTH + 1] = 0xA0;
+        let mut registers = plain_registers();
+        registers[GAIN_REGISTER] |= 0x60;
+        registers[EDGE_REGISTER] = 0x20;
+
+        let shades = capture(&image, &registers);
+
+        // The bright pixel gets brighter and its neighbours darker
+        assert_eq!(shades[IMAGE_WIDTH + 1], 0);
+        assert_eq!(shades[IMAGE_WIDTH], 2);
+        assert_eq!(shades[IMAGE_WIDTH + 5], 1);
+    }
+
+    #[test]
+    fn should_encode_tiles_row_by_row() {
+        let mut shades = vec![0; IMAGE_WIDTH * IMAGE_HEIGHT];
+        shades[0] = 3;
+        shades[9] = 1;
+        shades[8 * IMAGE_WIDTH + 7] = 2;
+
+        let tiles = encode_tiles(&shades);
+
+        assert_eq!(&tiles[0..2], &[0x80, 0x80]);
+        assert_eq!(&tiles[16..18], &[0x40, 0x00]);
+        assert_eq!(&tiles[16 * 16..16 * 16 + 2], &[0x00, 0x01]);
+    }
+
+    #[rstest]
+    #[case(0x00, 0x0000, 131_840)]
+    #[case(0x80, 0x0000, 129_792)]
+    #[case(0x80, 0x0100, 146_176)]
+    fn should_time_capture(#[case] gain: u8, #[case] exposure: u16, #[case] expected: u32) {
+        let mut registers = plain_registers();
+        registers[GAIN_REGISTER] = gain;
+        registers[EXPOSURE_HIGH_REGISTER] = (exposure >> 8) as u8;
+        registers[EXPOSURE_LOW_REGISTER] = exposure as u8;
+
+        assert_eq!(capture_cycles(&registers), expected);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::emulator_error::EmulatorError;
+
+const TITLE_START: usize = 0x0134;
+const TITLE_END: usize = 0x0143;
+const CGB_FLAG_ADDRESS: usize = 0x0143;
+const SGB_FLAG_ADDRESS: usize = 0x0146;
+const CARTRIDGE_TYPE_ADDRESS: usize = 0x0147;
+const ROM_SIZE_ADDRESS: usize = 0x0148;
+const RAM_SIZE_ADDRESS: usize = 0x0149;
+const HEADER_END: usize = 0x0150;
+/// MMM01 compilations keep the menu, and the header describing the whole
+/// cartridge, in the last 32 KiB of ROM
+const MMM01_MENU_SIZE: usize = 0x8000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapperKind {
+    RomOnly,
+    Mmm01,
+    Mbc5,
+    Mbc7,
+    PocketCamera,
+    HuC1,
+    HuC3,
+}
+
+/// Decoded meaning of the cartridge type byte at 0x0147
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CartridgeType {
+    pub mapper: MapperKind,
+    pub has_ram: bool,
+    pub has_battery: bool,
+    pub has_rumble: bool,
+    pub has_timer: bool,
+}
+
+impl CartridgeType {
+    pub fn build(byte: u8) -> Result<CartridgeType, EmulatorError> {
+        let (mapper, has_ram, has_battery, has_rumble, has_timer) = match byte {
+            0x00 => (MapperKind::RomOnly, false, false, false, false),
+            0x08 => (MapperKind::RomOnly, true, false, false, false),
+            0x09 => (MapperKind::RomOnly, true, true, false, false),
+            0x0B => (MapperKind::Mmm01, false, false, false, false),
+            0x0C => (MapperKind::Mmm01, true, false, false, false),
+            0x0D => (MapperKind::Mmm01, true, true, false, false),
+            0x19 => (MapperKind::Mbc5, false, false, false, false),
+            0x1A => (MapperKind::Mbc5, true, false, false, false),
+            0x1B => (MapperKind::Mbc5, true, true, false, false),
+            0x1C => (MapperKind::Mbc5, false, false, true, false),
+            0x1D => (MapperKind::Mbc5, true, false, true, false),
+            0x1E => (MapperKind::Mbc5, true, true, true, false),
+            0x22 => (MapperKind::Mbc7, true, true, true, false),
+            0xFC => (MapperKind::PocketCamera, true, true, false, false),
+            0xFE => (MapperKind::HuC3, true, true, false, true),
+            0xFF => (MapperKind::HuC1, true, true, false, false),
+            _ => return Err(EmulatorError::UnsupportedCartridgeType(byte)),
+        };
+
+        Ok(CartridgeType {
+            mapper,
+            has_ram,
+            has_battery,
+            has_rumble,
+            has_timer,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub cgb_flag: u8,
+    pub sgb_flag: u8,
+    pub cartridge_type: CartridgeType,
+    pub rom_banks: usize,
+    pub ram_size: usize,
+}
+
+impl CartridgeHeader {
+    pub fn build(rom: &[u8]) -> Result<CartridgeHeader, EmulatorError> {
+        if rom.len() < HEADER_END {
+            return Err(EmulatorError::RomTooSmall(rom.len()));
+        }
+
+        let title = rom[TITLE_START..TITLE_END]
+            .iter()
+            .take_while(|byte| **byte != 0x00)
+            .map(|byte| *byte as char)
+            .collect();
+
+        Ok(CartridgeHeader {
+            title,
+            cgb_flag: rom[CGB_FLAG_ADDRESS],
+            sgb_flag: rom[SGB_FLAG_ADDRESS],
+            cartridge_type: CartridgeType::build(rom[CARTRIDGE_TYPE_ADDRESS])?,
+            rom_banks: rom_banks(rom[ROM_SIZE_ADDRESS])?,
+            ram_size: ram_size(rom[RAM_SIZE_ADDRESS])?,
+        })
+    }
+
+    /// Finds the header that describes the cartridge. That is the one at the
+    /// start of the ROM, except for MMM01 compilations whose first header
+    /// belongs to the first game on the cartridge.
+    pub fn detect(rom: &[u8]) -> Result<CartridgeHeader, EmulatorError> {
+        let menu_header = rom
+            .len()
+            .checked_sub(MMM01_MENU_SIZE)
+            .and_then(|menu_start| CartridgeHeader::build(&rom[menu_start..]).ok())
+            .filter(|header| header.cartridge_type.mapper == MapperKind::Mmm01);
+
+        match menu_header {
+            Some(header) => Ok(header),
+            None => CartridgeHeader::build(rom),
+        }
+    }
+}
+
+fn rom_banks(byte: u8) -> Result<usize, EmulatorError> {
+    match byte {
+        // 32 KiB << n, in 16 KiB banks
+        0x00..=0x08 => Ok(2 << byte),
+        _ => Err(EmulatorError::UnknownRomSize(byte)),
+    }
+}
+
+fn ram_size(byte: u8) -> Result<usize, EmulatorError> {
+    match byte {
+        0x00 => Ok(0),
+        0x01 => Ok(0x800),
+        0x02 => Ok(0x2000),
+        0x03 => Ok(0x8000),
+        0x04 => Ok(0x20000),
+        0x05 => Ok(0x10000),
+        _ => Err(EmulatorError::UnknownRamSize(byte)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn rom_with_header(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+        rom[TITLE_START..TITLE_START + 4].copy_from_slice(b"TEST");
+        rom[CARTRIDGE_TYPE_ADDRESS] = cartridge_type;
+        rom[ROM_SIZE_ADDRESS] = rom_size;
+        rom[RAM_SIZE_ADDRESS] = ram_size;
+        rom
+    }
+
+    #[rstest]
+    #[case(0x19, MapperKind::Mbc5, false, false, false)]
+    #[case(0x1B, MapperKind::Mbc5, true, true, false)]
+    #[case(0x1C, MapperKind::Mbc5, false, false, true)]
+    #[case(0x1E, MapperKind::Mbc5, true, true, true)]
+    #[case(0x22, MapperKind::Mbc7, true, true, true)]
+    #[case(0xFC, MapperKind::PocketCamera, true, true, false)]
+    #[case(0xFE, MapperKind::HuC3, true, true, false)]
+    #[case(0xFF, MapperKind::HuC1, true, true, false)]
+    #[case(0x0D, MapperKind::Mmm01, true, true, false)]
+    #[case(0x00, MapperKind::RomOnly, false, false, false)]
+    fn should_decode_cartridge_type(
+        #[case] byte: u8,
+        #[case] expected_mapper: MapperKind,
+        #[case] expected_ram: bool,
+        #[case] expected_battery: bool,
+        #[case] expected_rumble: bool,
+    ) {
+        let cartridge_type = CartridgeType::build(byte).unwrap();
+
+        assert_eq!(cartridge_type.mapper, expected_mapper);
+        assert_eq!(cartridge_type.has_ram, expected_ram);
+        assert_eq!(cartridge_type.has_battery, expected_battery);
+        assert_eq!(cartridge_type.has_rumble, expected_rumble);
+    }
+
+    #[test]
+    fn should_reject_unknown_cartridge_type() {
+        let result = CartridgeType::build(0x42);
+        assert_eq!(result, Err(EmulatorError::UnsupportedCartridgeType(0x42)));
+    }
+
+    #[rstest]
+    #[case(0x00, 0x00, 2, 0)]
+    #[case(0x05, 0x03, 64, 0x8000)]
+    #[case(0x08, 0x04, 512, 0x20000)]
+    fn should_parse_header(
+        #[case] rom_size: u8,
+        #[case] ram_size: u8,
+        #[case] expected_banks: usize,
+        #[case] expected_ram: usize,
+    ) {
+        let rom = rom_with_header(0x1B, rom_size, ram_size);
+
+        let header = CartridgeHeader::build(&rom).unwrap();
+
+        assert_eq!(header.title, "TEST");
+        assert_eq!(header.rom_banks, expected_banks);
+        assert_eq!(header.ram_size, expected_ram);
+    }
+
+    #[test]
+    fn should_detect_mmm01_from_menu_header() {
+        let mut rom = vec![0x00; 0x40000];
+        // First game on the compilation uses an unsupported mapper
+        rom[CARTRIDGE_TYPE_ADDRESS] = 0x01;
+        let menu_start = rom.len() - MMM01_MENU_SIZE;
+        rom[menu_start + CARTRIDGE_TYPE_ADDRESS] = 0x0D;
+        rom[menu_start + ROM_SIZE_ADDRESS] = 0x03;
+        rom[menu_start + RAM_SIZE_ADDRESS] = 0x03;
+
+        let header = CartridgeHeader::detect(&rom).unwrap();
+
+        assert_eq!(header.cartridge_type.mapper, MapperKind::Mmm01);
+        assert_eq!(header.rom_banks, 16);
+        assert_eq!(header.ram_size, 0x8000);
+    }
+
+    #[test]
+    fn should_use_first_header_for_other_cartridges() {
+        let rom = rom_with_header(0x1B, 0x00, 0x02);
+
+        let header = CartridgeHeader::detect(&rom).unwrap();
+
+        assert_eq!(header.cartridge_type.mapper, MapperKind::Mbc5);
+    }
+
+    #[test]
+    fn should_reject_truncated_rom() {
+        let result = CartridgeHeader::build(&[0x00; 0x100]);
+        assert_eq!(result, Err(EmulatorError::RomTooSmall(0x100)));
+    }
+}
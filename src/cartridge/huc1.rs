@@ -0,0 +1,149 @@
+use crate::power_on::{MemoryRegion, PowerOnFill};
+
+use super::{
+    bank_offset, infrared::InfraredPort, mapper::Mapper, poke_banked_ram, RAM_BANK_SIZE,
+    ROM_BANK_SIZE,
+};
+
+const IR_MODE: u8 = 0x0E;
+
+/// Hudson HuC1: MBC1 style banking plus an infrared LED and receiver that
+/// replace the RAM area while IR mode is selected.
+pub struct HuC1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    ram_bank: u8,
+    ir_mode: bool,
+    infrared: InfraredPort,
+}
+
+impl HuC1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> HuC1 {
+        HuC1 {
+            rom,
+            ram: vec![0x00; ram_size],
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_mode: false,
+            infrared: InfraredPort::new(),
+        }
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn infrared_mut(&mut self) -> &mut InfraredPort {
+        &mut self.infrared
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank_size = RAM_BANK_SIZE.min(self.ram.len());
+        bank_offset(self.ram.len(), bank_size, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for HuC1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = new_value & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => self.rom_bank = new_value & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = new_value & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            return self.infrared.read();
+        }
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if self.ir_mode {
+            self.infrared.write(new_value);
+            return;
+        }
+        if self.ram.is_empty() {
+            return;
+        }
+        let offset = self.ram_offset(address);
+        self.ram[offset] = new_value;
+    }
+
+    fn battery_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_switch_rom_and_ram_banks() {
+        let mut rom = vec![0x00; 8 * ROM_BANK_SIZE];
+        rom[5 * ROM_BANK_SIZE] = 0x55;
+        let mut mbc = HuC1::new(rom, 4 * RAM_BANK_SIZE);
+
+        mbc.write_control(0x2000, 0x05);
+        mbc.write_control(0x4000, 0x02);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.write_control(0x4000, 0x00);
+
+        assert_eq!(mbc.read_rom(0x4000), 0x55);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        assert_eq!(mbc.ram()[2 * RAM_BANK_SIZE], 0x42);
+    }
+
+    #[test]
+    fn should_loop_infrared_back() {
+        let mut mbc = HuC1::new(vec![0x00; 2 * ROM_BANK_SIZE], RAM_BANK_SIZE);
+        mbc.write_control(0x0000, 0x0E);
+
+        mbc.write_ram(0xA000, 0x01);
+        let led_on = mbc.infrared_mut().led_on();
+        mbc.infrared_mut().set_light_detected(led_on);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xC1);
+        assert_eq!(mbc.ram()[0], 0x00);
+    }
+
+    #[test]
+    fn should_return_to_ram_after_leaving_ir_mode() {
+        let mut mbc = HuC1::new(vec![0x00; 2 * ROM_BANK_SIZE], RAM_BANK_SIZE);
+        mbc.write_ram(0xA000, 0x42);
+
+        mbc.write_control(0x0000, 0x0E);
+        assert_eq!(mbc.read_ram(0xA000), 0xC0);
+
+        mbc.write_control(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+}
@@ -0,0 +1,378 @@
+pub mod clock;
+
+use self::clock::HuC3Clock;
+
+use crate::power_on::{MemoryRegion, PowerOnFill};
+
+use super::{
+    bank_offset, infrared::InfraredPort, mapper::Mapper, poke_banked_ram, rtc_footer,
+    RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+const RTC_MEMORY_SIZE: usize = 0x100;
+/// RTC memory nibble holding the tone played by the tone generator
+const TONE_ADDRESS: usize = 0x26;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    RamReadOnly,
+    RamReadWrite,
+    CommandWrite,
+    ResponseRead,
+    Semaphore,
+    Infrared,
+    Disabled,
+}
+
+impl std::convert::From<u8> for Mode {
+    fn from(byte: u8) -> Self {
+        match byte & 0x0F {
+            0x0 => Mode::RamReadOnly,
+            0xA => Mode::RamReadWrite,
+            0xB => Mode::CommandWrite,
+            0xC => Mode::ResponseRead,
+            0xD => Mode::Semaphore,
+            0xE => Mode::Infrared,
+            _ => Mode::Disabled,
+        }
+    }
+}
+
+/// Hudson HuC3. Besides banking it talks to a microcontroller holding a
+/// clock, a tone generator and 256 nibbles of memory through a
+/// command/response protocol, and has the same IR port as the HuC1.
+pub struct HuC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank: u8,
+    ram_bank: u8,
+    mode: Mode,
+    command: u8,
+    argument: u8,
+    result: u8,
+    address: u8,
+    rtc_memory: Vec<u8>,
+    clock: HuC3Clock,
+    tone: Option<u8>,
+    infrared: InfraredPort,
+}
+
+impl HuC3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> HuC3 {
+        HuC3 {
+            rom,
+            ram: vec![0x00; ram_size],
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: Mode::RamReadOnly,
+            command: 0,
+            argument: 0,
+            result: 0,
+            address: 0,
+            rtc_memory: vec![0x00; RTC_MEMORY_SIZE],
+            clock: HuC3Clock::new(),
+            tone: None,
+            infrared: InfraredPort::new(),
+        }
+    }
+
+    pub fn clock(&self) -> &HuC3Clock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut HuC3Clock {
+        &mut self.clock
+    }
+
+    /// Tone currently played by the speaker, if any
+    pub fn tone(&self) -> Option<u8> {
+        self.tone
+    }
+
+    pub fn infrared_mut(&mut self) -> &mut InfraredPort {
+        &mut self.infrared
+    }
+
+    fn execute(&mut self) {
+        match self.command {
+            0x1 => {
+                self.result = self.rtc_memory[self.address as usize] & 0x0F;
+                self.address = self.address.wrapping_add(1);
+            }
+            0x3 => {
+                self.rtc_memory[self.address as usize] = self.argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            0x4 => self.address = (self.address & 0xF0) | self.argument,
+            0x5 => self.address = (self.address & 0x0F) | self.argument << 4,
+            0x6 => self.execute_extended(),
+            _ => (),
+        }
+    }
+
+    fn execute_extended(&mut self) {
+        match self.argument {
+            // Copy the current time into memory 0x00-0x05, least significant nibble first
+            0x0 => {
+                let minutes = self.clock.minutes();
+                let days = self.clock.days();
+                for nibble in 0..3 {
+                    self.rtc_memory[nibble] = ((minutes >> (nibble * 4)) & 0x0F) as u8;
+                    self.rtc_memory[nibble + 3] = ((days >> (nibble * 4)) & 0x0F) as u8;
+                }
+            }
+            // Set the clock from memory 0x00-0x05
+            0x1 => {
+                let (minutes, days) = (0..3).fold((0, 0), |(minutes, days), nibble| {
+                    (
+                        minutes | (self.rtc_memory[nibble] as u16) << (nibble * 4),
+                        days | (self.rtc_memory[nibble + 3] as u16) << (nibble * 4),
+                    )
+                });
+                self.clock.set(minutes, days);
+            }
+            0x2 => self.result = 0x1,
+            0xE => {
+                let tone = self.rtc_memory[TONE_ADDRESS];
+                self.tone = if tone == 0 { None } else { Some(tone) };
+            }
+            _ => (),
+        }
+    }
+
+    fn save_data(&self, now: u64) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend_from_slice(&self.clock.to_footer(now).to_bytes());
+        data
+    }
+
+    fn load_save_data(&mut self, data: &[u8], now: u64) {
+        let (ram, footer) = rtc_footer::split_save(data);
+        let len = ram.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&ram[..len]);
+        if let Some(footer) = footer {
+            self.clock.load_footer(&footer, now);
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank_size = RAM_BANK_SIZE.min(self.ram.len());
+        bank_offset(self.ram.len(), bank_size, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for HuC3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = Mode::from(new_value),
+            0x2000..=0x3FFF => self.rom_bank = new_value & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = new_value & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        match self.mode {
+            Mode::RamReadOnly | Mode::RamReadWrite if !self.ram.is_empty() => {
+                self.ram[self.ram_offset(address)]
+            }
+            Mode::ResponseRead => 0x80 | self.command << 4 | self.result,
+            // Commands complete immediately, so the controller is always ready
+            Mode::Semaphore => 0xFF,
+            Mode::Infrared => self.infrared.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        match self.mode {
+            Mode::RamReadWrite if !self.ram.is_empty() => {
+                let offset = self.ram_offset(address);
+                self.ram[offset] = new_value;
+            }
+            Mode::CommandWrite => {
+                self.command = (new_value >> 4) & 0x07;
+                self.argument = new_value & 0x0F;
+            }
+            Mode::Semaphore if new_value & 0x01 == 0 => self.execute(),
+            Mode::Infrared => self.infrared.write(new_value),
+            _ => (),
+        }
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.clock.tick(cycles);
+    }
+
+    /// Save RAM followed by the clock in an RTC footer
+    fn battery_data(&self) -> Vec<u8> {
+        self.save_data(rtc_footer::unix_time())
+    }
+
+    /// Save RAM and the minutes and days the clock counts. The seconds
+    /// into the current minute are left out along with the host time: a
+    /// footer written earlier still brings the clock to the right time.
+    fn battery_state(&self) -> Vec<u8> {
+        let mut footer = self.clock.to_footer(0);
+        footer.time.seconds = 0;
+        footer.latched.seconds = 0;
+        let mut state = self.ram.clone();
+        state.extend_from_slice(&footer.to_bytes());
+        state
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        self.load_save_data(data, rtc_footer::unix_time());
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::clock::CYCLES_PER_MINUTE;
+    use super::*;
+
+    fn huc3() -> HuC3 {
+        HuC3::new(vec![0x00; 4 * ROM_BANK_SIZE], 4 * RAM_BANK_SIZE)
+    }
+
+    fn run_command(mbc: &mut HuC3, command: u8, argument: u8) -> u8 {
+        mbc.write_control(0x0000, 0x0B);
+        mbc.write_ram(0xA000, command << 4 | argument);
+        mbc.write_control(0x0000, 0x0D);
+        mbc.write_ram(0xA000, 0xFE);
+        mbc.write_control(0x0000, 0x0C);
+        mbc.read_ram(0xA000)
+    }
+
+    fn set_address(mbc: &mut HuC3, address: u8) {
+        run_command(mbc, 0x4, address & 0x0F);
+        run_command(mbc, 0x5, address >> 4);
+    }
+
+    #[test]
+    fn should_protect_ram_in_read_only_mode() {
+        let mut mbc = huc3();
+
+        mbc.write_control(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.write_control(0x0000, 0x00);
+        mbc.write_ram(0xA000, 0x24);
+
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn should_read_back_written_nibbles() {
+        let mut mbc = huc3();
+
+        set_address(&mut mbc, 0x40);
+        run_command(&mut mbc, 0x3, 0x7);
+        run_command(&mut mbc, 0x3, 0x9);
+        set_address(&mut mbc, 0x40);
+
+        assert_eq!(run_command(&mut mbc, 0x1, 0x0), 0x80 | 0x10 | 0x7);
+        assert_eq!(run_command(&mut mbc, 0x1, 0x0), 0x80 | 0x10 | 0x9);
+    }
+
+    #[test]
+    fn should_latch_current_time() {
+        let mut mbc = huc3();
+        mbc.clock_mut().set(0x123, 0x456);
+
+        run_command(&mut mbc, 0x6, 0x0);
+        set_address(&mut mbc, 0x00);
+
+        let nibbles: Vec<u8> = (0..6)
+            .map(|_| run_command(&mut mbc, 0x1, 0x0) & 0x0F)
+            .collect();
+        assert_eq!(nibbles, vec![0x3, 0x2, 0x1, 0x6, 0x5, 0x4]);
+    }
+
+    #[test]
+    fn should_set_time_from_memory() {
+        let mut mbc = huc3();
+
+        set_address(&mut mbc, 0x00);
+        for nibble in [0x0, 0x1, 0x0, 0x2, 0x0, 0x0] {
+            run_command(&mut mbc, 0x3, nibble);
+        }
+        run_command(&mut mbc, 0x6, 0x1);
+        mbc.tick(CYCLES_PER_MINUTE as u32);
+
+        assert_eq!(mbc.clock().minutes(), 0x011);
+        assert_eq!(mbc.clock().days(), 0x002);
+    }
+
+    #[test]
+    fn should_report_status_and_play_tone() {
+        let mut mbc = huc3();
+
+        assert_eq!(run_command(&mut mbc, 0x6, 0x2) & 0x0F, 0x1);
+
+        set_address(&mut mbc, TONE_ADDRESS as u8);
+        run_command(&mut mbc, 0x3, 0x4);
+        run_command(&mut mbc, 0x6, 0xE);
+        assert_eq!(mbc.tone(), Some(0x4));
+    }
+
+    #[test]
+    fn should_loop_infrared_back() {
+        let mut mbc = huc3();
+        mbc.write_control(0x0000, 0x0E);
+
+        mbc.write_ram(0xA000, 0x01);
+        let led_on = mbc.infrared_mut().led_on();
+        mbc.infrared_mut().set_light_detected(led_on);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xC1);
+    }
+
+    #[test]
+    fn should_persist_clock_with_ram() {
+        let mut mbc = huc3();
+        mbc.write_control(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.clock_mut().set(100, 7);
+
+        let mut restored = huc3();
+        restored.load_save_data(&mbc.save_data(1000), 1000 + 3600);
+
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+        assert_eq!(restored.clock().minutes(), 160);
+        assert_eq!(restored.clock().days(), 7);
+    }
+
+    #[test]
+    fn should_load_saves_of_other_sizes() {
+        let mut save = vec![0x42; RAM_BANK_SIZE];
+        save.extend_from_slice(&HuC3Clock::new().to_footer(0).to_bytes()[..44]);
+        let mut mbc = huc3();
+
+        mbc.load_save_data(&save, 60);
+        mbc.write_control(0x0000, 0x0A);
+        mbc.write_control(0x4000, 0x01);
+
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        assert_eq!(mbc.clock().minutes(), 1);
+
+        mbc.load_save_data(&vec![0x24; 8 * RAM_BANK_SIZE], 60);
+        assert_eq!(mbc.read_ram(0xA000), 0x24);
+        assert_eq!(mbc.clock().minutes(), 1);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::cartridge::{
+    rtc_footer::{RtcFooter, RtcRegisters},
+    save_file::CYCLES_PER_SECOND,
+};
+
+/// Clock cycles in one minute of emulated time
+pub const CYCLES_PER_MINUTE: u64 = CYCLES_PER_SECOND * 60;
+pub const MINUTES_PER_DAY: u16 = 1440;
+/// Days are kept in a 12 bit counter
+const DAY_MASK: u16 = 0x0FFF;
+
+/// HuC3 real time clock, which only counts minutes within the day and days
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HuC3Clock {
+    minutes: u16,
+    days: u16,
+    cycles: u64,
+}
+
+impl HuC3Clock {
+    pub fn new() -> HuC3Clock {
+        HuC3Clock::default()
+    }
+
+    pub fn minutes(&self) -> u16 {
+        self.minutes
+    }
+
+    pub fn days(&self) -> u16 {
+        self.days
+    }
+
+    pub fn set(&mut self, minutes: u16, days: u16) {
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = days & DAY_MASK;
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+        let elapsed_minutes = self.cycles / CYCLES_PER_MINUTE;
+        self.cycles %= CYCLES_PER_MINUTE;
+        self.advance_minutes(elapsed_minutes);
+    }
+
+    pub fn advance_minutes(&mut self, elapsed_minutes: u64) {
+        let total = self.minutes as u64 + elapsed_minutes;
+        let days = self.days as u64 + total / MINUTES_PER_DAY as u64;
+        self.minutes = (total % MINUTES_PER_DAY as u64) as u16;
+        self.days = (days & DAY_MASK as u64) as u16;
+    }
+
+    /// Advances the clock by real time that passed while it wasn't emulated
+    pub fn advance_seconds(&mut self, elapsed_seconds: u64) {
+        let seconds = self.cycles / CYCLES_PER_SECOND + elapsed_seconds;
+        self.cycles = (seconds % 60) * CYCLES_PER_SECOND + self.cycles % CYCLES_PER_SECOND;
+        self.advance_minutes(seconds / 60);
+    }
+
+    /// Clock state in the MBC3 footer layout. The HuC3 has no latch and
+    /// keeps days 8-11 in the high day register.
+    pub fn to_footer(&self, timestamp: u64) -> RtcFooter {
+        let time = RtcRegisters {
+            seconds: (self.cycles / CYCLES_PER_SECOND) as u32,
+            minutes: (self.minutes % 60) as u32,
+            hours: (self.minutes / 60) as u32,
+            days_low: (self.days & 0xFF) as u32,
+            days_high: (self.days >> 8) as u32,
+        };
+        RtcFooter {
+            time,
+            latched: time,
+            timestamp,
+        }
+    }
+
+    /// Restores the clock and catches up with the time that passed on the
+    /// host since the footer was written
+    pub fn load_footer(&mut self, footer: &RtcFooter, now: u64) {
+        let time = footer.time;
+        let minutes = (time.hours % 24) * 60 + time.minutes % 60;
+        let days = (time.days_high & 0x0F) << 8 | (time.days_low & 0xFF);
+        self.set(minutes as u16, days as u16);
+        self.cycles = (time.seconds % 60) as u64 * CYCLES_PER_SECOND;
+        self.advance_seconds(now.saturating_sub(footer.timestamp));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0, 0, 1, 1, 0)]
+    #[case(1439, 0, 1, 0, 1)]
+    #[case(1000, 4095, 440, 0, 0)]
+    #[case(0, 2, 3 * 1440 + 5, 5, 5)]
+    fn should_advance_minutes(
+        #[case] minutes: u16,
+        #[case] days: u16,
+        #[case] elapsed: u64,
+        #[case] expected_minutes: u16,
+        #[case] expected_days: u16,
+    ) {
+        let mut clock = HuC3Clock::new();
+        clock.set(minutes, days);
+
+        clock.advance_minutes(elapsed);
+
+        assert_eq!(clock.minutes(), expected_minutes);
+        assert_eq!(clock.days(), expected_days);
+    }
+
+    #[test]
+    fn should_count_minutes_from_cycles() {
+        let mut clock = HuC3Clock::new();
+
+        clock.tick((CYCLES_PER_MINUTE - 1) as u32);
+        assert_eq!(clock.minutes(), 0);
+        clock.tick(1);
+        assert_eq!(clock.minutes(), 1);
+    }
+
+    #[test]
+    fn should_round_trip_footer() {
+        let mut clock = HuC3Clock::new();
+        clock.set(725, 300);
+
+        let mut restored = HuC3Clock::new();
+        restored.load_footer(&clock.to_footer(1000), 1000);
+
+        assert_eq!(restored.minutes(), 725);
+        assert_eq!(restored.days(), 300);
+    }
+
+    #[rstest]
+    #[case(0, 0, 0)]
+    #[case(59, 0, 0)]
+    #[case(60, 1, 0)]
+    #[case(86_400 + 30 * 60, 30, 1)]
+    fn should_catch_up_with_host_time(
+        #[case] elapsed: u64,
+        #[case] expected_minutes: u16,
+        #[case] expected_days: u16,
+    ) {
+        let footer = HuC3Clock::new().to_footer(1_700_000_000);
+
+        let mut clock = HuC3Clock::new();
+        clock.load_footer(&footer, 1_700_000_000 + elapsed);
+
+        assert_eq!(clock.minutes(), expected_minutes);
+        assert_eq!(clock.days(), expected_days);
+    }
+
+    #[test]
+    fn should_carry_partial_minutes_across_saves() {
+        let mut clock = HuC3Clock::new();
+        clock.tick((CYCLES_PER_SECOND * 45) as u32);
+
+        let mut restored = HuC3Clock::new();
+        restored.load_footer(&clock.to_footer(0), 15);
+
+        assert_eq!(restored.minutes(), 1);
+    }
+}
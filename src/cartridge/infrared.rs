@@ -0,0 +1,56 @@
+/// Infrared LED and photodiode found on Hudson carts, read and written
+/// through the RAM area while the mapper is in IR mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct InfraredPort {
+    led_on: bool,
+    light_detected: bool,
+}
+
+impl InfraredPort {
+    pub fn new() -> InfraredPort {
+        InfraredPort::default()
+    }
+
+    /// Value seen by the game: 0xC1 while light is received, 0xC0 otherwise
+    pub fn read(&self) -> u8 {
+        0xC0 | self.light_detected as u8
+    }
+
+    /// Bit 0 drives the LED
+    pub fn write(&mut self, new_value: u8) {
+        self.led_on = new_value & 0x01 != 0;
+    }
+
+    pub fn led_on(&self) -> bool {
+        self.led_on
+    }
+
+    /// Feeds the photodiode, e.g. with the LED state of another Game Boy
+    pub fn set_light_detected(&mut self, light_detected: bool) {
+        self.light_detected = light_detected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_report_received_light() {
+        let mut port = InfraredPort::new();
+
+        assert_eq!(port.read(), 0xC0);
+        port.set_light_detected(true);
+        assert_eq!(port.read(), 0xC1);
+    }
+
+    #[test]
+    fn should_drive_led_from_bit_zero() {
+        let mut port = InfraredPort::new();
+
+        port.write(0x01);
+        assert!(port.led_on());
+        port.write(0xFE);
+        assert!(!port.led_on());
+    }
+}
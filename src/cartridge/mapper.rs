@@ -0,0 +1,44 @@
+use std::any::Any;
+
+use crate::power_on::PowerOnFill;
+
+/// Behaviour of a cartridge as seen from the memory bus. The bus hands every
+/// access to 0x0000-0x7FFF and 0xA000-0xBFFF over to the mapper, so custom
+/// and bootleg controllers can be plugged in without touching the memory map.
+pub trait Mapper: Any {
+    /// Reads from the ROM area, 0x0000-0x7FFF
+    fn read_rom(&self, address: u16) -> u8;
+
+    /// Writes to the ROM area, which land on the controller's registers
+    fn write_control(&mut self, address: u16, new_value: u8);
+
+    /// Reads from the external RAM area, 0xA000-0xBFFF
+    fn read_ram(&self, address: u16) -> u8;
+
+    fn write_ram(&mut self, address: u16, new_value: u8);
+
+    /// Advances anything on the cartridge that runs on the system clock
+    fn tick(&mut self, _cycles: u32) {}
+
+    /// Contents that should survive a power cycle when backed by a battery
+    fn battery_data(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// What a save is checked against to tell whether it needs writing
+    /// again. Mappers whose battery data carries the host time leave it,
+    /// and anything else changing on every write, out of this.
+    fn battery_state(&self) -> Vec<u8> {
+        self.battery_data()
+    }
+
+    fn load_battery_data(&mut self, _data: &[u8]) {}
+
+    /// Writes straight into a bank of the cartridge RAM, mapped and enabled
+    /// or not, the way a cheat device does. Mappers without plain RAM
+    /// ignore it.
+    fn poke_ram(&mut self, _bank: u8, _address: u16, _new_value: u8) {}
+
+    /// Fills the cartridge RAM with what it holds when switched on
+    fn power_on(&mut self, _fill: &PowerOnFill) {}
+}
@@ -0,0 +1,278 @@
+use crate::power_on::{MemoryRegion, PowerOnFill};
+
+use super::{bank_offset, mapper::Mapper, poke_banked_ram, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const RUMBLE_MOTOR_BIT: u8 = 0b0000_1000;
+
+/// Change in the state of the rumble motor, stamped with the number of
+/// clock cycles the cartridge had seen when it happened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RumbleEvent {
+    pub motor_on: bool,
+    pub cycle: u64,
+}
+
+pub type RumbleListener = Box<dyn FnMut(RumbleEvent)>;
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    /// 9 bit bank number mapped at 0x4000-0x7FFF, bank 0 included
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    motor_on: bool,
+    cycles: u64,
+    rumble_listener: Option<RumbleListener>,
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom,
+            ram: vec![0x00; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            motor_on: false,
+            cycles: 0,
+            rumble_listener: None,
+        }
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn motor_on(&self) -> bool {
+        self.motor_on
+    }
+
+    /// Registers a callback invoked every time the rumble motor is switched
+    /// on or off. Writes that leave the motor state untouched are not reported.
+    pub fn set_rumble_listener(&mut self, listener: RumbleListener) {
+        self.rumble_listener = Some(listener);
+    }
+
+    fn set_motor(&mut self, motor_on: bool) {
+        if self.motor_on == motor_on {
+            return;
+        }
+        self.motor_on = motor_on;
+
+        let event = RumbleEvent {
+            motor_on,
+            cycle: self.cycles,
+        };
+        if let Some(listener) = self.rumble_listener.as_mut() {
+            listener(event);
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank_size = RAM_BANK_SIZE.min(self.ram.len());
+        bank_offset(self.ram.len(), bank_size, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = new_value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | new_value as u16,
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0x0FF) | ((new_value as u16 & 0x01) << 8)
+            }
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    // Rumble carts wire bit 3 to the motor instead of the RAM chip
+                    self.ram_bank = new_value & 0x07;
+                    self.set_motor(new_value & RUMBLE_MOTOR_BIT != 0);
+                } else {
+                    self.ram_bank = new_value & 0x0F;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return;
+        }
+        let offset = self.ram_offset(address);
+        self.ram[offset] = new_value;
+    }
+
+    fn tick(&mut self, cycles: u32) {
+        self.cycles += cycles as u64;
+    }
+
+    fn battery_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// 512 banks where the first byte of every bank holds its low number
+    /// and the second byte holds bit 8
+    fn banked_rom() -> Vec<u8> {
+        let mut rom = vec![0x00; 512 * ROM_BANK_SIZE];
+        for bank in 0..512 {
+            rom[bank * ROM_BANK_SIZE] = (bank & 0xFF) as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        rom
+    }
+
+    #[rstest]
+    #[case(0x00, 0x00, 0x000)]
+    #[case(0x01, 0x00, 0x001)]
+    #[case(0xFF, 0x00, 0x0FF)]
+    #[case(0x00, 0x01, 0x100)]
+    #[case(0xFF, 0x01, 0x1FF)]
+    #[case(0x23, 0xFF, 0x123)]
+    fn should_select_nine_bit_rom_bank(
+        #[case] low: u8,
+        #[case] high: u8,
+        #[case] expected_bank: usize,
+    ) {
+        let mut mbc = Mbc5::new(banked_rom(), 0, false);
+
+        mbc.write_control(0x2000, low);
+        mbc.write_control(0x3000, high);
+
+        assert_eq!(mbc.read_rom(0x4000), (expected_bank & 0xFF) as u8);
+        assert_eq!(mbc.read_rom(0x4001), (expected_bank >> 8) as u8);
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+    }
+
+    #[test]
+    fn should_wrap_rom_bank_to_rom_size() {
+        let mut rom = banked_rom();
+        rom.truncate(4 * ROM_BANK_SIZE);
+        let mut mbc = Mbc5::new(rom, 0, false);
+
+        mbc.write_control(0x2000, 0x06);
+
+        assert_eq!(mbc.read_rom(0x4000), 0x02);
+    }
+
+    #[test]
+    fn should_switch_between_sixteen_ram_banks() {
+        let mut mbc = Mbc5::new(banked_rom(), 16 * RAM_BANK_SIZE, false);
+        mbc.write_control(0x0000, 0x0A);
+
+        for bank in 0..16 {
+            mbc.write_control(0x4000, bank);
+            mbc.write_ram(0xA000, bank + 0x10);
+        }
+
+        for bank in 0..16 {
+            mbc.write_control(0x4000, bank);
+            assert_eq!(mbc.read_ram(0xA000), bank + 0x10);
+        }
+    }
+
+    #[rstest]
+    #[case(0x0A)]
+    #[case(0x1A)]
+    fn should_ignore_ram_while_disabled(#[case] enable: u8) {
+        let mut mbc = Mbc5::new(banked_rom(), RAM_BANK_SIZE, false);
+
+        mbc.write_ram(0xA000, 0x42);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        mbc.write_control(0x0000, enable);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+
+        mbc.write_control(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn should_not_drive_motor_without_rumble() {
+        let mut mbc = Mbc5::new(banked_rom(), 16 * RAM_BANK_SIZE, false);
+
+        mbc.write_control(0x4000, 0x08);
+
+        assert!(!mbc.motor_on());
+    }
+
+    #[test]
+    fn should_report_rumble_intervals() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&events);
+        let mut mbc = Mbc5::new(banked_rom(), 4 * RAM_BANK_SIZE, true);
+        mbc.set_rumble_listener(Box::new(move |event| recorded.borrow_mut().push(event)));
+
+        mbc.tick(100);
+        mbc.write_control(0x4000, 0x08);
+        mbc.tick(50);
+        mbc.write_control(0x4000, 0x09);
+        mbc.tick(25);
+        mbc.write_control(0x4000, 0x01);
+
+        assert!(!mbc.motor_on());
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                RumbleEvent {
+                    motor_on: true,
+                    cycle: 100
+                },
+                RumbleEvent {
+                    motor_on: false,
+                    cycle: 175
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_use_motor_bit_as_ram_bank_on_rumble_carts() {
+        let mut mbc = Mbc5::new(banked_rom(), 4 * RAM_BANK_SIZE, true);
+        mbc.write_control(0x0000, 0x0A);
+
+        mbc.write_control(0x4000, 0x01);
+        mbc.write_ram(0xA000, 0x42);
+        mbc.write_control(0x4000, 0x09);
+
+        assert_eq!(mbc.read_ram(0xA000), 0x42);
+    }
+}
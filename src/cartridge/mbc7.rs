@@ -0,0 +1,228 @@
+pub mod eeprom;
+
+use self::eeprom::Eeprom;
+
+use super::{bank_offset, mapper::Mapper, ROM_BANK_SIZE};
+
+/// Reading the accelerometer with the cartridge held flat
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+/// Change in the reading for one g of tilt
+const ACCELEROMETER_STEPS_PER_G: f32 = 0x70 as f32;
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+/// MBC7, the controller in Kirby Tilt 'n' Tumble and Command Master. Instead
+/// of RAM it exposes a two axis accelerometer and a 93LC56 EEPROM through
+/// registers at 0xA000-0xAFFF, selected by bits 4-7 of the address.
+pub struct Mbc7 {
+    rom: Vec<u8>,
+    rom_bank: u8,
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    tilt_x: f32,
+    tilt_y: f32,
+    latched_x: u16,
+    latched_y: u16,
+    latch_erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new(rom: Vec<u8>) -> Mbc7 {
+        Mbc7 {
+            rom,
+            rom_bank: 1,
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            tilt_x: 0.0,
+            tilt_y: 0.0,
+            latched_x: ACCELEROMETER_ERASED,
+            latched_y: ACCELEROMETER_ERASED,
+            latch_erased: false,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    /// Sets how far the cartridge is tilted, in g, along each axis. Positive
+    /// values tilt to the right and towards the player. The game only sees the
+    /// new values after it latches the accelerometer again.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt_x = x;
+        self.tilt_y = y;
+    }
+
+    fn registers_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank as usize,
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled_1 = new_value == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = new_value,
+            0x4000..=0x5FFF => self.ram_enabled_2 = new_value == 0x40,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.registers_enabled() || address >= 0xB000 {
+            return 0xFF;
+        }
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched_x as u8,
+            0x3 => (self.latched_x >> 8) as u8,
+            0x4 => self.latched_y as u8,
+            0x5 => (self.latched_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if !self.registers_enabled() || address >= 0xB000 {
+            return;
+        }
+        match (address >> 4) & 0x0F {
+            0x0 if new_value == 0x55 => {
+                self.latched_x = ACCELEROMETER_ERASED;
+                self.latched_y = ACCELEROMETER_ERASED;
+                self.latch_erased = true;
+            }
+            0x1 if new_value == 0xAA && self.latch_erased => {
+                self.latched_x = accelerometer_reading(self.tilt_x);
+                self.latched_y = accelerometer_reading(self.tilt_y);
+                self.latch_erased = false;
+            }
+            0x8 => self.eeprom.write_pins(new_value),
+            _ => (),
+        }
+    }
+
+    /// The EEPROM keeps its contents without a battery but saves the same way
+    fn battery_data(&self) -> Vec<u8> {
+        self.eeprom.image()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        self.eeprom.load_image(data);
+    }
+}
+
+fn accelerometer_reading(tilt: f32) -> u16 {
+    (ACCELEROMETER_CENTER + tilt * ACCELEROMETER_STEPS_PER_G).clamp(0.0, u16::MAX as f32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::eeprom::tests::{receive_word, send_bits, EWEN, READ, WRITE};
+    use super::*;
+    use rstest::*;
+
+    fn enabled_mbc7() -> Mbc7 {
+        let mut mbc = Mbc7::new(vec![0x00; 4 * ROM_BANK_SIZE]);
+        mbc.write_control(0x0000, 0x0A);
+        mbc.write_control(0x4000, 0x40);
+        mbc
+    }
+
+    fn latch(mbc: &mut Mbc7) -> (u16, u16) {
+        mbc.write_ram(0xA000, 0x55);
+        mbc.write_ram(0xA010, 0xAA);
+        let x = (mbc.read_ram(0xA030) as u16) << 8 | mbc.read_ram(0xA020) as u16;
+        let y = (mbc.read_ram(0xA050) as u16) << 8 | mbc.read_ram(0xA040) as u16;
+        (x, y)
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0, 0x81D0, 0x81D0)]
+    #[case(1.0, 0.0, 0x8240, 0x81D0)]
+    #[case(0.0, -1.0, 0x81D0, 0x8160)]
+    #[case(0.5, 2.0, 0x8208, 0x82B0)]
+    fn should_latch_tilt(
+        #[case] x: f32,
+        #[case] y: f32,
+        #[case] expected_x: u16,
+        #[case] expected_y: u16,
+    ) {
+        let mut mbc = enabled_mbc7();
+
+        mbc.set_tilt(x, y);
+
+        assert_eq!(latch(&mut mbc), (expected_x, expected_y));
+    }
+
+    #[test]
+    fn should_keep_latched_value_until_relatched() {
+        let mut mbc = enabled_mbc7();
+        latch(&mut mbc);
+
+        mbc.set_tilt(1.0, 1.0);
+        mbc.write_ram(0xA010, 0xAA);
+
+        assert_eq!(mbc.read_ram(0xA020), 0xD0);
+        assert_eq!(mbc.read_ram(0xA030), 0x81);
+    }
+
+    #[test]
+    fn should_read_erased_value_before_latch() {
+        let mut mbc = enabled_mbc7();
+
+        mbc.write_ram(0xA000, 0x55);
+
+        assert_eq!(mbc.read_ram(0xA020), 0x00);
+        assert_eq!(mbc.read_ram(0xA030), 0x80);
+    }
+
+    #[test]
+    fn should_hide_registers_until_both_enables_are_set() {
+        let mut mbc = Mbc7::new(vec![0x00; 4 * ROM_BANK_SIZE]);
+
+        mbc.write_control(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(0xA060), 0xFF);
+
+        mbc.write_control(0x4000, 0x40);
+        assert_eq!(mbc.read_ram(0xA060), 0x00);
+        assert_eq!(mbc.read_ram(0xB060), 0xFF);
+    }
+
+    #[test]
+    fn should_persist_eeprom_written_through_registers() {
+        let mut mbc = enabled_mbc7();
+
+        send_bits(&mut |pins| mbc.write_ram(0xA080, pins), EWEN, 11);
+        mbc.write_ram(0xA080, 0x00);
+        send_bits(
+            &mut |pins| mbc.write_ram(0xA080, pins),
+            (WRITE | 0x02) << 16 | 0xBEEF,
+            27,
+        );
+        mbc.write_ram(0xA080, 0x00);
+
+        let image = mbc.battery_data();
+        assert_eq!(&image[4..6], &[0xEF, 0xBE]);
+
+        let mut restored = enabled_mbc7();
+        restored.load_battery_data(&image);
+        send_bits(
+            &mut |pins| restored.write_ram(0xA080, pins),
+            READ | 0x02,
+            11,
+        );
+        let cell = std::cell::RefCell::new(restored);
+        let word = receive_word(
+            &mut |pins| cell.borrow_mut().write_ram(0xA080, pins),
+            &mut || cell.borrow().read_ram(0xA080),
+        );
+        assert_eq!(word, 0xBEEF);
+    }
+}
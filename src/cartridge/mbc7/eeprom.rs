@@ -0,0 +1,302 @@
+const WORDS: usize = 128;
+const COMMAND_BITS: u8 = 10;
+const DATA_BITS: u8 = 16;
+
+pub const EEPROM_SIZE: usize = WORDS * 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Waiting for the start bit
+    Idle,
+    /// Shifting in the opcode and address that follow the start bit
+    Command { bits: u16, count: u8 },
+    /// Shifting out a word, the first bit clocked out is a dummy zero
+    Reading { word: u16, remaining: u8 },
+    /// Shifting in the word a WRITE or WRAL will store
+    Writing {
+        address: Option<u8>,
+        bits: u16,
+        count: u8,
+    },
+}
+
+/// Microchip 93LC56 serial EEPROM in its 128 x 16 bit organisation, driven
+/// by bit-banging chip select, clock and data in through an MBC7 register.
+pub struct Eeprom {
+    words: Vec<u16>,
+    write_enabled: bool,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+    state: State,
+}
+
+impl Eeprom {
+    pub fn new() -> Eeprom {
+        Eeprom {
+            words: vec![0xFFFF; WORDS],
+            write_enabled: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            state: State::Idle,
+        }
+    }
+
+    /// Pin state as seen by the MBC7 register: CS in bit 7, CLK in bit 6,
+    /// DI in bit 1 and DO in bit 0
+    pub fn read_pins(&self) -> u8 {
+        (self.chip_select as u8) << 7
+            | (self.clock as u8) << 6
+            | (self.data_in as u8) << 1
+            | self.data_out as u8
+    }
+
+    pub fn write_pins(&mut self, new_value: u8) {
+        let chip_select = new_value & 0b1000_0000 != 0;
+        let clock = new_value & 0b0100_0000 != 0;
+        self.data_in = new_value & 0b0000_0010 != 0;
+
+        if !chip_select {
+            self.state = State::Idle;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.clock_rising_edge();
+        }
+
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    pub fn image(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    pub fn load_image(&mut self, image: &[u8]) {
+        for (word, bytes) in self.words.iter_mut().zip(image.chunks_exact(2)) {
+            *word = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+
+    fn clock_rising_edge(&mut self) {
+        let bit = self.data_in as u16;
+        self.state = match self.state {
+            State::Idle if self.data_in => State::Command { bits: 0, count: 0 },
+            State::Idle => State::Idle,
+            State::Command { bits, count } => {
+                let bits = bits << 1 | bit;
+                if count + 1 == COMMAND_BITS {
+                    self.execute(bits)
+                } else {
+                    State::Command {
+                        bits,
+                        count: count + 1,
+                    }
+                }
+            }
+            State::Reading { word, remaining } => {
+                self.data_out = word & 0x8000 != 0;
+                if remaining == 1 {
+                    State::Idle
+                } else {
+                    State::Reading {
+                        word: word << 1,
+                        remaining: remaining - 1,
+                    }
+                }
+            }
+            State::Writing {
+                address,
+                bits,
+                count,
+            } => {
+                let bits = bits << 1 | bit;
+                if count + 1 == DATA_BITS {
+                    self.store(address, bits);
+                    State::Idle
+                } else {
+                    State::Writing {
+                        address,
+                        bits,
+                        count: count + 1,
+                    }
+                }
+            }
+        };
+    }
+
+    fn execute(&mut self, command: u16) -> State {
+        let opcode = command >> 8;
+        let address = (command & 0x7F) as u8;
+
+        match opcode {
+            0b10 => {
+                self.data_out = false;
+                State::Reading {
+                    word: self.words[address as usize],
+                    remaining: DATA_BITS,
+                }
+            }
+            0b01 => State::Writing {
+                address: Some(address),
+                bits: 0,
+                count: 0,
+            },
+            0b11 => {
+                self.store(Some(address), 0xFFFF);
+                State::Idle
+            }
+            _ => match (command >> 6) & 0b11 {
+                0b11 => {
+                    self.write_enabled = true;
+                    State::Idle
+                }
+                0b00 => {
+                    self.write_enabled = false;
+                    State::Idle
+                }
+                0b10 => {
+                    self.store(None, 0xFFFF);
+                    State::Idle
+                }
+                _ => State::Writing {
+                    address: None,
+                    bits: 0,
+                    count: 0,
+                },
+            },
+        }
+    }
+
+    /// Stores a word at the given address, or everywhere when there is no address
+    fn store(&mut self, address: Option<u8>, word: u16) {
+        if !self.write_enabled {
+            return;
+        }
+        match address {
+            Some(address) => self.words[address as usize] = word,
+            None => self.words.fill(word),
+        }
+        self.data_out = true;
+    }
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Eeprom::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const CS: u8 = 0b1000_0000;
+    const CLK: u8 = 0b0100_0000;
+    const DI: u8 = 0b0000_0010;
+
+    // Start bit, opcode and address as clocked into the chip, 11 bits each
+    pub(crate) const EWEN: u32 = 0x4C0;
+    const ERAL: u32 = 0x480;
+    const WRAL: u32 = 0x440;
+    pub(crate) const WRITE: u32 = 0x500;
+    pub(crate) const READ: u32 = 0x600;
+    const ERASE: u32 = 0x700;
+
+    /// Clocks the bits in MSB first, the same way a game would bit-bang them
+    pub(crate) fn send_bits(write: &mut impl FnMut(u8), value: u32, count: u8) {
+        for shift in (0..count).rev() {
+            let data = if (value >> shift) & 1 != 0 { DI } else { 0 };
+            write(CS | data);
+            write(CS | CLK | data);
+        }
+    }
+
+    pub(crate) fn receive_word(write: &mut impl FnMut(u8), read: &mut impl FnMut() -> u8) -> u16 {
+        let mut word = 0;
+        for _ in 0..16 {
+            write(CS);
+            write(CS | CLK);
+            word = word << 1 | (read() & 0x01) as u16;
+        }
+        word
+    }
+
+    fn command(eeprom: &mut Eeprom, value: u32, count: u8) {
+        eeprom.write_pins(0x00);
+        send_bits(&mut |pins| eeprom.write_pins(pins), value, count);
+    }
+
+    fn read_word(eeprom: &mut Eeprom, address: u8) -> u16 {
+        command(eeprom, READ | address as u32, 11);
+        let mut word = 0;
+        for _ in 0..16 {
+            eeprom.write_pins(CS);
+            eeprom.write_pins(CS | CLK);
+            word = word << 1 | (eeprom.read_pins() & 0x01) as u16;
+        }
+        word
+    }
+
+    #[test]
+    fn should_ignore_writes_until_enabled() {
+        let mut eeprom = Eeprom::new();
+
+        command(&mut eeprom, (WRITE | 0x03) << 16 | 0x1234, 27);
+
+        assert_eq!(read_word(&mut eeprom, 0x03), 0xFFFF);
+    }
+
+    #[test]
+    fn should_write_and_read_back_word() {
+        let mut eeprom = Eeprom::new();
+
+        command(&mut eeprom, EWEN, 11);
+        command(&mut eeprom, (WRITE | 0x03) << 16 | 0x1234, 27);
+
+        assert_eq!(read_word(&mut eeprom, 0x03), 0x1234);
+        assert_eq!(read_word(&mut eeprom, 0x04), 0xFFFF);
+    }
+
+    #[test]
+    fn should_write_all_and_erase_all() {
+        let mut eeprom = Eeprom::new();
+        command(&mut eeprom, EWEN, 11);
+
+        command(&mut eeprom, WRAL << 16 | 0xABCD, 27);
+        assert_eq!(read_word(&mut eeprom, 0x7F), 0xABCD);
+
+        command(&mut eeprom, ERAL, 11);
+        assert_eq!(read_word(&mut eeprom, 0x7F), 0xFFFF);
+    }
+
+    #[test]
+    fn should_erase_single_word() {
+        let mut eeprom = Eeprom::new();
+        eeprom.load_image(&[0x00; EEPROM_SIZE]);
+        command(&mut eeprom, EWEN, 11);
+
+        command(&mut eeprom, ERASE | 0x01, 11);
+
+        assert_eq!(read_word(&mut eeprom, 0x01), 0xFFFF);
+        assert_eq!(read_word(&mut eeprom, 0x00), 0x0000);
+    }
+
+    #[test]
+    fn should_round_trip_image() {
+        let mut image = vec![0x00; EEPROM_SIZE];
+        image[0] = 0x34;
+        image[1] = 0x12;
+        let mut eeprom = Eeprom::new();
+
+        eeprom.load_image(&image);
+
+        assert_eq!(read_word(&mut eeprom, 0x00), 0x1234);
+        assert_eq!(eeprom.image(), image);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::power_on::{MemoryRegion, PowerOnFill};
+
+use super::{bank_offset, mapper::Mapper, poke_banked_ram, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const LOCK_BIT: u8 = 0b0100_0000;
+
+/// MMM01, the multicart controller used by compilation carts. It powers up
+/// unlocked with the menu in the last 32 KiB of ROM mapped at 0x0000-0x7FFF.
+/// The menu then writes the outer bank bits of the chosen game plus masks
+/// saying which bank bits the game may still change, and sets the lock bit.
+/// From then on the game sees an MBC1 style mapper confined to its slice of
+/// the ROM and RAM, and only a power cycle unlocks the controller again.
+///
+/// The MBC1 banking mode and multiplex bits are not modelled.
+pub struct Mmm01 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    locked: bool,
+    ram_enabled: bool,
+    /// 9 bit ROM bank built from the low, mid and high bank registers
+    rom_bank: u16,
+    /// ROM bank bits the menu fixed before locking, the game can't change them
+    rom_bank_fixed: u16,
+    ram_bank: u8,
+    ram_bank_fixed: u8,
+}
+
+impl Mmm01 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Mmm01 {
+        Mmm01 {
+            rom,
+            ram: vec![0x00; ram_size],
+            locked: false,
+            ram_enabled: false,
+            rom_bank: 0,
+            rom_bank_fixed: 0x1E0,
+            ram_bank: 0,
+            ram_bank_fixed: 0,
+        }
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn write_unlocked(&mut self, address: u16, new_value: u8) {
+        let value = new_value as u16;
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = new_value & 0x0F == 0x0A;
+                // RAM bank mask, bits 4-5, fixes RA0-1
+                self.ram_bank_fixed = (new_value >> 4) & 0x03;
+                self.locked = new_value & LOCK_BIT != 0;
+            }
+            // RB0-4 in bits 0-4, RB5-6 in bits 5-6
+            0x2000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x180) | (value & 0x7F),
+            // RA0-1 in bits 0-1, RA2-3 in bits 2-3, RB7-8 in bits 4-5
+            0x4000..=0x5FFF => {
+                self.ram_bank = new_value & 0x0F;
+                self.rom_bank = (self.rom_bank & 0x07F) | ((value >> 4) & 0x03) << 7;
+            }
+            // ROM bank mask, bits 2-5, fixes RB1-4. RB5-8 are always fixed.
+            0x6000..=0x7FFF => self.rom_bank_fixed = 0x1E0 | ((value >> 2) & 0x0F) << 1,
+            _ => (),
+        }
+    }
+
+    fn write_locked(&mut self, address: u16, new_value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = new_value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                let writable = !self.rom_bank_fixed & 0x1F;
+                self.rom_bank = (self.rom_bank & !writable) | (new_value as u16 & writable);
+            }
+            0x4000..=0x5FFF => {
+                let writable = !self.ram_bank_fixed & 0x03;
+                self.ram_bank = (self.ram_bank & !writable) | (new_value & writable);
+            }
+            _ => (),
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        let bank_size = RAM_BANK_SIZE.min(self.ram.len());
+        bank_offset(self.ram.len(), bank_size, self.ram_bank as usize, address)
+    }
+}
+
+impl Mapper for Mmm01 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = if self.locked {
+            match address {
+                0x0000..=0x3FFF => self.rom_bank & self.rom_bank_fixed,
+                _ if self.rom_bank & !self.rom_bank_fixed == 0 => self.rom_bank | 0x01,
+                _ => self.rom_bank,
+            }
+        } else {
+            // The menu lives in the last two banks
+            let banks = (self.rom.len() / ROM_BANK_SIZE).max(2) as u16;
+            match address {
+                0x0000..=0x3FFF => banks - 2,
+                _ => banks - 1,
+            }
+        };
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank as usize, address)]
+    }
+
+    fn write_control(&mut self, address: u16, new_value: u8) {
+        if self.locked {
+            self.write_locked(address, new_value);
+        } else {
+            self.write_unlocked(address, new_value);
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[self.ram_offset(address)]
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return;
+        }
+        let offset = self.ram_offset(address);
+        self.ram[offset] = new_value;
+    }
+
+    fn battery_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multicart() -> Mmm01 {
+        let mut rom = vec![0x00; 64 * ROM_BANK_SIZE];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        Mmm01::new(rom, 4 * RAM_BANK_SIZE)
+    }
+
+    #[test]
+    fn should_boot_into_menu_in_last_banks() {
+        let mbc = multicart();
+
+        assert!(!mbc.locked());
+        assert_eq!(mbc.read_rom(0x0000), 62);
+        assert_eq!(mbc.read_rom(0x4000), 63);
+    }
+
+    #[test]
+    fn should_confine_game_to_its_banks_after_menu_locks() {
+        let mut mbc = multicart();
+
+        // Menu picks the 8 bank game at banks 8-15: RB3 stays set,
+        // RB3-4 are masked and the lock bit is written last
+        mbc.write_control(0x2000, 0x08);
+        mbc.write_control(0x6000, 0b0011_0000);
+        mbc.write_control(0x0000, 0x40);
+
+        assert!(mbc.locked());
+        assert_eq!(mbc.read_rom(0x0000), 8);
+        assert_eq!(mbc.read_rom(0x4000), 9);
+
+        mbc.write_control(0x2000, 0x03);
+        assert_eq!(mbc.read_rom(0x4000), 11);
+
+        mbc.write_control(0x2000, 0x1F);
+        assert_eq!(mbc.read_rom(0x4000), 15);
+        assert_eq!(mbc.read_rom(0x0000), 8);
+    }
+
+    #[test]
+    fn should_ignore_outer_bank_writes_once_locked() {
+        let mut mbc = multicart();
+        mbc.write_control(0x2000, 0x08);
+        mbc.write_control(0x6000, 0b0011_0000);
+        mbc.write_control(0x0000, 0x40);
+
+        mbc.write_control(0x6000, 0x00);
+        mbc.write_control(0x4000, 0x30);
+        mbc.write_control(0x0000, 0x00);
+        mbc.write_control(0x2000, 0x12);
+
+        assert!(mbc.locked());
+        assert_eq!(mbc.read_rom(0x0000), 8);
+        assert_eq!(mbc.read_rom(0x4000), 10);
+    }
+
+    #[test]
+    fn should_fix_masked_ram_bank_bits() {
+        let mut mbc = multicart();
+        mbc.write_control(0x4000, 0x02);
+        mbc.write_control(0x0000, 0x4A | 0x20);
+
+        mbc.write_control(0x4000, 0x01);
+        mbc.write_ram(0xA000, 0x42);
+
+        assert_eq!(mbc.ram()[3 * RAM_BANK_SIZE], 0x42);
+    }
+}
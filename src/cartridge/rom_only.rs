@@ -0,0 +1,90 @@
+use crate::power_on::{MemoryRegion, PowerOnFill};
+
+use super::{bank_offset, mapper::Mapper, poke_banked_ram, ROM_BANK_SIZE};
+
+/// Cartridges without a memory bank controller: 32 KiB of ROM mapped
+/// straight into 0x0000-0x7FFF and an optional unbanked 8 KiB of RAM.
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> RomOnly {
+        RomOnly {
+            rom,
+            ram: vec![0x00; ram_size],
+        }
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+}
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        let bank = address as usize / ROM_BANK_SIZE;
+        self.rom[bank_offset(self.rom.len(), ROM_BANK_SIZE, bank, address)]
+    }
+
+    /// There is no controller to receive register writes
+    fn write_control(&mut self, _address: u16, _new_value: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ram.is_empty() {
+            return 0xFF;
+        }
+        self.ram[bank_offset(self.ram.len(), self.ram.len(), 0, address)]
+    }
+
+    fn write_ram(&mut self, address: u16, new_value: u8) {
+        if self.ram.is_empty() {
+            return;
+        }
+        let offset = bank_offset(self.ram.len(), self.ram.len(), 0, address);
+        self.ram[offset] = new_value;
+    }
+
+    fn battery_data(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn load_battery_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.ram.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn poke_ram(&mut self, bank: u8, address: u16, new_value: u8) {
+        poke_banked_ram(&mut self.ram, bank, address, new_value);
+    }
+
+    fn power_on(&mut self, fill: &PowerOnFill) {
+        fill.fill(MemoryRegion::CartRam, &mut self.ram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_map_rom_without_banking() {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x0000] = 0x11;
+        rom[0x7FFF] = 0x22;
+        let cartridge = RomOnly::new(rom, 0);
+
+        assert_eq!(cartridge.read_rom(0x0000), 0x11);
+        assert_eq!(cartridge.read_rom(0x7FFF), 0x22);
+    }
+
+    #[test]
+    fn should_return_open_bus_without_ram() {
+        let mut cartridge = RomOnly::new(vec![0x00; 0x8000], 0);
+
+        cartridge.write_ram(0xA000, 0x42);
+
+        assert_eq!(cartridge.read_ram(0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Footer with a 64 bit timestamp, as written by BGB and newer VBA builds
+pub const RTC_FOOTER_SIZE: usize = 48;
+/// Older VBA footer with a 32 bit timestamp
+pub const LEGACY_RTC_FOOTER_SIZE: usize = 44;
+/// Every battery RAM size is a multiple of this, so whatever is left over
+/// at the end of a save is the footer
+const RAM_SIZE_GRANULARITY: usize = 0x100;
+
+/// Clock registers in the order they appear in a footer, each stored as a
+/// little endian 32 bit value
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RtcRegisters {
+    pub seconds: u32,
+    pub minutes: u32,
+    pub hours: u32,
+    pub days_low: u32,
+    /// Day counter bit 8 plus the halt and carry flags on MBC3
+    pub days_high: u32,
+}
+
+/// Clock state appended to `.sav` files so the time survives moving a save
+/// between emulators: the running and latched registers plus the host time
+/// when the save was written, in seconds since the Unix epoch. The layout
+/// is the one VBA and BGB use for MBC3 saves, but there is no MBC3 mapper
+/// here yet, so only HuC3 reads and writes it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RtcFooter {
+    pub time: RtcRegisters,
+    pub latched: RtcRegisters,
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    pub fn parse(bytes: &[u8]) -> Option<RtcFooter> {
+        let timestamp = match bytes.len() {
+            RTC_FOOTER_SIZE => u64::from_le_bytes(bytes[40..48].try_into().ok()?),
+            LEGACY_RTC_FOOTER_SIZE => read_u32(bytes, 10) as u64,
+            _ => return None,
+        };
+
+        Some(RtcFooter {
+            time: read_registers(bytes, 0),
+            latched: read_registers(bytes, 5),
+            timestamp,
+        })
+    }
+
+    /// Always uses the 48 byte BGB and VBA-M layout. Older VBA builds that
+    /// expect the 44 byte one by file size won't find this footer.
+    pub fn to_bytes(&self) -> [u8; RTC_FOOTER_SIZE] {
+        let mut bytes = [0x00; RTC_FOOTER_SIZE];
+        for (index, register) in registers(&self.time)
+            .into_iter()
+            .chain(registers(&self.latched))
+            .enumerate()
+        {
+            bytes[index * 4..index * 4 + 4].copy_from_slice(&register.to_le_bytes());
+        }
+        bytes[40..48].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+}
+
+/// Splits a save into the battery RAM image and the RTC footer, if any
+pub fn split_save(data: &[u8]) -> (&[u8], Option<RtcFooter>) {
+    let footer_size = data.len() % RAM_SIZE_GRANULARITY;
+    let (ram, footer) = data.split_at(data.len() - footer_size);
+    match RtcFooter::parse(footer) {
+        Some(footer) => (ram, Some(footer)),
+        None => (data, None),
+    }
+}
+
+/// Seconds since the Unix epoch on the host
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn read_u32(bytes: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[index * 4],
+        bytes[index * 4 + 1],
+        bytes[index * 4 + 2],
+        bytes[index * 4 + 3],
+    ])
+}
+
+fn read_registers(bytes: &[u8], first: usize) -> RtcRegisters {
+    RtcRegisters {
+        seconds: read_u32(bytes, first),
+        minutes: read_u32(bytes, first + 1),
+        hours: read_u32(bytes, first + 2),
+        days_low: read_u32(bytes, first + 3),
+        days_high: read_u32(bytes, first + 4),
+    }
+}
+
+fn registers(registers: &RtcRegisters) -> [u32; 5] {
+    [
+        registers.seconds,
+        registers.minutes,
+        registers.hours,
+        registers.days_low,
+        registers.days_high,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn footer() -> RtcFooter {
+        RtcFooter {
+            time: RtcRegisters {
+                seconds: 12,
+                minutes: 34,
+                hours: 5,
+                days_low: 0x20,
+                days_high: 0x01,
+            },
+            latched: RtcRegisters::default(),
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn should_round_trip_footer() {
+        let bytes = footer().to_bytes();
+
+        assert_eq!(&bytes[0..4], &[12, 0, 0, 0]);
+        assert_eq!(RtcFooter::parse(&bytes), Some(footer()));
+    }
+
+    #[test]
+    fn should_read_legacy_footer_with_32_bit_timestamp() {
+        let bytes = footer().to_bytes();
+
+        assert_eq!(RtcFooter::parse(&bytes[..44]), Some(footer()));
+    }
+
+    #[rstest]
+    #[case(0x2000, RTC_FOOTER_SIZE, 0x2000, true)]
+    #[case(0x2000, LEGACY_RTC_FOOTER_SIZE, 0x2000, true)]
+    #[case(0x800, RTC_FOOTER_SIZE, 0x800, true)]
+    #[case(0x8000, 0, 0x8000, false)]
+    #[case(0x2000, 7, 0x2007, false)]
+    fn should_split_footer_from_save(
+        #[case] ram_size: usize,
+        #[case] footer_size: usize,
+        #[case] expected_ram_size: usize,
+        #[case] expected_footer: bool,
+    ) {
+        let mut save = vec![0x00; ram_size];
+        save.extend_from_slice(&footer().to_bytes()[..footer_size.min(RTC_FOOTER_SIZE)]);
+
+        let (ram, footer) = split_save(&save);
+
+        assert_eq!(ram.len(), expected_ram_size);
+        assert_eq!(footer.is_some(), expected_footer);
+    }
+}
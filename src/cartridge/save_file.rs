@@ -0,0 +1,188 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::emulator_error::EmulatorError;
+
+/// Clock cycles in one second of emulated time
+pub const CYCLES_PER_SECOND: u64 = 4_194_304;
+const DEFAULT_FLUSH_INTERVAL: u64 = CYCLES_PER_SECOND;
+
+/// `.sav` file backing the battery powered memory of a cartridge
+pub struct SaveFile {
+    path: PathBuf,
+    /// Emulated clock cycles between periodic flushes, `None` to only flush
+    /// on request and on shutdown
+    flush_interval: Option<u64>,
+    cycles_since_flush: u64,
+    /// State of what the file currently holds, so unchanged data isn't
+    /// written again
+    saved: Option<Vec<u8>>,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> SaveFile {
+        SaveFile {
+            path,
+            flush_interval: Some(DEFAULT_FLUSH_INTERVAL),
+            cycles_since_flush: 0,
+            saved: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_flush_interval(&mut self, cycles: Option<u64>) {
+        self.flush_interval = cycles;
+    }
+
+    /// Reads the file, `None` when there is no save yet
+    pub fn load(&mut self) -> Result<Option<Vec<u8>>, EmulatorError> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                self.saved = Some(data.clone());
+                Ok(Some(data))
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Counts emulated time, returns whether a periodic flush is due
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let Some(interval) = self.flush_interval else {
+            return false;
+        };
+        self.cycles_since_flush += cycles as u64;
+        self.cycles_since_flush >= interval
+    }
+
+    /// Replaces the file with `data` unless it already holds it. The data
+    /// goes to a temporary file first that is then renamed over the save, so
+    /// a crash halfway through leaves the previous save intact. A failed
+    /// write removes the temporary file again.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), EmulatorError> {
+        self.write_state(data, || data.to_vec())
+    }
+
+    /// Same as `write`, but whether the file is written is decided by
+    /// `state` instead of the data, which only gets built when it is. For
+    /// saves stamped with something that changes on every write, like the
+    /// host time.
+    pub fn write_state(
+        &mut self,
+        state: &[u8],
+        data: impl FnOnce() -> Vec<u8>,
+    ) -> Result<(), EmulatorError> {
+        self.cycles_since_flush = 0;
+        if self.saved.as_deref() == Some(state) {
+            return Ok(());
+        }
+
+        let temp_path = temp_path(&self.path);
+        let written =
+            write_synced(&temp_path, &data()).and_then(|_| fs::rename(&temp_path, &self.path));
+        if let Err(error) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error.into());
+        }
+
+        self.saved = Some(state.to_vec());
+        Ok(())
+    }
+}
+
+fn write_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Empty directory for a single test to put its files in
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gameboy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn should_not_load_missing_save() {
+        let mut save_file = SaveFile::new(scratch_dir("missing-save").join("game.sav"));
+
+        assert_eq!(save_file.load(), Ok(None));
+    }
+
+    #[test]
+    fn should_replace_save_without_leaving_temp_file() {
+        let dir = scratch_dir("replace-save");
+        let path = dir.join("game.sav");
+        fs::write(&path, [0x11; 4]).unwrap();
+        let mut save_file = SaveFile::new(path.clone());
+
+        save_file.write(&[0x22; 8]).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), vec![0x22; 8]);
+        assert!(!dir.join("game.sav.tmp").exists());
+    }
+
+    #[test]
+    fn should_skip_writing_unchanged_data() {
+        let path = scratch_dir("unchanged-save").join("game.sav");
+        let mut save_file = SaveFile::new(path.clone());
+        save_file.write(&[0x22; 8]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        save_file.write(&[0x22; 8]).unwrap();
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn should_signal_flush_after_interval() {
+        let mut save_file = SaveFile::new(PathBuf::from("game.sav"));
+        save_file.set_flush_interval(Some(100));
+
+        assert!(!save_file.tick(99));
+        assert!(save_file.tick(1));
+
+        save_file.set_flush_interval(None);
+        assert!(!save_file.tick(1000));
+    }
+
+    #[test]
+    fn should_report_io_errors() {
+        let dir = scratch_dir("io-error-save");
+        let mut save_file = SaveFile::new(dir.join("missing").join("game.sav"));
+
+        assert_eq!(
+            save_file.write(&[0x00]),
+            Err(EmulatorError::Io(io::ErrorKind::NotFound))
+        );
+    }
+
+    #[test]
+    fn should_remove_temp_file_when_rename_fails() {
+        let dir = scratch_dir("failed-rename-save");
+        let path = dir.join("game.sav");
+        fs::create_dir_all(path.join("occupied")).unwrap();
+        let mut save_file = SaveFile::new(path);
+
+        assert!(save_file.write(&[0x22; 8]).is_err());
+        assert!(!dir.join("game.sav.tmp").exists());
+    }
+}
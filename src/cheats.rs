@@ -0,0 +1,183 @@
+pub mod game_genie;
+pub mod game_shark;
+
+use crate::emulator_error::EmulatorError;
+
+use self::{game_genie::GameGenieCode, game_shark::GameSharkCode};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheatCode {
+    GameGenie(GameGenieCode),
+    GameShark(GameSharkCode),
+}
+
+impl CheatCode {
+    /// Tells the formats apart by their shape: Game Genie codes are split by
+    /// dashes into groups of three, GameShark codes are eight digits
+    pub fn parse(code: &str) -> Result<CheatCode, EmulatorError> {
+        if code.contains('-') {
+            GameGenieCode::parse(code).map(CheatCode::GameGenie)
+        } else {
+            GameSharkCode::parse(code).map(CheatCode::GameShark)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    /// The code as it was entered
+    pub code: String,
+    pub decoded: CheatCode,
+    pub enabled: bool,
+}
+
+/// Cheats the memory bus applies. Game Genie codes patch ROM reads as they
+/// happen, GameShark codes are written once per frame.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Cheats {
+    cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Cheats {
+        Cheats::default()
+    }
+
+    /// Adds a cheat, enabled, and returns its index in `list`
+    pub fn add(&mut self, code: &str) -> Result<usize, EmulatorError> {
+        let code = code.trim().to_uppercase();
+        let decoded = CheatCode::parse(&code)?;
+        self.cheats.push(Cheat {
+            code,
+            decoded,
+            enabled: true,
+        });
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    /// Does nothing for an index that isn't in `list`
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(cheat) = self.cheats.get_mut(index) {
+            cheat.enabled = enabled;
+        }
+    }
+
+    pub fn list(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn clear(&mut self) {
+        self.cheats.clear();
+    }
+
+    /// What a ROM read returns once the enabled Game Genie codes are
+    /// applied, the first code that matches wins
+    pub fn patch_rom_read(&self, address: u16, value: u8) -> u8 {
+        self.enabled()
+            .filter_map(|code| match code {
+                CheatCode::GameGenie(code) => Some(code.apply(address, value)),
+                CheatCode::GameShark(_) => None,
+            })
+            .find(|patched| *patched != value)
+            .unwrap_or(value)
+    }
+
+    /// Writes the enabled GameShark codes want done this frame
+    pub fn game_shark_codes(&self) -> impl Iterator<Item = GameSharkCode> + '_ {
+        self.enabled().filter_map(|code| match code {
+            CheatCode::GameShark(code) => Some(*code),
+            CheatCode::GameGenie(_) => None,
+        })
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = &CheatCode> {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| &cheat.decoded)
+    }
+}
+
+/// Values of the hex digits in `code`, `None` if anything else is in there
+fn parse_hex_digits(code: &str) -> Option<Vec<u8>> {
+    code.chars()
+        .map(|digit| digit.to_digit(16).map(|value| value as u8))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        "00a-17b-c49",
+        CheatCode::GameGenie(GameGenieCode { address: 0x4A17, value: 0x00, compare: Some(0xC8) })
+    )]
+    #[case(
+        " 01ff16d0 ",
+        CheatCode::GameShark(GameSharkCode { address: 0xD016, value: 0xFF, bank: None })
+    )]
+    fn should_recognise_format(#[case] code: &str, #[case] expected: CheatCode) {
+        let mut cheats = Cheats::new();
+
+        let index = cheats.add(code).unwrap();
+
+        assert_eq!(cheats.list()[index].decoded, expected);
+        assert_eq!(cheats.list()[index].code, code.trim().to_uppercase());
+        assert!(cheats.list()[index].enabled);
+    }
+
+    #[test]
+    fn should_reject_invalid_code() {
+        let mut cheats = Cheats::new();
+
+        assert_eq!(
+            cheats.add("XYZ"),
+            Err(EmulatorError::InvalidCheatCode("XYZ".to_string()))
+        );
+        assert!(cheats.list().is_empty());
+    }
+
+    #[test]
+    fn should_only_apply_enabled_cheats() {
+        let mut cheats = Cheats::new();
+        let genie = cheats.add("3E9-F5F").unwrap();
+        let shark = cheats.add("01FF16D0").unwrap();
+
+        cheats.set_enabled(genie, false);
+        cheats.set_enabled(shark, false);
+
+        assert_eq!(cheats.patch_rom_read(0x09F5, 0x12), 0x12);
+        assert_eq!(cheats.game_shark_codes().count(), 0);
+
+        cheats.set_enabled(genie, true);
+
+        assert_eq!(cheats.patch_rom_read(0x09F5, 0x12), 0x3E);
+    }
+
+    #[test]
+    fn should_use_first_matching_game_genie_code() {
+        let mut cheats = Cheats::new();
+        cheats.add("00A-17B-C49").unwrap();
+        cheats.add("11A-17B").unwrap();
+
+        assert_eq!(cheats.patch_rom_read(0x4A17, 0xC8), 0x00);
+        assert_eq!(cheats.patch_rom_read(0x4A17, 0x42), 0x11);
+    }
+
+    #[test]
+    fn should_remove_cheat() {
+        let mut cheats = Cheats::new();
+        cheats.add("01FF16D0").unwrap();
+
+        assert!(cheats.remove(1).is_none());
+        assert_eq!(cheats.remove(0).unwrap().code, "01FF16D0");
+        assert!(cheats.list().is_empty());
+    }
+}
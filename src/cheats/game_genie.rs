@@ -0,0 +1,97 @@
+use crate::emulator_error::EmulatorError;
+
+use super::parse_hex_digits;
+
+/// Game Genie code: replaces a byte read from cartridge ROM, optionally only
+/// when the ROM holds the compare byte there, so the code only hits the
+/// right bank
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameGenieCode {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+impl GameGenieCode {
+    /// Decodes `ABC-DEF-GHI`, or `ABC-DEF` without a compare byte. AB is
+    /// the value, FCDE the address with F inverted, and GI the compare byte
+    /// scrambled by a rotation and XOR. H isn't used.
+    pub fn parse(code: &str) -> Result<GameGenieCode, EmulatorError> {
+        let digits = parse_hex_digits(&code.replace('-', ""))
+            .filter(|digits| digits.len() == 6 || digits.len() == 9)
+            .ok_or_else(|| EmulatorError::InvalidCheatCode(code.to_string()))?;
+
+        let value = digits[0] << 4 | digits[1];
+        let address = ((digits[5] ^ 0x0F) as u16) << 12
+            | (digits[2] as u16) << 8
+            | (digits[3] as u16) << 4
+            | digits[4] as u16;
+        if address > 0x7FFF {
+            return Err(EmulatorError::InvalidCheatCode(code.to_string()));
+        }
+        let compare =
+            (digits.len() == 9).then(|| (digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA);
+
+        Ok(GameGenieCode {
+            address,
+            value,
+            compare,
+        })
+    }
+
+    /// What the CPU reads at `address` given the byte the ROM holds there
+    pub fn apply(&self, address: u16, value: u8) -> u8 {
+        if address != self.address || self.compare.is_some_and(|compare| compare != value) {
+            return value;
+        }
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("00A-17B-C49", 0x4A17, 0x00, Some(0xC8))]
+    #[case("3E9-F5F-E6A", 0x09F5, 0x3E, Some(0x00))]
+    #[case("01B-C2E", 0x1BC2, 0x01, None)]
+    fn should_decode_code(
+        #[case] code: &str,
+        #[case] expected_address: u16,
+        #[case] expected_value: u8,
+        #[case] expected_compare: Option<u8>,
+    ) {
+        let decoded = GameGenieCode::parse(code).unwrap();
+
+        assert_eq!(decoded.address, expected_address);
+        assert_eq!(decoded.value, expected_value);
+        assert_eq!(decoded.compare, expected_compare);
+    }
+
+    #[rstest]
+    #[case("00A-170")]
+    #[case("00A-17")]
+    #[case("00A-17G-C49")]
+    fn should_reject_invalid_codes(#[case] code: &str) {
+        assert_eq!(
+            GameGenieCode::parse(code),
+            Err(EmulatorError::InvalidCheatCode(code.to_string()))
+        );
+    }
+
+    #[rstest]
+    #[case(0x4A17, 0xC8, 0x00)]
+    #[case(0x4A17, 0xC9, 0xC9)]
+    #[case(0x4A18, 0xC8, 0xC8)]
+    fn should_replace_matching_reads(
+        #[case] address: u16,
+        #[case] value: u8,
+        #[case] expected: u8,
+    ) {
+        let code = GameGenieCode::parse("00A-17B-C49").unwrap();
+
+        assert_eq!(code.apply(address, value), expected);
+    }
+}
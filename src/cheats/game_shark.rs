@@ -0,0 +1,86 @@
+use crate::emulator_error::EmulatorError;
+
+use super::parse_hex_digits;
+
+const CURRENT_BANK: u8 = 0x01;
+
+/// GameShark code: writes a byte into RAM once per frame, which keeps a
+/// value such as a life counter pinned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameSharkCode {
+    pub address: u16,
+    pub value: u8,
+    /// Bank of the cartridge RAM or CGB WRAM the address lies in, `None` to
+    /// write to whatever bank is mapped at the time
+    pub bank: Option<u8>,
+}
+
+impl GameSharkCode {
+    /// Decodes `TTVVLLHH`: type, value and the address low byte first. Type
+    /// 01 writes through the current mapping, 8X targets cartridge RAM bank X
+    /// at 0xA000-0xBFFF and 9X WRAM bank X at 0xD000-0xDFFF.
+    pub fn parse(code: &str) -> Result<GameSharkCode, EmulatorError> {
+        let invalid = || EmulatorError::InvalidCheatCode(code.to_string());
+        let digits = parse_hex_digits(code)
+            .filter(|digits| digits.len() == 8)
+            .ok_or_else(invalid)?;
+        let bytes: Vec<u8> = digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect();
+
+        let address = (bytes[3] as u16) << 8 | bytes[2] as u16;
+        let bank = match (bytes[0], address) {
+            (CURRENT_BANK, _) => None,
+            (0x80..=0x8F, 0xA000..=0xBFFF) => Some(bytes[0] & 0x0F),
+            (0x90..=0x97, 0xD000..=0xDFFF) => Some(bytes[0] & 0x07),
+            _ => return Err(invalid()),
+        };
+
+        Ok(GameSharkCode {
+            address,
+            value: bytes[1],
+            bank,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("0105C0D1", 0xD1C0, 0x05, None)]
+    #[case("01FF16D0", 0xD016, 0xFF, None)]
+    #[case("836300A0", 0xA000, 0x63, Some(3))]
+    #[case("920102D0", 0xD002, 0x01, Some(2))]
+    fn should_decode_code(
+        #[case] code: &str,
+        #[case] expected_address: u16,
+        #[case] expected_value: u8,
+        #[case] expected_bank: Option<u8>,
+    ) {
+        assert_eq!(
+            GameSharkCode::parse(code),
+            Ok(GameSharkCode {
+                address: expected_address,
+                value: expected_value,
+                bank: expected_bank,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("0105C0")]
+    #[case("0105C0DG")]
+    #[case("020102D0")]
+    #[case("830102D0")]
+    #[case("920100A0")]
+    fn should_reject_invalid_codes(#[case] code: &str) {
+        assert_eq!(
+            GameSharkCode::parse(code),
+            Err(EmulatorError::InvalidCheatCode(code.to_string()))
+        );
+    }
+}
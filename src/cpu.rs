@@ -11,5 +11,5 @@ mod complement;
 mod flag_registers;
 mod instruction;
 mod jump;
-mod memory_bus;
+pub mod memory_bus;
 mod registers;
@@ -9,7 +9,7 @@ use super::{
     bit::{bit_check::bit_check, bit_reset::bit_reset, bit_set::bit_set},
     complement::complement,
     instruction::{IncDecTarget, Instruction},
-    jump::{jump, jump_relative},
+    jump::{evaluate_test, jump, jump_relative},
     logical_operators::{and::and, or::or, xor::xor},
     memory_bus::MemoryBus,
     registers::Registers,
@@ -38,20 +38,35 @@ impl CPU {
         }
     }
 
-    fn step(&mut self) {
+    /// Runs one instruction and advances the rest of the system by the
+    /// clock cycles it took, which are returned. Cycles the CPU spent halted
+    /// by the bus, e.g. for a general purpose VRAM DMA, are included.
+    pub fn step(&mut self) -> u32 {
         let mut instruction_byte = self.bus.read_byte(self.pc);
         let prefixed = instruction_byte == 0xCB;
         if prefixed {
             instruction_byte = self.bus.read_byte(self.pc.wrapping_add(1));
         }
 
-        let next_pc = if let Ok(instruction) = Instruction::from_byte(instruction_byte, prefixed) {
-            self.execute(instruction)
-        } else {
-            panic!("Unkown instruction found for: 0x{:x}", instruction_byte);
-        };
+        let (next_pc, cycles) =
+            if let Ok(instruction) = Instruction::from_byte(instruction_byte, prefixed) {
+                let branch_taken = match &instruction {
+                    Instruction::JP(test) | Instruction::JR(test) => {
+                        evaluate_test(&self.registers.f, test)
+                    }
+                    _ => false,
+                };
+                let cycles = instruction.cycles(branch_taken);
+                (self.execute(instruction), cycles)
+            } else {
+                panic!("Unkown instruction found for: 0x{:x}", instruction_byte);
+            };
 
         self.pc = next_pc;
+
+        let cycles = cycles + self.bus.take_stalled_cycles();
+        self.bus.tick(cycles);
+        cycles
     }
 
     fn execute(&mut self, instruction: Instruction) -> u16 {
@@ -253,3 +268,22 @@ impl CPU {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_add_stalled_cycles_to_step() {
+        let mut cpu = CPU::new();
+        cpu.bus.set_cgb_mode(true);
+        cpu.bus.write_byte(0xFF51, 0xC0);
+        cpu.bus.write_byte(0xFF53, 0x00);
+        cpu.bus.write_byte(0xFF55, 0x01);
+
+        // Two blocks of DMA on top of a NOP, then a plain NOP
+        assert_eq!(cpu.step(), 4 + 2 * 32);
+        assert_eq!(cpu.step(), 4);
+        assert_eq!(cpu.pc, 2);
+    }
+}
@@ -61,6 +61,59 @@ impl Instruction {
         }
     }
 
+    /// Clock cycles the instruction takes, `branch_taken` only matters for
+    /// conditional jumps
+    pub fn cycles(&self, branch_taken: bool) -> u32 {
+        match self {
+            Instruction::NOP
+            | Instruction::JPHL
+            | Instruction::CCF
+            | Instruction::SCF
+            | Instruction::RRA
+            | Instruction::RLA
+            | Instruction::RRCA
+            | Instruction::RLCA
+            | Instruction::CPL => 4,
+            Instruction::JP(_) if branch_taken => 16,
+            Instruction::JP(_) => 12,
+            Instruction::JR(_) if branch_taken => 12,
+            Instruction::JR(_) => 8,
+            Instruction::ADD(target)
+            | Instruction::ADC(target)
+            | Instruction::SUB(target)
+            | Instruction::SBC(target)
+            | Instruction::AND(target)
+            | Instruction::OR(target)
+            | Instruction::XOR(target)
+            | Instruction::CP(target) => match target {
+                ArithmeticTarget::HL | ArithmeticTarget::Constant => 8,
+                _ => 4,
+            },
+            Instruction::ADDHL(_) => 8,
+            Instruction::ADDSP => 16,
+            Instruction::INC(target) | Instruction::DEC(target) => match target {
+                IncDecTarget::Byte(ArithmeticTarget::HL) => 12,
+                IncDecTarget::Byte(_) => 4,
+                IncDecTarget::Word(_) => 8,
+            },
+            Instruction::BIT(_, ArithmeticTarget::HL) => 12,
+            Instruction::BIT(_, target)
+            | Instruction::RES(_, target)
+            | Instruction::SET(_, target)
+            | Instruction::SRL(target)
+            | Instruction::RR(target)
+            | Instruction::RL(target)
+            | Instruction::RRC(target)
+            | Instruction::RLC(target)
+            | Instruction::SRA(target)
+            | Instruction::SLA(target)
+            | Instruction::SWAP(target) => match target {
+                ArithmeticTarget::HL => 16,
+                _ => 8,
+            },
+        }
+    }
+
     fn from_byte_prefixed(byte: u8) -> Result<Instruction, EmulatorError> {
         match byte {
             0x00 => Ok(Instruction::RLC(ArithmeticTarget::B)),
@@ -448,3 +501,34 @@ impl Instruction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0x00, false, false, 4)]
+    #[case(0x80, false, false, 4)]
+    #[case(0x86, false, false, 8)]
+    #[case(0x34, false, false, 12)]
+    #[case(0x03, false, false, 8)]
+    #[case(0xC3, false, true, 16)]
+    #[case(0xC2, false, false, 12)]
+    #[case(0x20, false, true, 12)]
+    #[case(0x20, false, false, 8)]
+    #[case(0xE8, false, false, 16)]
+    #[case(0x11, true, false, 8)]
+    #[case(0x46, true, false, 12)]
+    #[case(0x86, true, false, 16)]
+    fn should_count_instruction_cycles(
+        #[case] byte: u8,
+        #[case] prefixed: bool,
+        #[case] branch_taken: bool,
+        #[case] expected_cycles: u32,
+    ) {
+        let instruction = Instruction::from_byte(byte, prefixed).unwrap();
+
+        assert_eq!(instruction.cycles(branch_taken), expected_cycles);
+    }
+}
@@ -21,7 +21,7 @@ pub fn jump_relative(cpu: &CPU, test: JumpTest) -> u16 {
 fn jump_internal<F>(cpu: &CPU, test: JumpTest, instruction_size: u16, perform_jump: F) -> u16
     where F: Fn() -> u16
 {
-    let should_jump = evaluate_test(&cpu.registers.f, test);
+    let should_jump = evaluate_test(&cpu.registers.f, &test);
 
     if should_jump {
         perform_jump()
@@ -30,7 +30,7 @@ fn jump_internal<F>(cpu: &CPU, test: JumpTest, instruction_size: u16, perform_ju
     }
 }
 
-fn evaluate_test(flags: &FlagsRegister, test: JumpTest) -> bool {
+pub fn evaluate_test(flags: &FlagsRegister, test: &JumpTest) -> bool {
     match test {
         JumpTest::NotZero => !flags.zero,
         JumpTest::NotCarry => !flags.carry,
@@ -1,19 +1,836 @@
+pub mod hdma;
+pub mod lcd_mode;
+pub mod oam_dma;
+
+use std::{cell::Cell, ops::Range};
+
+use crate::{
+    cartridge::Cartridge,
+    cheats::{game_shark::GameSharkCode, Cheats},
+    power_on::{MemoryRegion, PowerOnFill},
+    ppu::Ppu,
+    sgb::Sgb,
+};
+
+use self::{
+    hdma::{Hdma, BLOCK_SIZE, CYCLES_PER_BLOCK},
+    lcd_mode::LcdMode,
+    oam_dma::OamDma,
+};
+
+const P1_REGISTER: u16 = 0xFF00;
+const IF_REGISTER: u16 = 0xFF0F;
+const DMA_REGISTER: u16 = 0xFF46;
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+/// Set in the header's CGB flag by games that support Color features
+const CGB_SUPPORT_BIT: u8 = 0x80;
+const VBK_REGISTER: u16 = 0xFF4F;
+const SVBK_REGISTER: u16 = 0xFF70;
+pub const VRAM_BANK_SIZE: usize = 0x2000;
+const VRAM_BANKS: usize = 2;
+pub const WRAM_BANK_SIZE: usize = 0x1000;
+const WRAM_BANKS: usize = 8;
+const OAM_RANGE: Range<usize> = 0xFE00..0xFEA0;
+
 pub struct MemoryBus {
     memory: [u8; 0xFFFF],
+    cartridge: Option<Cartridge>,
+    oam_dma: OamDma,
+    /// Byte the DMA last put on the bus, which is what the CPU reads while
+    /// the transfer blocks it
+    dma_value: u8,
+    /// T-cycles that didn't add up to a whole M-cycle yet
+    pending_cycles: u32,
+    /// Whether Color Game Boy hardware is enabled, taken from the header of
+    /// the inserted cartridge
+    cgb_mode: bool,
+    hdma: Hdma,
+    /// Cycles the CPU has to spend halted, e.g. during VRAM DMA
+    stalled_cycles: u32,
+    /// Both VRAM banks, bank 1 holds the CGB tile data and attributes
+    vram: Vec<u8>,
+    vram_bank: u8,
+    /// Eight 4 KiB WRAM banks, bank 0 at 0xC000 and a switchable one at 0xD000
+    wram: Vec<u8>,
+    wram_bank: u8,
+    lcd_mode: LcdMode,
+    /// Whether VRAM and OAM accesses the current LCD mode forbids are dropped
+    enforce_lcd_access: bool,
+    /// CPU accesses dropped because of the LCD mode, for diagnostics
+    blocked_accesses: Cell<u64>,
+    cheats: Cheats,
+    ppu: Ppu,
+    /// Present when running as a Super Game Boy
+    sgb: Option<Sgb>,
 }
 
 impl MemoryBus {
     pub fn new() -> MemoryBus {
-        MemoryBus {
+        MemoryBus::with_power_on_fill(&PowerOnFill::default())
+    }
+
+    /// Starts with the RAMs on the bus filled as `fill` describes
+    pub fn with_power_on_fill(fill: &PowerOnFill) -> MemoryBus {
+        let mut bus = MemoryBus {
             memory: [0x00; 0xFFFF],
-        }
+            cartridge: None,
+            oam_dma: OamDma::new(),
+            dma_value: 0xFF,
+            pending_cycles: 0,
+            cgb_mode: false,
+            hdma: Hdma::new(),
+            stalled_cycles: 0,
+            vram: vec![0x00; VRAM_BANKS * VRAM_BANK_SIZE],
+            vram_bank: 0,
+            wram: vec![0x00; WRAM_BANKS * WRAM_BANK_SIZE],
+            wram_bank: 1,
+            lcd_mode: LcdMode::HBlank,
+            enforce_lcd_access: true,
+            blocked_accesses: Cell::new(0),
+            cheats: Cheats::new(),
+            ppu: Ppu::new(),
+            sgb: None,
+        };
+
+        fill.fill(MemoryRegion::Wram, &mut bus.wram);
+        fill.fill(MemoryRegion::Vram, &mut bus.vram);
+        fill.fill(MemoryRegion::Oam, &mut bus.memory[OAM_RANGE]);
+        fill.fill(MemoryRegion::Hram, &mut bus.memory[0xFF80..0xFFFF]);
+        bus
     }
 
+    /// Reads as the CPU sees it. During OAM DMA only HRAM and the I/O
+    /// registers, which sit on their own bus, are reachable.
     pub fn read_byte(&self, address: u16) -> u8 {
-        self.memory[address as usize]
+        if self.dma_blocks(address) {
+            return self.dma_value;
+        }
+        if self.lcd_blocks(address) {
+            return 0xFF;
+        }
+        self.read_unblocked(address)
     }
 
     pub fn write_byte(&mut self, address: u16, new_value: u8) {
-        self.memory[address as usize] = new_value;
+        if self.dma_blocks(address) || self.lcd_blocks(address) {
+            return;
+        }
+        self.write_unblocked(address, new_value);
+    }
+
+    /// VRAM can't be touched during pixel transfer, OAM during OAM scan and
+    /// pixel transfer. Counts the access when it is blocked.
+    fn lcd_blocks(&self, address: u16) -> bool {
+        if !self.enforce_lcd_access {
+            return false;
+        }
+        let blocked = match address {
+            0x8000..=0x9FFF => !self.lcd_mode.vram_accessible(),
+            0xFE00..=0xFE9F => !self.lcd_mode.oam_accessible(),
+            _ => false,
+        };
+        if blocked {
+            self.blocked_accesses.set(self.blocked_accesses.get() + 1);
+        }
+        blocked
+    }
+
+    pub fn lcd_mode(&self) -> LcdMode {
+        self.lcd_mode
+    }
+
+    /// Follows the PPU every time it changes mode, can be set directly to
+    /// exercise the access rules on their own
+    pub fn set_lcd_mode(&mut self, lcd_mode: LcdMode) {
+        self.lcd_mode = lcd_mode;
+    }
+
+    /// Lets the CPU reach VRAM and OAM in every mode when disabled, like
+    /// lax emulators do
+    pub fn set_lcd_access_enforcement(&mut self, enforce: bool) {
+        self.enforce_lcd_access = enforce;
+    }
+
+    pub fn blocked_accesses(&self) -> u64 {
+        self.blocked_accesses.get()
+    }
+
+    pub fn reset_blocked_accesses(&mut self) {
+        self.blocked_accesses.set(0);
+    }
+
+    fn read_unblocked(&self, address: u16) -> u8 {
+        match (&self.cartridge, address) {
+            (Some(cartridge), 0x0000..=0x7FFF) => self
+                .cheats
+                .patch_rom_read(address, cartridge.read_rom(address)),
+            (Some(cartridge), 0xA000..=0xBFFF) => cartridge.read_ram(address),
+            (_, P1_REGISTER) => self.read_p1(),
+            (_, DMA_REGISTER) => self.oam_dma.register(),
+            (_, 0xFF40..=0xFF4B) => self.ppu.read(address),
+            (_, 0xFF51..=0xFF55) if self.cgb_mode => self.hdma.read(address),
+            (_, 0xFF68..=0xFF6B) if self.cgb_mode => self.ppu.read(address),
+            (_, 0x8000..=0x9FFF) => self.vram[self.vram_index(address)],
+            (_, 0xC000..=0xFDFF) => self.wram[self.wram_index(address)],
+            (_, VBK_REGISTER) if self.cgb_mode => 0xFE | self.vram_bank,
+            (_, SVBK_REGISTER) if self.cgb_mode => 0xF8 | self.wram_bank,
+            _ => self.memory[address as usize],
+        }
+    }
+
+    fn write_unblocked(&mut self, address: u16, new_value: u8) {
+        match (&mut self.cartridge, address) {
+            (Some(cartridge), 0x0000..=0x7FFF) => cartridge.write_rom(address, new_value),
+            (Some(cartridge), 0xA000..=0xBFFF) => cartridge.write_ram(address, new_value),
+            (_, P1_REGISTER) => {
+                self.memory[address as usize] = new_value;
+                if let Some(sgb) = self.sgb.as_mut() {
+                    sgb.write_p1(new_value);
+                }
+            }
+            (_, DMA_REGISTER) => self.oam_dma.start(new_value),
+            (_, 0xFF40..=0xFF4B) => {
+                self.ppu.write(address, new_value);
+                self.lcd_mode = self.ppu.mode();
+            }
+            (_, 0xFF68..=0xFF6B) if self.cgb_mode => self.ppu.write(address, new_value),
+            (_, 0xFF51..=0xFF55) if self.cgb_mode => {
+                let blocks = self.hdma.write(address, new_value);
+                for _ in 0..blocks {
+                    let (source, destination) = self.hdma.next_block();
+                    self.copy_vram_block(source, destination);
+                }
+            }
+            (_, 0x8000..=0x9FFF) => {
+                let index = self.vram_index(address);
+                self.vram[index] = new_value;
+            }
+            (_, 0xC000..=0xFDFF) => {
+                let index = self.wram_index(address);
+                self.wram[index] = new_value;
+            }
+            (_, VBK_REGISTER) if self.cgb_mode => self.vram_bank = new_value & 0x01,
+            (_, SVBK_REGISTER) if self.cgb_mode => self.wram_bank = new_value & 0x07,
+            _ => self.memory[address as usize] = new_value,
+        }
+    }
+
+    fn vram_index(&self, address: u16) -> usize {
+        self.vram_bank() as usize * VRAM_BANK_SIZE + (address as usize - 0x8000)
+    }
+
+    /// Echo RAM at 0xE000-0xFDFF mirrors 0xC000-0xDDFF
+    fn wram_index(&self, address: u16) -> usize {
+        let offset = (address as usize - 0xC000) % (2 * WRAM_BANK_SIZE);
+        match offset {
+            0x0000..=0x0FFF => offset,
+            _ => self.wram_bank() as usize * WRAM_BANK_SIZE + offset - WRAM_BANK_SIZE,
+        }
+    }
+
+    /// VRAM bank mapped at 0x8000-0x9FFF, always 0 outside CGB mode
+    pub fn vram_bank(&self) -> u8 {
+        if self.cgb_mode {
+            self.vram_bank
+        } else {
+            0
+        }
+    }
+
+    /// WRAM bank mapped at 0xD000-0xDFFF. Writing 0 to SVBK selects bank 1,
+    /// which is also the only bank outside CGB mode.
+    pub fn wram_bank(&self) -> u8 {
+        if self.cgb_mode {
+            self.wram_bank.max(1)
+        } else {
+            1
+        }
+    }
+
+    /// Contents of a VRAM bank regardless of the one currently mapped
+    pub fn vram(&self, bank: usize) -> &[u8] {
+        &self.vram[bank * VRAM_BANK_SIZE..(bank + 1) * VRAM_BANK_SIZE]
+    }
+
+    /// Contents of a WRAM bank regardless of the one currently mapped
+    pub fn wram(&self, bank: usize) -> &[u8] {
+        &self.wram[bank * WRAM_BANK_SIZE..(bank + 1) * WRAM_BANK_SIZE]
+    }
+
+    fn copy_vram_block(&mut self, source: u16, destination: u16) {
+        for offset in 0..BLOCK_SIZE {
+            let value = self.read_unblocked(source.wrapping_add(offset));
+            self.write_unblocked(destination + offset, value);
+        }
+        self.stalled_cycles += CYCLES_PER_BLOCK;
+    }
+
+    /// Tells the bus the LCD entered HBlank, which is when an HBlank VRAM
+    /// DMA copies its next block
+    pub fn notify_hblank(&mut self) {
+        if let Some((source, destination)) = self.hdma.hblank_block() {
+            self.copy_vram_block(source, destination);
+        }
+    }
+
+    /// Takes the cycles the CPU has to wait out before running again
+    pub fn take_stalled_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.stalled_cycles)
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.ppu.set_cgb_mode(cgb_mode);
+    }
+
+    /// Advances every component on the bus by the given amount of clock cycles
+    pub fn tick(&mut self, cycles: u32) {
+        if let Some(cartridge) = self.cartridge.as_mut() {
+            cartridge.tick(cycles);
+        }
+
+        for _ in 0..cycles {
+            self.step_ppu();
+        }
+        self.memory[IF_REGISTER as usize] |= self.ppu.take_interrupts();
+
+        self.pending_cycles += cycles;
+        while self.pending_cycles >= T_CYCLES_PER_M_CYCLE {
+            self.pending_cycles -= T_CYCLES_PER_M_CYCLE;
+            self.step_dma();
+        }
+    }
+
+    /// HBlank is when HBlank VRAM DMA copies a block, VBlank is when the
+    /// GameShark codes get written and the SGB looks at the finished frame
+    fn step_ppu(&mut self) {
+        let Some(mode) = self.ppu.step(&self.vram, &self.memory[OAM_RANGE]) else {
+            return;
+        };
+        self.lcd_mode = mode;
+        match mode {
+            LcdMode::HBlank => self.notify_hblank(),
+            LcdMode::VBlank => {
+                self.apply_game_shark_codes();
+                if let Some(sgb) = self.sgb.as_mut() {
+                    sgb.end_frame(self.ppu.framebuffer());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn sgb(&self) -> Option<&Sgb> {
+        self.sgb.as_ref()
+    }
+
+    /// Runs as a Super Game Boy, which listens for command packets on P1
+    pub fn set_sgb_mode(&mut self, sgb_mode: bool) {
+        self.sgb = sgb_mode.then(Sgb::new);
+    }
+
+    /// The SGB answers with the selected controller's ID in place of the
+    /// buttons while several are connected
+    fn read_p1(&self) -> u8 {
+        let p1 = self.memory[P1_REGISTER as usize];
+        match self.sgb.as_ref().and_then(|sgb| sgb.joypad_id(p1)) {
+            Some(id) => (p1 & 0xF0) | id,
+            None => p1,
+        }
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn dma_active(&self) -> bool {
+        self.oam_dma.active()
+    }
+
+    fn step_dma(&mut self) {
+        if let Some((source, destination)) = self.oam_dma.step() {
+            self.dma_value = self.read_unblocked(source);
+            self.memory[destination as usize] = self.dma_value;
+        }
+    }
+
+    fn dma_blocks(&self, address: u16) -> bool {
+        self.oam_dma.active() && address < 0xFF00
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    /// Cheats can be added, removed and toggled while the game runs
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    /// Writes the enabled GameShark codes into RAM, meant to run once per
+    /// frame at the start of VBlank like the real device does
+    pub fn apply_game_shark_codes(&mut self) {
+        let codes: Vec<_> = self.cheats.game_shark_codes().collect();
+        for code in codes {
+            self.write_game_shark_code(code);
+        }
+    }
+
+    fn write_game_shark_code(&mut self, code: GameSharkCode) {
+        match (code.bank, &mut self.cartridge, code.address) {
+            (Some(bank), Some(cartridge), 0xA000..=0xBFFF) => {
+                cartridge.poke_ram(bank, code.address, code.value)
+            }
+            (Some(bank), _, 0xD000..=0xDFFF) => {
+                let index =
+                    bank.max(1) as usize * WRAM_BANK_SIZE + (code.address as usize - 0xD000);
+                self.wram[index] = code.value;
+            }
+            _ => self.write_unblocked(code.address, code.value),
+        }
+    }
+
+    /// Maps the cartridge into 0x0000-0x7FFF and 0xA000-0xBFFF, replacing
+    /// the plain memory that backs those areas when no cartridge is present
+    /// Color games also switch on the CGB hardware.
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) {
+        self.set_cgb_mode(cartridge.header().cgb_flag & CGB_SUPPORT_BIT != 0);
+        self.cartridge = Some(cartridge);
+    }
+
+    pub fn eject_cartridge(&mut self) -> Option<Cartridge> {
+        self.cartridge.take()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
+    }
+}
+
+impl Default for MemoryBus {
+    fn default() -> Self {
+        MemoryBus::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cartridge::tests::rom_with_type, power_on::FillPattern};
+    use rstest::*;
+
+    #[test]
+    fn should_use_plain_memory_without_cartridge() {
+        let mut bus = MemoryBus::new();
+
+        bus.write_byte(0x2000, 0x42);
+        bus.write_byte(0xA000, 0x24);
+
+        assert_eq!(bus.read_byte(0x2000), 0x42);
+        assert_eq!(bus.read_byte(0xA000), 0x24);
+    }
+
+    #[test]
+    fn should_dispatch_cartridge_areas_to_cartridge() {
+        let mut bus = MemoryBus::new();
+        bus.insert_cartridge(Cartridge::build(rom_with_type(0x1B, 0x02, 0x02)).unwrap());
+
+        bus.write_byte(0x2000, 0x03);
+        bus.write_byte(0x0000, 0x0A);
+        bus.write_byte(0xA000, 0x24);
+
+        assert_eq!(bus.read_byte(0x4000), 0x03);
+        assert_eq!(bus.read_byte(0xA000), 0x24);
+        assert_eq!(bus.read_byte(0x2000), 0x00);
+    }
+
+    #[test]
+    fn should_fall_back_to_plain_memory_after_eject() {
+        let mut bus = MemoryBus::new();
+        bus.insert_cartridge(Cartridge::build(rom_with_type(0x19, 0x02, 0x00)).unwrap());
+
+        bus.write_byte(0x2000, 0x03);
+        assert!(bus.eject_cartridge().is_some());
+
+        assert_eq!(bus.read_byte(0x2000), 0x00);
+    }
+
+    fn bus_with_dma_source() -> MemoryBus {
+        let mut bus = MemoryBus::new();
+        for offset in 0..0xA0 {
+            bus.write_byte(0xC100 + offset, offset as u8 + 1);
+        }
+        bus
+    }
+
+    #[test]
+    fn should_copy_source_page_into_oam() {
+        let mut bus = bus_with_dma_source();
+
+        bus.write_byte(0xFF46, 0xC1);
+        bus.tick(4 + 160 * 4);
+
+        assert!(!bus.dma_active());
+        assert_eq!(bus.read_byte(0xFE00), 0x01);
+        assert_eq!(bus.read_byte(0xFE9F), 0xA0);
+        assert_eq!(bus.read_byte(0xFF46), 0xC1);
+    }
+
+    #[test]
+    fn should_block_cpu_outside_hram_during_dma() {
+        let mut bus = bus_with_dma_source();
+        bus.write_byte(0xFF80, 0x42);
+
+        bus.write_byte(0xFF46, 0xC1);
+        bus.tick(4);
+        assert_eq!(bus.read_byte(0xC000), 0x00);
+        bus.tick(3 * 4);
+
+        assert!(bus.dma_active());
+        assert_eq!(bus.read_byte(0xC000), 0x03);
+        assert_eq!(bus.read_byte(0x0150), 0x03);
+        assert_eq!(bus.read_byte(0xFF80), 0x42);
+
+        bus.write_byte(0xC000, 0x99);
+        bus.tick(157 * 4);
+        assert!(!bus.dma_active());
+        assert_eq!(bus.read_byte(0xC000), 0x00);
+    }
+
+    #[test]
+    fn should_count_m_cycles_across_ticks() {
+        let mut bus = bus_with_dma_source();
+
+        bus.write_byte(0xFF46, 0xC1);
+        bus.tick(3);
+        assert!(!bus.dma_active());
+        bus.tick(2);
+        assert!(!bus.dma_active());
+        bus.tick(3);
+
+        assert!(bus.dma_active());
+    }
+
+    fn cgb_bus_with_hdma_source() -> MemoryBus {
+        let mut bus = MemoryBus::new();
+        bus.set_cgb_mode(true);
+        for offset in 0..0x40 {
+            bus.write_byte(0xC000 + offset, offset as u8 + 1);
+        }
+        bus.write_byte(0xFF51, 0xC0);
+        bus.write_byte(0xFF52, 0x00);
+        bus.write_byte(0xFF53, 0x01);
+        bus.write_byte(0xFF54, 0x00);
+        bus
+    }
+
+    #[test]
+    fn should_copy_everything_and_stall_cpu_for_general_purpose_dma() {
+        let mut bus = cgb_bus_with_hdma_source();
+
+        bus.write_byte(0xFF55, 0x03);
+
+        assert_eq!(bus.read_byte(0x8100), 0x01);
+        assert_eq!(bus.read_byte(0x813F), 0x40);
+        assert_eq!(bus.read_byte(0xFF55), 0xFF);
+        assert_eq!(bus.take_stalled_cycles(), 4 * 32);
+        assert_eq!(bus.take_stalled_cycles(), 0);
+    }
+
+    #[test]
+    fn should_copy_one_block_per_hblank() {
+        let mut bus = cgb_bus_with_hdma_source();
+
+        bus.write_byte(0xFF55, 0x81);
+        assert_eq!(bus.read_byte(0x8100), 0x00);
+
+        bus.notify_hblank();
+        assert_eq!(bus.read_byte(0x8100), 0x01);
+        assert_eq!(bus.read_byte(0x8110), 0x00);
+        assert_eq!(bus.read_byte(0xFF55), 0x00);
+        assert_eq!(bus.take_stalled_cycles(), 32);
+
+        bus.notify_hblank();
+        bus.notify_hblank();
+        assert_eq!(bus.read_byte(0x8110), 0x11);
+        assert_eq!(bus.read_byte(0x8120), 0x00);
+        assert_eq!(bus.read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn should_ignore_hdma_registers_outside_cgb_mode() {
+        let mut bus = cgb_bus_with_hdma_source();
+        bus.set_cgb_mode(false);
+
+        bus.write_byte(0xFF55, 0x03);
+
+        assert_eq!(bus.read_byte(0x8100), 0x00);
+        assert_eq!(bus.take_stalled_cycles(), 0);
+    }
+
+    #[test]
+    fn should_enable_cgb_mode_for_color_cartridges() {
+        let mut rom = rom_with_type(0x19, 0x02, 0x00);
+        rom[0x0143] = 0xC0;
+        let mut bus = MemoryBus::new();
+
+        bus.insert_cartridge(Cartridge::build(rom).unwrap());
+
+        assert!(bus.cgb_mode());
+    }
+
+    #[rstest]
+    #[case(0x00, 1)]
+    #[case(0x01, 1)]
+    #[case(0x05, 5)]
+    #[case(0x0F, 7)]
+    fn should_switch_wram_bank_at_d000(#[case] svbk: u8, #[case] expected_bank: usize) {
+        let mut bus = MemoryBus::new();
+        bus.set_cgb_mode(true);
+
+        bus.write_byte(0xFF70, svbk);
+        bus.write_byte(0xD000, 0x42);
+        bus.write_byte(0xC000, 0x24);
+
+        assert_eq!(bus.wram_bank() as usize, expected_bank);
+        assert_eq!(bus.wram(expected_bank)[0], 0x42);
+        assert_eq!(bus.wram(0)[0], 0x24);
+        assert_eq!(bus.read_byte(0xF000), 0x42);
+    }
+
+    #[test]
+    fn should_switch_vram_bank() {
+        let mut bus = MemoryBus::new();
+        bus.set_cgb_mode(true);
+        bus.write_byte(0x9800, 0x11);
+
+        bus.write_byte(0xFF4F, 0x01);
+        bus.write_byte(0x9800, 0x22);
+
+        assert_eq!(bus.read_byte(0xFF4F), 0xFF);
+        assert_eq!(bus.read_byte(0x9800), 0x22);
+        assert_eq!(bus.vram(0)[0x1800], 0x11);
+        assert_eq!(bus.vram(1)[0x1800], 0x22);
+    }
+
+    #[rstest]
+    #[case(true, 0xC1)]
+    #[case(false, 0x40)]
+    fn should_reach_palette_ram_only_in_cgb_mode(#[case] cgb_mode: bool, #[case] expected: u8) {
+        let mut bus = MemoryBus::new();
+        bus.set_cgb_mode(cgb_mode);
+
+        bus.write_byte(0xFF6A, 0x80);
+        bus.write_byte(0xFF6B, 0x34);
+
+        assert_eq!(bus.ppu().read(0xFF6A), expected);
+    }
+
+    #[rstest]
+    #[case(true, 0x3F)]
+    #[case(false, 0x30)]
+    fn should_send_p1_pulses_to_sgb(#[case] sgb_mode: bool, #[case] expected: u8) {
+        let mut bus = MemoryBus::new();
+        bus.set_sgb_mode(sgb_mode);
+        // MLT_REQ for two players: 0x89, 0x01 and zeros, then a 0 stop bit
+        let mut packet = [0x00; 16];
+        packet[0..2].copy_from_slice(&[0x89, 0x01]);
+
+        bus.write_byte(P1_REGISTER, 0x00);
+        bus.write_byte(P1_REGISTER, 0x30);
+        for bit in 0..129 {
+            let one = bit < 128 && packet[bit / 8] & (1 << (bit % 8)) != 0;
+            bus.write_byte(P1_REGISTER, if one { 0x10 } else { 0x20 });
+            bus.write_byte(P1_REGISTER, 0x30);
+        }
+
+        assert_eq!(bus.read_byte(P1_REGISTER), expected);
+        assert_eq!(bus.sgb().map(|sgb| sgb.players()), sgb_mode.then_some(2));
+    }
+
+    #[test]
+    fn should_keep_single_banks_outside_cgb_mode() {
+        let mut bus = MemoryBus::new();
+
+        bus.write_byte(0xFF4F, 0x01);
+        bus.write_byte(0xFF70, 0x03);
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xD000, 0x22);
+
+        assert_eq!(bus.vram_bank(), 0);
+        assert_eq!(bus.wram_bank(), 1);
+        assert_eq!(bus.vram(0)[0], 0x11);
+        assert_eq!(bus.wram(1)[0], 0x22);
+    }
+
+    #[rstest]
+    #[case(LcdMode::HBlank, 0x8000, true)]
+    #[case(LcdMode::VBlank, 0x9FFF, true)]
+    #[case(LcdMode::OamScan, 0x8000, true)]
+    #[case(LcdMode::PixelTransfer, 0x8000, false)]
+    #[case(LcdMode::HBlank, 0xFE00, true)]
+    #[case(LcdMode::VBlank, 0xFE9F, true)]
+    #[case(LcdMode::OamScan, 0xFE00, false)]
+    #[case(LcdMode::PixelTransfer, 0xFE9F, false)]
+    #[case(LcdMode::PixelTransfer, 0xC000, true)]
+    fn should_honour_lcd_mode(
+        #[case] lcd_mode: LcdMode,
+        #[case] address: u16,
+        #[case] expected_accessible: bool,
+    ) {
+        let mut bus = MemoryBus::new();
+        bus.write_byte(address, 0x42);
+        bus.set_lcd_mode(lcd_mode);
+
+        bus.write_byte(address, 0x24);
+        let value = bus.read_byte(address);
+
+        let expected_value = if expected_accessible { 0x24 } else { 0xFF };
+        assert_eq!(value, expected_value);
+        assert_eq!(
+            bus.blocked_accesses(),
+            if expected_accessible { 0 } else { 2 }
+        );
+    }
+
+    #[test]
+    fn should_allow_every_access_without_enforcement() {
+        let mut bus = MemoryBus::new();
+        bus.set_lcd_access_enforcement(false);
+        bus.set_lcd_mode(LcdMode::PixelTransfer);
+
+        bus.write_byte(0x8000, 0x42);
+
+        assert_eq!(bus.read_byte(0x8000), 0x42);
+        assert_eq!(bus.blocked_accesses(), 0);
+    }
+
+    #[test]
+    fn should_let_vram_dma_through_during_pixel_transfer() {
+        let mut bus = cgb_bus_with_hdma_source();
+        bus.set_lcd_mode(LcdMode::PixelTransfer);
+
+        bus.write_byte(0xFF55, 0x00);
+        bus.set_lcd_mode(LcdMode::HBlank);
+
+        assert_eq!(bus.read_byte(0x8100), 0x01);
+        assert_eq!(bus.blocked_accesses(), 0);
+    }
+
+    #[test]
+    fn should_fill_ram_regions_at_power_on() {
+        let fill = PowerOnFill {
+            wram: FillPattern::Ones,
+            oam: FillPattern::Ones,
+            ..PowerOnFill::default()
+        };
+
+        let bus = MemoryBus::with_power_on_fill(&fill);
+
+        assert_eq!(bus.read_byte(0xC000), 0xFF);
+        assert_eq!(bus.read_byte(0xDFFF), 0xFF);
+        assert_eq!(bus.read_byte(0xFE9F), 0xFF);
+        assert_eq!(bus.read_byte(0xFEA0), 0x00);
+        assert_eq!(bus.read_byte(0xFF80), 0x00);
+        assert_eq!(bus.read_byte(0x8000), 0x00);
+    }
+
+    #[test]
+    fn should_reproduce_random_power_on_state() {
+        let fill = PowerOnFill::new(FillPattern::Random, 42);
+
+        let first = MemoryBus::with_power_on_fill(&fill);
+        let second = MemoryBus::with_power_on_fill(&fill);
+
+        assert_eq!(first.wram(3), second.wram(3));
+        assert_eq!(first.read_byte(0xFF90), second.read_byte(0xFF90));
+    }
+
+    #[rstest]
+    #[case(0x01, 0x42)]
+    #[case(0x02, 0x02)]
+    fn should_patch_rom_reads_with_game_genie(#[case] rom_bank: u8, #[case] expected: u8) {
+        let mut bus = MemoryBus::new();
+        bus.insert_cartridge(Cartridge::build(rom_with_type(0x1B, 0x02, 0x03)).unwrap());
+        bus.cheats_mut().add("420-00B-E0E").unwrap();
+
+        bus.write_byte(0x2000, rom_bank);
+
+        assert_eq!(bus.read_byte(0x4000), expected);
+    }
+
+    #[test]
+    fn should_write_game_shark_codes_into_their_banks() {
+        let mut bus = MemoryBus::new();
+        bus.insert_cartridge(Cartridge::build(rom_with_type(0x1B, 0x02, 0x03)).unwrap());
+        bus.cheats_mut().add("824200A0").unwrap();
+        bus.cheats_mut().add("932400D0").unwrap();
+        bus.cheats_mut().add("011100C0").unwrap();
+
+        bus.apply_game_shark_codes();
+
+        bus.write_byte(0x0000, 0x0A);
+        bus.write_byte(0x4000, 0x02);
+        assert_eq!(bus.read_byte(0xA000), 0x42);
+        bus.write_byte(0x4000, 0x00);
+        assert_eq!(bus.read_byte(0xA000), 0x00);
+        assert_eq!(bus.wram(3)[0], 0x24);
+        assert_eq!(bus.read_byte(0xD000), 0x00);
+        assert_eq!(bus.read_byte(0xC000), 0x11);
+    }
+
+    #[test]
+    fn should_skip_disabled_game_shark_codes() {
+        let mut bus = MemoryBus::new();
+        let index = bus.cheats_mut().add("011100C0").unwrap();
+        bus.cheats_mut().set_enabled(index, false);
+
+        bus.apply_game_shark_codes();
+
+        assert_eq!(bus.read_byte(0xC000), 0x00);
+    }
+
+    #[test]
+    fn should_drive_lcd_mode_and_interrupts_from_ppu() {
+        let mut bus = MemoryBus::new();
+
+        bus.write_byte(0xFF40, 0x80);
+        assert_eq!(bus.lcd_mode(), LcdMode::OamScan);
+        bus.tick(100);
+        assert_eq!(bus.lcd_mode(), LcdMode::PixelTransfer);
+        assert_eq!(bus.read_byte(0x8000), 0xFF);
+
+        bus.tick(144 * 456 - 100);
+
+        assert_eq!(bus.lcd_mode(), LcdMode::VBlank);
+        assert_eq!(bus.read_byte(0xFF44), 144);
+        assert_eq!(bus.read_byte(0xFF0F) & 0x01, 0x01);
+        assert_eq!(bus.ppu().frames(), 1);
+    }
+
+    #[test]
+    fn should_run_hblank_dma_and_game_shark_codes_from_ppu() {
+        let mut bus = cgb_bus_with_hdma_source();
+        bus.cheats_mut().add("014200C1").unwrap();
+        bus.write_byte(0xFF55, 0x81);
+        bus.write_byte(0xFF40, 0x80);
+
+        bus.tick(456);
+        assert_eq!(bus.vram(0)[0x100], 0x01);
+        assert_eq!(bus.vram(0)[0x110], 0x00);
+        assert_eq!(bus.read_byte(0xC100), 0x00);
+
+        bus.tick(143 * 456);
+
+        assert_eq!(bus.vram(0)[0x110], 0x11);
+        assert_eq!(bus.read_byte(0xC100), 0x42);
     }
 }
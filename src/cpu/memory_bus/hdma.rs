@@ -0,0 +1,171 @@
+pub const BLOCK_SIZE: u16 = 0x10;
+/// Clock cycles the CPU is halted for each block copied. Double speed mode
+/// isn't modelled, so this is always the normal speed value.
+pub const CYCLES_PER_BLOCK: u32 = 32;
+const VRAM_START: u16 = 0x8000;
+const VRAM_OFFSET_MASK: u16 = 0x1FF0;
+const HBLANK_MODE_BIT: u8 = 0b1000_0000;
+
+/// CGB VRAM DMA behind HDMA1-HDMA5 (0xFF51-0xFF55). A general purpose
+/// transfer copies everything at once while the CPU waits, an HBlank
+/// transfer copies one 0x10 byte block at the start of every HBlank until
+/// it is done or cancelled.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Hdma {
+    source: u16,
+    /// Offset into VRAM, the destination always stays inside it
+    destination: u16,
+    /// Blocks left to copy
+    remaining: u8,
+    hblank_active: bool,
+}
+
+impl Hdma {
+    pub fn new() -> Hdma {
+        Hdma::default()
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            // Bit 7 clear while an HBlank transfer runs, the low bits give
+            // the blocks left minus one, 0xFF once everything was copied
+            0xFF55 if self.hblank_active => self.remaining.wrapping_sub(1) & !HBLANK_MODE_BIT,
+            0xFF55 => self.remaining.wrapping_sub(1) | HBLANK_MODE_BIT,
+            _ => 0xFF,
+        }
+    }
+
+    /// Returns the number of blocks a general purpose transfer started by
+    /// this write wants copied right away
+    pub fn write(&mut self, address: u16, new_value: u8) -> u8 {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | (new_value as u16) << 8,
+            0xFF52 => self.source = (self.source & 0xFF00) | (new_value as u16 & 0xF0),
+            0xFF53 => {
+                self.destination = (self.destination & 0x00FF) | (new_value as u16 & 0x1F) << 8
+            }
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (new_value as u16 & 0xF0),
+            0xFF55 => return self.start(new_value),
+            _ => (),
+        }
+        0
+    }
+
+    pub fn hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    /// Takes the next block of an HBlank transfer, if one is running
+    pub fn hblank_block(&mut self) -> Option<(u16, u16)> {
+        if !self.hblank_active {
+            return None;
+        }
+        let block = self.next_block();
+        if self.remaining == 0 {
+            self.hblank_active = false;
+        }
+        Some(block)
+    }
+
+    /// Source and destination of the next block, advancing past it
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, VRAM_START | self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & VRAM_OFFSET_MASK;
+        self.remaining = self.remaining.saturating_sub(1);
+        block
+    }
+
+    fn start(&mut self, new_value: u8) -> u8 {
+        let hblank = new_value & HBLANK_MODE_BIT != 0;
+        if self.hblank_active && !hblank {
+            // Cancels the HBlank transfer, leaving the remaining length readable
+            self.hblank_active = false;
+            return 0;
+        }
+
+        self.remaining = (new_value & !HBLANK_MODE_BIT) + 1;
+        self.hblank_active = hblank;
+        if hblank {
+            0
+        } else {
+            self.remaining
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn hdma(source: u16, destination: u16) -> Hdma {
+        let mut hdma = Hdma::new();
+        hdma.write(0xFF51, (source >> 8) as u8);
+        hdma.write(0xFF52, source as u8);
+        hdma.write(0xFF53, (destination >> 8) as u8);
+        hdma.write(0xFF54, destination as u8);
+        hdma
+    }
+
+    #[rstest]
+    #[case(0xC123, 0x8456, 0xC120, 0x8450)]
+    #[case(0x4000, 0x9FF0, 0x4000, 0x9FF0)]
+    #[case(0xD000, 0xE000, 0xD000, 0x8000)]
+    #[case(0xD000, 0x1230, 0xD000, 0x9230)]
+    fn should_align_addresses_to_blocks(
+        #[case] source: u16,
+        #[case] destination: u16,
+        #[case] expected_source: u16,
+        #[case] expected_destination: u16,
+    ) {
+        let mut hdma = hdma(source, destination);
+
+        assert_eq!(hdma.next_block(), (expected_source, expected_destination));
+    }
+
+    #[test]
+    fn should_request_every_block_for_general_purpose_transfer() {
+        let mut hdma = hdma(0xC000, 0x8000);
+
+        assert_eq!(hdma.write(0xFF55, 0x03), 4);
+        assert!(!hdma.hblank_active());
+    }
+
+    #[test]
+    fn should_count_down_hblank_blocks() {
+        let mut hdma = hdma(0xC000, 0x8000);
+
+        assert_eq!(hdma.write(0xFF55, 0x81), 0);
+        assert_eq!(hdma.read(0xFF55), 0x01);
+
+        assert_eq!(hdma.hblank_block(), Some((0xC000, 0x8000)));
+        assert_eq!(hdma.read(0xFF55), 0x00);
+        assert_eq!(hdma.hblank_block(), Some((0xC010, 0x8010)));
+        assert_eq!(hdma.read(0xFF55), 0xFF);
+        assert_eq!(hdma.hblank_block(), None);
+    }
+
+    #[test]
+    fn should_keep_remaining_length_after_cancel() {
+        let mut hdma = hdma(0xC000, 0x8000);
+        hdma.write(0xFF55, 0x85);
+        hdma.hblank_block();
+
+        assert_eq!(hdma.write(0xFF55, 0x00), 0);
+
+        assert!(!hdma.hblank_active());
+        assert_eq!(hdma.read(0xFF55), 0x84);
+        assert_eq!(hdma.hblank_block(), None);
+    }
+
+    #[test]
+    fn should_wrap_destination_inside_vram() {
+        let mut hdma = hdma(0xC000, 0x9FF0);
+        hdma.write(0xFF55, 0x81);
+
+        hdma.hblank_block();
+
+        assert_eq!(hdma.hblank_block(), Some((0xC010, 0x8000)));
+    }
+}
@@ -0,0 +1,54 @@
+/// Mode the LCD controller reports in the low bits of STAT. It decides
+/// whether the CPU can reach VRAM and OAM.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LcdMode {
+    /// Also reported while the LCD is off
+    #[default]
+    HBlank,
+    VBlank,
+    OamScan,
+    PixelTransfer,
+}
+
+impl LcdMode {
+    pub fn vram_accessible(&self) -> bool {
+        *self != LcdMode::PixelTransfer
+    }
+
+    pub fn oam_accessible(&self) -> bool {
+        matches!(self, LcdMode::HBlank | LcdMode::VBlank)
+    }
+}
+
+impl std::convert::From<LcdMode> for u8 {
+    fn from(mode: LcdMode) -> Self {
+        match mode {
+            LcdMode::HBlank => 0,
+            LcdMode::VBlank => 1,
+            LcdMode::OamScan => 2,
+            LcdMode::PixelTransfer => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(LcdMode::HBlank, 0, true, true)]
+    #[case(LcdMode::VBlank, 1, true, true)]
+    #[case(LcdMode::OamScan, 2, true, false)]
+    #[case(LcdMode::PixelTransfer, 3, false, false)]
+    fn should_describe_mode(
+        #[case] mode: LcdMode,
+        #[case] expected_bits: u8,
+        #[case] expected_vram: bool,
+        #[case] expected_oam: bool,
+    ) {
+        assert_eq!(u8::from(mode), expected_bits);
+        assert_eq!(mode.vram_accessible(), expected_vram);
+        assert_eq!(mode.oam_accessible(), expected_oam);
+    }
+}
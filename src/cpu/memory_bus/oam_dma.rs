@@ -0,0 +1,128 @@
+pub const OAM_START: u16 = 0xFE00;
+pub const OAM_SIZE: u16 = 0xA0;
+/// M-cycles between the write to 0xFF46 and the first byte being copied
+const STARTUP_DELAY: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transfer {
+    source: u16,
+    index: u16,
+}
+
+/// OAM DMA controller behind 0xFF46. It copies 160 bytes from
+/// `source << 8` into OAM, one byte per M-cycle, after a short start-up
+/// delay. Writing the register again restarts the copy from the first
+/// byte, with the old transfer running on until the new one takes over.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OamDma {
+    register: u8,
+    /// Source of a transfer that was requested but hasn't started yet, with
+    /// the M-cycles left before it does
+    pending: Option<(u16, u8)>,
+    transfer: Option<Transfer>,
+}
+
+impl OamDma {
+    pub fn new() -> OamDma {
+        OamDma::default()
+    }
+
+    pub fn register(&self) -> u8 {
+        self.register
+    }
+
+    pub fn start(&mut self, new_value: u8) {
+        self.register = new_value;
+        self.pending = Some((source_address(new_value), STARTUP_DELAY));
+    }
+
+    /// Whether a transfer is using the bus, which blocks the CPU
+    pub fn active(&self) -> bool {
+        self.transfer.is_some()
+    }
+
+    /// Advances one M-cycle and returns the addresses of the byte to copy,
+    /// source first, if a byte is copied during it
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        if let Some((source, delay)) = self.pending {
+            if delay == 0 {
+                self.pending = None;
+                self.transfer = Some(Transfer { source, index: 0 });
+            } else {
+                self.pending = Some((source, delay - 1));
+            }
+        }
+
+        let transfer = self.transfer.as_mut()?;
+        let copy = (transfer.source + transfer.index, OAM_START + transfer.index);
+        transfer.index += 1;
+        if transfer.index == OAM_SIZE {
+            self.transfer = None;
+        }
+        Some(copy)
+    }
+}
+
+/// Sources past 0xDF00 read from work RAM through its echo
+fn source_address(new_value: u8) -> u16 {
+    let address = (new_value as u16) << 8;
+    match address {
+        0xE000..=0xFFFF => address - 0x2000,
+        _ => address,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0x00, 0x0000)]
+    #[case(0xC1, 0xC100)]
+    #[case(0xDF, 0xDF00)]
+    #[case(0xE0, 0xC000)]
+    #[case(0xFE, 0xDE00)]
+    fn should_copy_from_source_page(#[case] new_value: u8, #[case] expected_source: u16) {
+        let mut dma = OamDma::new();
+
+        dma.start(new_value);
+        dma.step();
+
+        assert_eq!(dma.step(), Some((expected_source, OAM_START)));
+        assert_eq!(dma.register(), new_value);
+    }
+
+    #[test]
+    fn should_copy_one_byte_per_m_cycle_after_startup_delay() {
+        let mut dma = OamDma::new();
+        dma.start(0xC0);
+
+        assert_eq!(dma.step(), None);
+        assert!(!dma.active());
+
+        let copies: Vec<_> = (0..OAM_SIZE).filter_map(|_| dma.step()).collect();
+
+        assert_eq!(copies.len(), OAM_SIZE as usize);
+        assert_eq!(copies[0xA0 - 1], (0xC09F, 0xFE9F));
+        assert!(!dma.active());
+        assert_eq!(dma.step(), None);
+    }
+
+    #[test]
+    fn should_restart_when_retriggered_mid_transfer() {
+        let mut dma = OamDma::new();
+        dma.start(0xC0);
+        for _ in 0..11 {
+            dma.step();
+        }
+
+        dma.start(0xD0);
+
+        // The old transfer keeps going during the new one's delay
+        assert_eq!(dma.step(), Some((0xC00A, 0xFE0A)));
+        assert_eq!(dma.step(), Some((0xD000, 0xFE00)));
+        let remaining = (0..OAM_SIZE).filter_map(|_| dma.step()).count();
+        assert_eq!(remaining, OAM_SIZE as usize - 1);
+    }
+}
@@ -0,0 +1,49 @@
+/// Reflected polynomial of the CRC-32 used by zip, PNG and the patch formats
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// CRC-32 of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    finish(update(start(), data))
+}
+
+/// Initial value for a CRC computed in several pieces with `update`
+pub fn start() -> u32 {
+    0xFFFF_FFFF
+}
+
+pub fn update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+pub fn finish(crc: u32) -> u32 {
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(b"", 0x0000_0000)]
+    #[case(b"a", 0xE8B7_BE43)]
+    #[case(b"123456789", 0xCBF4_3926)]
+    fn should_compute_crc32(#[case] data: &[u8], #[case] expected: u32) {
+        assert_eq!(crc32(data), expected);
+    }
+
+    #[test]
+    fn should_compute_same_crc_in_pieces() {
+        let crc = finish(update(update(start(), b"1234"), b"56789"));
+
+        assert_eq!(crc, crc32(b"123456789"));
+    }
+}
@@ -2,4 +2,23 @@
 pub enum EmulatorError {
     OutOfBoundsIndex(u8),
     UnknownInstruction(u8),
+    RomTooSmall(usize),
+    UnsupportedCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    InvalidPgm,
+    InvalidImageSize(usize, usize),
+    Io(std::io::ErrorKind),
+    InvalidPatch,
+    /// Expected and actual CRC32 of a patch, its source or its target
+    PatchChecksumMismatch(u32, u32),
+    InvalidCheatCode(String),
+    /// Offending line of a palette file, or the missing layer
+    InvalidPalette(String),
+}
+
+impl std::convert::From<std::io::Error> for EmulatorError {
+    fn from(error: std::io::Error) -> Self {
+        EmulatorError::Io(error.kind())
+    }
 }
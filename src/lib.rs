@@ -1,2 +1,10 @@
+pub mod cartridge;
+pub mod cheats;
 pub mod cpu;
+pub mod crc32;
 pub mod emulator_error;
+pub mod patch;
+pub mod power_on;
+pub mod ppu;
+pub mod sgb;
+pub mod video;
@@ -0,0 +1,193 @@
+pub mod bps;
+pub mod ips;
+pub mod ups;
+
+use std::path::{Path, PathBuf};
+
+use crate::emulator_error::EmulatorError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+    Ups,
+}
+
+impl PatchFormat {
+    /// Recognises the format from the magic at the start of the patch
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(ips::MAGIC) {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(bps::MAGIC) {
+            Some(PatchFormat::Bps)
+        } else if patch.starts_with(ups::MAGIC) {
+            Some(PatchFormat::Ups)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PatchFormat::Ips => "ips",
+            PatchFormat::Bps => "bps",
+            PatchFormat::Ups => "ups",
+        }
+    }
+}
+
+/// Applies an IPS, BPS or UPS patch to a ROM image
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => ips::apply(rom, patch),
+        Some(PatchFormat::Bps) => bps::apply(rom, patch),
+        Some(PatchFormat::Ups) => ups::apply(rom, patch),
+        None => Err(EmulatorError::InvalidPatch),
+    }
+}
+
+/// Patch next to the ROM sharing its file stem, e.g. `game.ips` for
+/// `game.gb`. BPS wins over UPS and UPS over IPS when there are several.
+pub fn find_patch(rom_path: &Path) -> Option<PathBuf> {
+    [PatchFormat::Bps, PatchFormat::Ups, PatchFormat::Ips]
+        .iter()
+        .map(|format| rom_path.with_extension(format.extension()))
+        .find(|path| path.is_file())
+}
+
+/// Largest ROM a Game Boy mapper can address, MBC5's 8 MiB
+const MAX_ROM_SIZE: usize = 0x80_0000;
+
+/// Target size from a BPS or UPS header, refused before anything is
+/// allocated when no Game Boy ROM could be that big
+fn check_target_size(size: usize) -> Result<usize, EmulatorError> {
+    if size > MAX_ROM_SIZE {
+        return Err(EmulatorError::InvalidPatch);
+    }
+    Ok(size)
+}
+
+/// Variable length integer shared by BPS and UPS. Every byte carries 7 bits
+/// and the encoding is offset so each value has exactly one representation.
+fn read_number(patch: &[u8], position: &mut usize) -> Result<usize, EmulatorError> {
+    let mut value: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte = *patch.get(*position).ok_or(EmulatorError::InvalidPatch)?;
+        *position += 1;
+        value = (byte as usize & 0x7F)
+            .checked_mul(shift)
+            .and_then(|part| value.checked_add(part))
+            .ok_or(EmulatorError::InvalidPatch)?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_shl(7).ok_or(EmulatorError::InvalidPatch)?;
+        value = value
+            .checked_add(shift)
+            .ok_or(EmulatorError::InvalidPatch)?;
+    }
+}
+
+/// Source, target and patch checksums stored in the last 12 bytes of BPS
+/// and UPS patches. Checks the one covering the patch itself.
+fn read_footer(patch: &[u8]) -> Result<(u32, u32), EmulatorError> {
+    if patch.len() < 12 {
+        return Err(EmulatorError::InvalidPatch);
+    }
+    let footer = &patch[patch.len() - 12..];
+    let checksum = |index: usize| {
+        u32::from_le_bytes([
+            footer[index],
+            footer[index + 1],
+            footer[index + 2],
+            footer[index + 3],
+        ])
+    };
+
+    let actual = crate::crc32::crc32(&patch[..patch.len() - 4]);
+    if checksum(8) != actual {
+        return Err(EmulatorError::PatchChecksumMismatch(checksum(8), actual));
+    }
+    Ok((checksum(0), checksum(4)))
+}
+
+fn verify_checksum(data: &[u8], expected: u32) -> Result<(), EmulatorError> {
+    let actual = crate::crc32::crc32(data);
+    if actual != expected {
+        return Err(EmulatorError::PatchChecksumMismatch(expected, actual));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rstest::*;
+
+    pub(crate) fn write_number(patch: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                patch.push(byte | 0x80);
+                return;
+            }
+            patch.push(byte);
+            value -= 1;
+        }
+    }
+
+    /// Appends the source, target and patch checksums
+    pub(crate) fn write_footer(patch: &mut Vec<u8>, source: &[u8], target: &[u8]) {
+        patch.extend_from_slice(&crate::crc32::crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crate::crc32::crc32(target).to_le_bytes());
+        let checksum = crate::crc32::crc32(patch);
+        patch.extend_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(0x7F)]
+    #[case(0x80)]
+    #[case(0x4080)]
+    #[case(0x12_3456)]
+    fn should_round_trip_numbers(#[case] value: usize) {
+        let mut patch = Vec::new();
+        write_number(&mut patch, value);
+        let mut position = 0;
+
+        assert_eq!(read_number(&patch, &mut position), Ok(value));
+        assert_eq!(position, patch.len());
+    }
+
+    #[rstest]
+    #[case(b"PATCHEOF", Some(PatchFormat::Ips))]
+    #[case(b"BPS1", Some(PatchFormat::Bps))]
+    #[case(b"UPS1", Some(PatchFormat::Ups))]
+    #[case(b"GB", None)]
+    fn should_detect_format(#[case] patch: &[u8], #[case] expected: Option<PatchFormat>) {
+        assert_eq!(PatchFormat::detect(patch), expected);
+    }
+
+    #[test]
+    fn should_reject_unknown_patches() {
+        assert_eq!(
+            apply_patch(&[0x00; 4], b"NOPE"),
+            Err(EmulatorError::InvalidPatch)
+        );
+    }
+
+    #[test]
+    fn should_find_patch_next_to_rom() {
+        let dir = crate::cartridge::save_file::tests::scratch_dir("find-patch");
+        let rom_path = dir.join("game.gb");
+        assert_eq!(find_patch(&rom_path), None);
+
+        std::fs::write(dir.join("game.ips"), b"PATCHEOF").unwrap();
+        assert_eq!(find_patch(&rom_path), Some(dir.join("game.ips")));
+
+        std::fs::write(dir.join("game.bps"), b"BPS1").unwrap();
+        assert_eq!(find_patch(&rom_path), Some(dir.join("game.bps")));
+    }
+}
@@ -0,0 +1,219 @@
+use crate::emulator_error::EmulatorError;
+
+use super::{check_target_size, read_footer, read_number, verify_checksum};
+
+pub const MAGIC: &[u8] = b"BPS1";
+const FOOTER_SIZE: usize = 12;
+
+/// Applies a BPS patch, checking the CRC32 of the patch, the source ROM
+/// and the patched ROM
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    let (source_checksum, target_checksum) = read_footer(patch)?;
+    verify_checksum(rom, source_checksum)?;
+
+    let mut position = MAGIC.len();
+    let source_size = read_number(patch, &mut position)?;
+    let target_size = check_target_size(read_number(patch, &mut position)?)?;
+    let metadata_size = read_number(patch, &mut position)?;
+    let actions_end = patch.len() - FOOTER_SIZE;
+    position = position
+        .checked_add(metadata_size)
+        .filter(|position| *position <= actions_end)
+        .ok_or(EmulatorError::InvalidPatch)?;
+    if source_size != rom.len() {
+        return Err(EmulatorError::InvalidPatch);
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    while position < actions_end {
+        let action = read_number(patch, &mut position)?;
+        let length = (action >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err(EmulatorError::InvalidPatch);
+        }
+        match action & 0x03 {
+            // Source read: same bytes as the source at the same offset
+            0 => {
+                let start = target.len();
+                let bytes = rom
+                    .get(start..start + length)
+                    .ok_or(EmulatorError::InvalidPatch)?;
+                target.extend_from_slice(bytes);
+            }
+            // Target read: bytes stored in the patch
+            1 => {
+                let end = position
+                    .checked_add(length)
+                    .filter(|end| *end <= actions_end)
+                    .ok_or(EmulatorError::InvalidPatch)?;
+                target.extend_from_slice(&patch[position..end]);
+                position = end;
+            }
+            // Source copy: bytes from anywhere in the source
+            2 => {
+                source_offset = relative_offset(patch, &mut position, source_offset)?;
+                let end = source_offset
+                    .checked_add(length)
+                    .ok_or(EmulatorError::InvalidPatch)?;
+                let bytes = rom
+                    .get(source_offset..end)
+                    .ok_or(EmulatorError::InvalidPatch)?;
+                target.extend_from_slice(bytes);
+                source_offset = end;
+            }
+            // Target copy: bytes already written, possibly overlapping the
+            // ones being written to repeat a pattern
+            _ => {
+                target_offset = relative_offset(patch, &mut position, target_offset)?;
+                for _ in 0..length {
+                    let byte = *target
+                        .get(target_offset)
+                        .ok_or(EmulatorError::InvalidPatch)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(EmulatorError::InvalidPatch);
+    }
+    verify_checksum(&target, target_checksum)?;
+    Ok(target)
+}
+
+/// Offsets are stored relative to the previous one, sign in the lowest bit
+fn relative_offset(
+    patch: &[u8],
+    position: &mut usize,
+    current: usize,
+) -> Result<usize, EmulatorError> {
+    let data = read_number(patch, position)?;
+    let distance = data >> 1;
+    let offset = if data & 0x01 != 0 {
+        current.checked_sub(distance)
+    } else {
+        current.checked_add(distance)
+    };
+    offset.ok_or(EmulatorError::InvalidPatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{write_footer, write_number};
+    use super::*;
+    use rstest::*;
+
+    fn action(patch: &mut Vec<u8>, command: usize, length: usize) {
+        write_number(patch, (length - 1) << 2 | command);
+    }
+
+    fn header(source: &[u8], target_size: usize) -> Vec<u8> {
+        let mut patch = MAGIC.to_vec();
+        write_number(&mut patch, source.len());
+        write_number(&mut patch, target_size);
+        write_number(&mut patch, 0);
+        patch
+    }
+
+    /// Keeps the first two bytes, writes 0xAB, copies source bytes 4-5 and
+    /// then repeats the last two target bytes twice
+    fn patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = header(source, target.len());
+        action(&mut patch, 0, 2);
+        action(&mut patch, 1, 1);
+        patch.push(0xAB);
+        action(&mut patch, 2, 2);
+        write_number(&mut patch, 4 << 1);
+        action(&mut patch, 3, 4);
+        write_number(&mut patch, 3 << 1);
+        write_footer(&mut patch, source, target);
+        patch
+    }
+
+    const SOURCE: [u8; 6] = [0x10, 0x11, 0x12, 0x13, 0x14, 0x15];
+    const TARGET: [u8; 9] = [0x10, 0x11, 0xAB, 0x14, 0x15, 0x14, 0x15, 0x14, 0x15];
+
+    #[test]
+    fn should_apply_every_action() {
+        let patch = patch(&SOURCE, &TARGET);
+
+        assert_eq!(apply(&SOURCE, &patch), Ok(TARGET.to_vec()));
+    }
+
+    #[test]
+    fn should_reject_wrong_source() {
+        let patch = patch(&SOURCE, &TARGET);
+        let mut source = SOURCE;
+        source[0] = 0x00;
+
+        assert!(matches!(
+            apply(&source, &patch),
+            Err(EmulatorError::PatchChecksumMismatch(_, _))
+        ));
+    }
+
+    #[test]
+    fn should_reject_corrupted_patch() {
+        let mut patch = patch(&SOURCE, &TARGET);
+        let index = patch.len() - 14;
+        patch[index] ^= 0xFF;
+
+        assert!(matches!(
+            apply(&SOURCE, &patch),
+            Err(EmulatorError::PatchChecksumMismatch(_, _))
+        ));
+    }
+
+    /// Header with the given sizes followed by `actions`, checksums valid
+    fn malformed(target_size: usize, metadata_size: usize, actions: &[u8]) -> Vec<u8> {
+        let mut patch = MAGIC.to_vec();
+        write_number(&mut patch, SOURCE.len());
+        write_number(&mut patch, target_size);
+        write_number(&mut patch, metadata_size);
+        patch.extend_from_slice(actions);
+        write_footer(&mut patch, &SOURCE, &TARGET);
+        patch
+    }
+
+    #[rstest]
+    // Target size no ROM could have
+    #[case(usize::MAX, 0, vec![])]
+    // Metadata running past the end of the patch
+    #[case(TARGET.len(), usize::MAX, vec![])]
+    #[case(TARGET.len(), 40, vec![])]
+    // Target read longer than the target
+    #[case(2, 0, vec![0x89, 0xAB, 0xAB, 0xAB])]
+    // Target copy repeating past the target
+    #[case(4, 0, vec![0x84, 0xFF, 0x80])]
+    // Source copy from far outside the source
+    #[case(TARGET.len(), 0, vec![0x82, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x80])]
+    fn should_reject_malformed_patches(
+        #[case] target_size: usize,
+        #[case] metadata_size: usize,
+        #[case] actions: Vec<u8>,
+    ) {
+        let patch = malformed(target_size, metadata_size, &actions);
+
+        assert_eq!(apply(&SOURCE, &patch), Err(EmulatorError::InvalidPatch));
+    }
+
+    #[test]
+    fn should_reject_wrong_target_checksum() {
+        let mut target = TARGET;
+        target[8] = 0x00;
+        let patch = patch(&SOURCE, &target);
+
+        assert_eq!(
+            apply(&SOURCE, &patch),
+            Err(EmulatorError::PatchChecksumMismatch(
+                crate::crc32::crc32(&target),
+                crate::crc32::crc32(&TARGET)
+            ))
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::emulator_error::EmulatorError;
+
+pub const MAGIC: &[u8] = b"PATCH";
+const EOF_MARKER: &[u8] = b"EOF";
+
+/// Applies an IPS patch. Records with a zero size are run-length encoded,
+/// and the three bytes some patches put after the EOF marker give the size
+/// the output is truncated to.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    let mut output = rom.to_vec();
+    let mut position = MAGIC.len();
+
+    loop {
+        let record = patch
+            .get(position..position + 3)
+            .ok_or(EmulatorError::InvalidPatch)?;
+        position += 3;
+        if record == EOF_MARKER {
+            break;
+        }
+        let offset = read_be(record);
+
+        let size = read_be(take(patch, &mut position, 2)?);
+        let (length, data) = if size == 0 {
+            let length = read_be(take(patch, &mut position, 2)?);
+            (length, None)
+        } else {
+            (size, Some(take(patch, &mut position, size)?))
+        };
+
+        if output.len() < offset + length {
+            output.resize(offset + length, 0x00);
+        }
+        match data {
+            Some(data) => output[offset..offset + length].copy_from_slice(data),
+            None => {
+                let value = take(patch, &mut position, 1)?[0];
+                output[offset..offset + length].fill(value);
+            }
+        }
+    }
+
+    if let Some(truncation) = patch.get(position..position + 3) {
+        output.truncate(read_be(truncation));
+    }
+    Ok(output)
+}
+
+fn take<'a>(
+    patch: &'a [u8],
+    position: &mut usize,
+    length: usize,
+) -> Result<&'a [u8], EmulatorError> {
+    let bytes = patch
+        .get(*position..*position + length)
+        .ok_or(EmulatorError::InvalidPatch)?;
+    *position += length;
+    Ok(bytes)
+}
+
+fn read_be(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |value, byte| value << 8 | *byte as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(records: &[&[u8]], truncation: Option<[u8; 3]>) -> Vec<u8> {
+        let mut patch = MAGIC.to_vec();
+        for record in records {
+            patch.extend_from_slice(record);
+        }
+        patch.extend_from_slice(EOF_MARKER);
+        if let Some(truncation) = truncation {
+            patch.extend_from_slice(&truncation);
+        }
+        patch
+    }
+
+    #[test]
+    fn should_replace_bytes() {
+        let patch = patch(&[&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]], None);
+
+        let output = apply(&[0x00; 6], &patch).unwrap();
+
+        assert_eq!(output, vec![0x00, 0x00, 0xAA, 0xBB, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn should_expand_run_length_records() {
+        let patch = patch(&[&[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x77]], None);
+
+        let output = apply(&[0x00; 6], &patch).unwrap();
+
+        assert_eq!(output, vec![0x00, 0x77, 0x77, 0x77, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn should_grow_rom_written_past_its_end() {
+        let patch = patch(&[&[0x00, 0x00, 0x05, 0x00, 0x01, 0x11]], None);
+
+        let output = apply(&[0x22; 2], &patch).unwrap();
+
+        assert_eq!(output, vec![0x22, 0x22, 0x00, 0x00, 0x00, 0x11]);
+    }
+
+    #[test]
+    fn should_truncate_after_eof() {
+        let patch = patch(&[], Some([0x00, 0x00, 0x03]));
+
+        let output = apply(&[0x22; 6], &patch).unwrap();
+
+        assert_eq!(output, vec![0x22; 3]);
+    }
+
+    #[test]
+    fn should_reject_cut_short_patch() {
+        let mut patch = patch(&[&[0x00, 0x00, 0x01, 0x00, 0x04, 0x11]], None);
+        patch.truncate(patch.len() - 3);
+
+        assert_eq!(apply(&[0x00; 6], &patch), Err(EmulatorError::InvalidPatch));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::emulator_error::EmulatorError;
+
+use super::{check_target_size, read_footer, read_number, verify_checksum};
+
+pub const MAGIC: &[u8] = b"UPS1";
+const FOOTER_SIZE: usize = 12;
+
+/// Applies a UPS patch. Every hunk skips some bytes and then XORs bytes
+/// into the ROM up to a zero terminator. Checksums of the patch, the
+/// source ROM and the patched ROM are checked.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, EmulatorError> {
+    let (source_checksum, target_checksum) = read_footer(patch)?;
+    verify_checksum(rom, source_checksum)?;
+
+    let mut position = MAGIC.len();
+    let source_size = read_number(patch, &mut position)?;
+    let target_size = check_target_size(read_number(patch, &mut position)?)?;
+    if source_size != rom.len() {
+        return Err(EmulatorError::InvalidPatch);
+    }
+
+    let hunks_end = patch.len() - FOOTER_SIZE;
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0x00);
+    let mut offset: usize = 0;
+
+    while position < hunks_end {
+        offset = offset
+            .checked_add(read_number(patch, &mut position)?)
+            .filter(|offset| *offset <= target_size)
+            .ok_or(EmulatorError::InvalidPatch)?;
+        loop {
+            let byte = *patch
+                .get(position)
+                .filter(|_| position < hunks_end)
+                .ok_or(EmulatorError::InvalidPatch)?;
+            position += 1;
+            if byte == 0x00 {
+                // The terminator stands for one unchanged byte
+                offset += 1;
+                break;
+            }
+            if let Some(target_byte) = target.get_mut(offset) {
+                *target_byte ^= byte;
+            }
+            offset += 1;
+        }
+    }
+
+    verify_checksum(&target, target_checksum)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{write_footer, write_number};
+    use super::*;
+    use rstest::*;
+
+    fn patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = MAGIC.to_vec();
+        write_number(&mut patch, source.len());
+        write_number(&mut patch, target.len());
+
+        let mut last = 0;
+        let mut index = 0;
+        while index < target.len() {
+            let source_byte = source.get(index).copied().unwrap_or(0x00);
+            if source_byte == target[index] {
+                index += 1;
+                continue;
+            }
+            write_number(&mut patch, index - last);
+            while index < target.len() {
+                let xor = source.get(index).copied().unwrap_or(0x00) ^ target[index];
+                if xor == 0x00 {
+                    break;
+                }
+                patch.push(xor);
+                index += 1;
+            }
+            patch.push(0x00);
+            index += 1;
+            last = index;
+        }
+
+        write_footer(&mut patch, source, target);
+        patch
+    }
+
+    #[test]
+    fn should_xor_hunks_into_rom() {
+        let source = [0x10, 0x11, 0x12, 0x13, 0x14, 0x15];
+        let target = [0x10, 0xAA, 0xBB, 0x13, 0x14, 0xCC];
+
+        assert_eq!(
+            apply(&source, &patch(&source, &target)),
+            Ok(target.to_vec())
+        );
+    }
+
+    #[test]
+    fn should_grow_rom_to_target_size() {
+        let source = [0x10, 0x11];
+        let target = [0x10, 0x11, 0x00, 0x42];
+
+        assert_eq!(
+            apply(&source, &patch(&source, &target)),
+            Ok(target.to_vec())
+        );
+    }
+
+    #[rstest]
+    // Target size no ROM could have
+    #[case(usize::MAX, vec![])]
+    // Skip far past the end of the target
+    #[case(2, vec![0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x7E, 0x80, 0x01, 0x00])]
+    // Hunk without a terminator
+    #[case(2, vec![0x80, 0x01])]
+    fn should_reject_malformed_patches(#[case] target_size: usize, #[case] hunks: Vec<u8>) {
+        let source = [0x10, 0x11];
+        let mut patch = MAGIC.to_vec();
+        write_number(&mut patch, source.len());
+        write_number(&mut patch, target_size);
+        patch.extend_from_slice(&hunks);
+        write_footer(&mut patch, &source, &source);
+
+        assert_eq!(apply(&source, &patch), Err(EmulatorError::InvalidPatch));
+    }
+
+    #[test]
+    fn should_reject_wrong_source() {
+        let source = [0x10, 0x11];
+        let patch = patch(&source, &[0x20, 0x11]);
+
+        assert!(matches!(
+            apply(&[0x10, 0x12], &patch),
+            Err(EmulatorError::PatchChecksumMismatch(_, _))
+        ));
+    }
+}
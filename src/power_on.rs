@@ -0,0 +1,171 @@
+/// What a memory region holds when the console is switched on
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FillPattern {
+    #[default]
+    Zeros,
+    Ones,
+    /// Pseudo-random bytes, reproducible from the seed
+    Random,
+    /// Approximation of what a DMG is usually found with: noise in the
+    /// static RAMs and cleared VRAM
+    Dmg,
+    /// Approximation of what a CGB is usually found with: WRAM in runs of
+    /// 0x00 and 0xFF, noise in HRAM and cartridge RAM, cleared VRAM and OAM
+    Cgb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryRegion {
+    Wram,
+    Hram,
+    Vram,
+    Oam,
+    CartRam,
+}
+
+/// Power-on contents for every region. The same seed always produces the
+/// same memory, so a run can be reproduced.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PowerOnFill {
+    pub wram: FillPattern,
+    pub hram: FillPattern,
+    pub vram: FillPattern,
+    pub oam: FillPattern,
+    pub cart_ram: FillPattern,
+    pub seed: u64,
+}
+
+impl PowerOnFill {
+    /// Uses the same pattern for every region
+    pub fn new(pattern: FillPattern, seed: u64) -> PowerOnFill {
+        PowerOnFill {
+            wram: pattern,
+            hram: pattern,
+            vram: pattern,
+            oam: pattern,
+            cart_ram: pattern,
+            seed,
+        }
+    }
+
+    pub fn pattern(&self, region: MemoryRegion) -> FillPattern {
+        match region {
+            MemoryRegion::Wram => self.wram,
+            MemoryRegion::Hram => self.hram,
+            MemoryRegion::Vram => self.vram,
+            MemoryRegion::Oam => self.oam,
+            MemoryRegion::CartRam => self.cart_ram,
+        }
+    }
+
+    pub fn fill(&self, region: MemoryRegion, memory: &mut [u8]) {
+        let mut random = XorShift::new(self.seed, region);
+        let pattern = match (self.pattern(region), region) {
+            (FillPattern::Dmg, MemoryRegion::Vram) => FillPattern::Zeros,
+            (FillPattern::Dmg, _) => FillPattern::Random,
+            (FillPattern::Cgb, MemoryRegion::Vram | MemoryRegion::Oam) => FillPattern::Zeros,
+            (FillPattern::Cgb, MemoryRegion::Hram | MemoryRegion::CartRam) => FillPattern::Random,
+            (pattern, _) => pattern,
+        };
+
+        for (index, byte) in memory.iter_mut().enumerate() {
+            *byte = match pattern {
+                FillPattern::Zeros => 0x00,
+                FillPattern::Ones => 0xFF,
+                FillPattern::Random => random.next_byte(),
+                // Only CGB WRAM is left at this point
+                FillPattern::Dmg | FillPattern::Cgb => {
+                    if (index / 8) % 2 == 0 {
+                        0x00
+                    } else {
+                        0xFF
+                    }
+                }
+            };
+        }
+    }
+}
+
+/// xorshift64, seeded per region so regions don't repeat each other
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64, region: MemoryRegion) -> XorShift {
+        // splitmix64 spreads small seeds over the whole state
+        let mut state = seed ^ (region as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        XorShift(if state == 0 { 1 } else { state })
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 32) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn filled(fill: &PowerOnFill, region: MemoryRegion) -> Vec<u8> {
+        let mut memory = vec![0x55; 0x40];
+        fill.fill(region, &mut memory);
+        memory
+    }
+
+    #[rstest]
+    #[case(FillPattern::Zeros, 0x00)]
+    #[case(FillPattern::Ones, 0xFF)]
+    fn should_fill_constant_patterns(#[case] pattern: FillPattern, #[case] expected: u8) {
+        let fill = PowerOnFill::new(pattern, 0);
+
+        assert_eq!(filled(&fill, MemoryRegion::Wram), vec![expected; 0x40]);
+    }
+
+    #[test]
+    fn should_reproduce_random_fill_from_seed() {
+        let fill = PowerOnFill::new(FillPattern::Random, 1234);
+
+        let wram = filled(&fill, MemoryRegion::Wram);
+
+        assert_eq!(wram, filled(&fill, MemoryRegion::Wram));
+        assert_ne!(wram, filled(&fill, MemoryRegion::Hram));
+        assert_ne!(
+            wram,
+            filled(
+                &PowerOnFill::new(FillPattern::Random, 1235),
+                MemoryRegion::Wram
+            )
+        );
+        assert!(wram.iter().any(|byte| *byte != wram[0]));
+    }
+
+    #[test]
+    fn should_fill_cgb_wram_in_runs() {
+        let fill = PowerOnFill::new(FillPattern::Cgb, 0);
+
+        let wram = filled(&fill, MemoryRegion::Wram);
+
+        assert_eq!(&wram[0..8], &[0x00; 8]);
+        assert_eq!(&wram[8..16], &[0xFF; 8]);
+        assert_eq!(filled(&fill, MemoryRegion::Vram), vec![0x00; 0x40]);
+    }
+
+    #[test]
+    fn should_mix_patterns_per_region() {
+        let fill = PowerOnFill {
+            wram: FillPattern::Ones,
+            oam: FillPattern::Dmg,
+            ..PowerOnFill::default()
+        };
+
+        assert_eq!(filled(&fill, MemoryRegion::Wram), vec![0xFF; 0x40]);
+        assert_eq!(filled(&fill, MemoryRegion::Hram), vec![0x00; 0x40]);
+        assert_ne!(filled(&fill, MemoryRegion::Oam), vec![0x00; 0x40]);
+    }
+}
@@ -0,0 +1,1054 @@
+pub mod background;
+pub mod color;
+pub mod dmg_palette;
+pub mod fifo;
+pub mod objects;
+pub mod palette_ram;
+pub mod tile;
+
+use crate::cpu::memory_bus::lcd_mode::LcdMode;
+
+use self::{
+    background::{render_background, render_window, BgPixel},
+    color::ColorCorrection,
+    dmg_palette::{DmgLayer, DmgPalette},
+    fifo::PixelFifo,
+    objects::{render_objects, select_objects, Object, ObjectPixel},
+    palette_ram::PaletteRam,
+};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+pub const DOTS_PER_LINE: u32 = 456;
+pub const VISIBLE_LINES: u8 = 144;
+pub const LINES_PER_FRAME: u8 = 154;
+const OAM_SCAN_DOTS: u32 = 80;
+/// Shortest possible pixel transfer, the extra dots scrolling, the window
+/// and sprites cost on hardware aren't modelled
+const PIXEL_TRANSFER_DOTS: u32 = 172;
+
+/// Bits of the IF register the PPU requests
+pub const VBLANK_INTERRUPT: u8 = 0b01;
+pub const STAT_INTERRUPT: u8 = 0b10;
+
+pub const LCDC_REGISTER: u16 = 0xFF40;
+pub const STAT_REGISTER: u16 = 0xFF41;
+pub const SCY_REGISTER: u16 = 0xFF42;
+pub const SCX_REGISTER: u16 = 0xFF43;
+pub const LY_REGISTER: u16 = 0xFF44;
+pub const LYC_REGISTER: u16 = 0xFF45;
+pub const BGP_REGISTER: u16 = 0xFF47;
+pub const OBP0_REGISTER: u16 = 0xFF48;
+pub const OBP1_REGISTER: u16 = 0xFF49;
+pub const WY_REGISTER: u16 = 0xFF4A;
+pub const WX_REGISTER: u16 = 0xFF4B;
+pub const BCPS_REGISTER: u16 = 0xFF68;
+pub const BCPD_REGISTER: u16 = 0xFF69;
+pub const OCPS_REGISTER: u16 = 0xFF6A;
+pub const OCPD_REGISTER: u16 = 0xFF6B;
+
+const LCD_ENABLE_BIT: u8 = 0b1000_0000;
+const WINDOW_TILE_MAP_BIT: u8 = 0b0100_0000;
+const WINDOW_ENABLE_BIT: u8 = 0b0010_0000;
+const TILE_DATA_BIT: u8 = 0b0001_0000;
+const BG_TILE_MAP_BIT: u8 = 0b0000_1000;
+const OBJ_SIZE_BIT: u8 = 0b0000_0100;
+const OBJ_ENABLE_BIT: u8 = 0b0000_0010;
+/// Clear blanks both the background and the window on DMG, on CGB it puts
+/// every object over the background instead
+const BG_ENABLE_BIT: u8 = 0b0000_0001;
+const STAT_HBLANK_BIT: u8 = 0b0000_1000;
+const STAT_VBLANK_BIT: u8 = 0b0001_0000;
+const STAT_OAM_BIT: u8 = 0b0010_0000;
+const STAT_LYC_BIT: u8 = 0b0100_0000;
+const STAT_WRITABLE_BITS: u8 = 0b0111_1000;
+const STAT_COINCIDENCE_BIT: u8 = 0b0000_0100;
+const STAT_UNUSED_BIT: u8 = 0b1000_0000;
+/// RGB555 white, what the CGB shows with the LCD off
+const WHITE: u16 = 0x7FFF;
+
+/// How pixel transfer is carried out
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    /// Whole lines at once when pixel transfer ends, which always takes 172
+    /// dots. Cheap, but registers written mid-line only show on the next.
+    #[default]
+    Scanline,
+    /// Dot by dot through the fetchers and pixel FIFOs, so mid-line writes
+    /// take effect where they happen and pixel transfer takes as long as the
+    /// scroll, window and objects make it
+    PixelFifo,
+}
+
+/// Registers the renderers work from, as they are at the current dot
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LineRegisters {
+    pub lcdc: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wx: u8,
+    pub window_line: u8,
+    /// Set once LY matched WY this frame
+    pub window_y_reached: bool,
+    pub cgb_mode: bool,
+}
+
+impl LineRegisters {
+    pub fn bg_enabled(&self) -> bool {
+        self.lcdc & BG_ENABLE_BIT != 0
+    }
+
+    /// The CGB always draws the background and window
+    pub fn bg_drawn(&self) -> bool {
+        self.cgb_mode || self.bg_enabled()
+    }
+
+    /// Whether the window shows on this line once X reaches it
+    pub fn window_enabled(&self) -> bool {
+        self.bg_drawn() && self.lcdc & WINDOW_ENABLE_BIT != 0 && self.window_y_reached
+    }
+
+    pub fn objects_enabled(&self) -> bool {
+        self.lcdc & OBJ_ENABLE_BIT != 0
+    }
+
+    pub fn object_height(&self) -> u8 {
+        if self.lcdc & OBJ_SIZE_BIT != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    /// Shade of a dot with background colour `index` and the object pixel
+    /// that won it, if any. Objects flagged to sit behind the background
+    /// only show over colour 0.
+    pub fn shade(&self, index: u8, object: Option<ObjectPixel>) -> u8 {
+        self.dmg_pixel(index, object).1
+    }
+
+    /// Like `shade`, along with the layer the shade came from
+    pub fn dmg_pixel(&self, index: u8, object: Option<ObjectPixel>) -> (DmgLayer, u8) {
+        match object {
+            Some(object) if !object.behind_background || index == 0 => {
+                if object.obp1 {
+                    (DmgLayer::Obj1, shade(self.obp1, object.color))
+                } else {
+                    (DmgLayer::Obj0, shade(self.obp0, object.color))
+                }
+            }
+            _ if self.bg_enabled() => (DmgLayer::Background, shade(self.bgp, index)),
+            _ => (DmgLayer::Background, 0),
+        }
+    }
+
+    /// Whether a CGB object pixel shows over the background pixel under
+    /// it. Background colour 0 never covers objects, colours 1-3 do when
+    /// either the object or the tile asks for it, unless LCDC bit 0 is
+    /// clear.
+    pub fn cgb_object_visible(&self, background: BgPixel, object: ObjectPixel) -> bool {
+        !self.bg_enabled()
+            || background.color == 0
+            || !(object.behind_background || background.priority)
+    }
+}
+
+/// Timing side of the picture processing unit. Every line takes 456 dots,
+/// one per clock cycle: OAM scan, pixel transfer and HBlank for the 144
+/// visible lines, then ten lines of VBlank. It owns the LCD registers at
+/// 0xFF40-0xFF4B, except for the OAM DMA register, and the CGB palette
+/// registers at 0xFF68-0xFF6B.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ppu {
+    lcdc: u8,
+    /// Only the interrupt enable bits, the rest of STAT is derived
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    /// Dot within the current line
+    dot: u32,
+    mode: LcdMode,
+    /// STAT interrupt sources ORed together. The interrupt is requested
+    /// when it goes high, so a source firing while another one already
+    /// holds it high is swallowed.
+    stat_line: bool,
+    interrupts: u8,
+    frames: u64,
+    /// Row of the window drawn next, which only moves on for lines the
+    /// window actually showed up on
+    window_line: u8,
+    /// Set once LY matched WY this frame, the window can show from then on
+    window_y_reached: bool,
+    /// Objects the OAM scan picked for the current line
+    line_objects: Vec<Object>,
+    /// CGB rendering: BG attributes, CGB palettes and OAM-order object
+    /// priority
+    cgb_mode: bool,
+    renderer: Renderer,
+    /// Renderer the current line started with
+    line_renderer: Renderer,
+    fifo: PixelFifo,
+    /// BCPS/BCPD and OCPS/OCPD, only reachable in CGB mode
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+    /// Shades 0-3 after BGP, OBP0 or OBP1, one byte per pixel
+    framebuffer: Vec<u8>,
+    /// Which of BGP, OBP0 and OBP1 each shade went through
+    layers: Vec<DmgLayer>,
+    dmg_palette: DmgPalette,
+    /// RGB555 colours from palette RAM in CGB mode
+    color_framebuffer: Vec<u16>,
+    color_correction: ColorCorrection,
+}
+
+impl Ppu {
+    /// Starts with the LCD off, the boot ROM or the game switches it on
+    pub fn new() -> Ppu {
+        Ppu {
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            dot: 0,
+            mode: LcdMode::HBlank,
+            stat_line: false,
+            interrupts: 0,
+            frames: 0,
+            window_line: 0,
+            window_y_reached: false,
+            line_objects: Vec::new(),
+            cgb_mode: false,
+            renderer: Renderer::Scanline,
+            line_renderer: Renderer::Scanline,
+            fifo: PixelFifo::new(),
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            layers: vec![DmgLayer::Background; SCREEN_WIDTH * SCREEN_HEIGHT],
+            dmg_palette: DmgPalette::default(),
+            color_framebuffer: vec![WHITE; SCREEN_WIDTH * SCREEN_HEIGHT],
+            color_correction: ColorCorrection::Raw,
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            LCDC_REGISTER => self.lcdc,
+            STAT_REGISTER => {
+                let coincidence = if self.ly == self.lyc {
+                    STAT_COINCIDENCE_BIT
+                } else {
+                    0
+                };
+                STAT_UNUSED_BIT | self.stat | coincidence | u8::from(self.mode)
+            }
+            SCY_REGISTER => self.scy,
+            SCX_REGISTER => self.scx,
+            LY_REGISTER => self.ly,
+            LYC_REGISTER => self.lyc,
+            BGP_REGISTER => self.bgp,
+            OBP0_REGISTER => self.obp0,
+            OBP1_REGISTER => self.obp1,
+            WY_REGISTER => self.wy,
+            WX_REGISTER => self.wx,
+            BCPS_REGISTER => self.bg_palettes.read_spec(),
+            BCPD_REGISTER => self.bg_palettes.read_data(),
+            OCPS_REGISTER => self.obj_palettes.read_spec(),
+            OCPD_REGISTER => self.obj_palettes.read_data(),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, new_value: u8) {
+        match address {
+            LCDC_REGISTER => self.write_lcdc(new_value),
+            STAT_REGISTER => self.stat = new_value & STAT_WRITABLE_BITS,
+            SCY_REGISTER => self.scy = new_value,
+            SCX_REGISTER => self.scx = new_value,
+            LYC_REGISTER => self.lyc = new_value,
+            BGP_REGISTER => self.bgp = new_value,
+            OBP0_REGISTER => self.obp0 = new_value,
+            OBP1_REGISTER => self.obp1 = new_value,
+            WY_REGISTER => self.wy = new_value,
+            WX_REGISTER => self.wx = new_value,
+            BCPS_REGISTER => self.bg_palettes.write_spec(new_value),
+            BCPD_REGISTER => self.bg_palettes.write_data(new_value),
+            OCPS_REGISTER => self.obj_palettes.write_spec(new_value),
+            OCPD_REGISTER => self.obj_palettes.write_data(new_value),
+            // LY is read only
+            _ => (),
+        }
+        self.update_stat_line();
+    }
+
+    /// Switching the LCD off resets it to the start of the frame and blanks
+    /// the screen, switching it back on starts drawing from line 0
+    fn write_lcdc(&mut self, new_value: u8) {
+        let was_enabled = self.enabled();
+        self.lcdc = new_value;
+        match (was_enabled, self.enabled()) {
+            (true, false) => {
+                self.ly = 0;
+                self.dot = 0;
+                self.mode = LcdMode::HBlank;
+                self.start_frame();
+                self.framebuffer.fill(0);
+                self.layers.fill(DmgLayer::Background);
+                self.color_framebuffer.fill(WHITE);
+            }
+            (false, true) => self.mode = LcdMode::OamScan,
+            _ => (),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.lcdc & LCD_ENABLE_BIT != 0
+    }
+
+    pub fn mode(&self) -> LcdMode {
+        self.mode
+    }
+
+    pub fn ly(&self) -> u8 {
+        self.ly
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+    }
+
+    pub fn dmg_palette(&self) -> &DmgPalette {
+        &self.dmg_palette
+    }
+
+    /// Colours for the shades outside CGB mode, greyscale unless changed
+    pub fn set_dmg_palette(&mut self, dmg_palette: DmgPalette) {
+        self.dmg_palette = dmg_palette;
+    }
+
+    pub fn color_correction(&self) -> ColorCorrection {
+        self.color_correction
+    }
+
+    pub fn set_color_correction(&mut self, color_correction: ColorCorrection) {
+        self.color_correction = color_correction;
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    /// Takes effect from the next line
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    /// Frames completed since power on, counted when VBlank starts
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The picture as shades 0-3, row by row. Only drawn outside CGB mode.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// The picture as RGB555 colours, row by row. Only drawn in CGB mode.
+    pub fn color_framebuffer(&self) -> &[u16] {
+        &self.color_framebuffer
+    }
+
+    /// The picture as 24-bit RGB, three bytes per pixel. CGB colours go
+    /// through the colour correction, DMG shades through the DMG palette.
+    pub fn rgb_framebuffer(&self) -> Vec<u8> {
+        if self.cgb_mode {
+            self.color_framebuffer
+                .iter()
+                .flat_map(|color| self.color_correction.apply(*color))
+                .collect()
+        } else {
+            self.framebuffer
+                .iter()
+                .zip(&self.layers)
+                .flat_map(|(shade, layer)| self.dmg_palette.color(*layer, *shade))
+                .collect()
+        }
+    }
+
+    /// Advances one dot and returns the mode entered, if it changed. Does
+    /// nothing while the LCD is off. `vram` and `oam` are read for drawing.
+    pub fn step(&mut self, vram: &[u8], oam: &[u8]) -> Option<LcdMode> {
+        if !self.enabled() {
+            return None;
+        }
+
+        self.dot += 1;
+        if self.dot == DOTS_PER_LINE {
+            self.dot = 0;
+            self.ly = (self.ly + 1) % LINES_PER_FRAME;
+        }
+
+        let mode = if self.ly >= VISIBLE_LINES {
+            LcdMode::VBlank
+        } else if self.dot < OAM_SCAN_DOTS {
+            LcdMode::OamScan
+        } else if self.dot == OAM_SCAN_DOTS
+            || (self.mode == LcdMode::PixelTransfer && !self.transfer_done())
+        {
+            LcdMode::PixelTransfer
+        } else {
+            LcdMode::HBlank
+        };
+        let changed = mode != self.mode;
+        self.mode = mode;
+        match mode {
+            _ if !changed => (),
+            LcdMode::PixelTransfer => self.start_transfer(oam),
+            LcdMode::HBlank => self.finish_transfer(vram),
+            LcdMode::VBlank => {
+                self.interrupts |= VBLANK_INTERRUPT;
+                self.frames += 1;
+                self.start_frame();
+            }
+            _ => (),
+        }
+        if mode == LcdMode::PixelTransfer && self.line_renderer == Renderer::PixelFifo {
+            let registers = self.line_registers();
+            if let Some((x, background, object)) = self.fifo.step(&registers, vram) {
+                self.put_pixel(&registers, x, background, object);
+            }
+        }
+        self.update_stat_line();
+
+        changed.then_some(mode)
+    }
+
+    fn start_frame(&mut self) {
+        self.window_line = 0;
+        self.window_y_reached = false;
+    }
+
+    /// The OAM scan result is known once pixel transfer starts
+    fn start_transfer(&mut self, oam: &[u8]) {
+        if self.ly == self.wy {
+            self.window_y_reached = true;
+        }
+        let registers = self.line_registers();
+        self.line_objects = select_objects(oam, self.ly, registers.object_height());
+        self.line_renderer = self.renderer;
+        if self.line_renderer == Renderer::PixelFifo {
+            self.fifo.start_line(&registers, &self.line_objects);
+        }
+    }
+
+    fn transfer_done(&self) -> bool {
+        match self.line_renderer {
+            Renderer::Scanline => self.dot >= OAM_SCAN_DOTS + PIXEL_TRANSFER_DOTS,
+            Renderer::PixelFifo => self.fifo.done(),
+        }
+    }
+
+    fn finish_transfer(&mut self, vram: &[u8]) {
+        match self.line_renderer {
+            Renderer::Scanline => self.render_line(vram),
+            Renderer::PixelFifo if self.fifo.window_drawn() => self.window_line += 1,
+            Renderer::PixelFifo => (),
+        }
+    }
+
+    fn line_registers(&self) -> LineRegisters {
+        LineRegisters {
+            lcdc: self.lcdc,
+            scy: self.scy,
+            scx: self.scx,
+            ly: self.ly,
+            bgp: self.bgp,
+            obp0: self.obp0,
+            obp1: self.obp1,
+            wx: self.wx,
+            window_line: self.window_line,
+            window_y_reached: self.window_y_reached,
+            cgb_mode: self.cgb_mode,
+        }
+    }
+
+    fn render_line(&mut self, vram: &[u8]) {
+        let registers = self.line_registers();
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+        if registers.bg_drawn() {
+            render_background(vram, &registers, &mut line);
+        }
+        if registers.window_enabled() && render_window(vram, &registers, &mut line) {
+            self.window_line += 1;
+        }
+
+        let objects = if registers.objects_enabled() {
+            render_objects(
+                vram,
+                &self.line_objects,
+                self.ly,
+                registers.object_height(),
+                self.cgb_mode,
+            )
+        } else {
+            [None; SCREEN_WIDTH]
+        };
+
+        for (x, (background, object)) in line.into_iter().zip(objects).enumerate() {
+            self.put_pixel(&registers, x, background, object);
+        }
+    }
+
+    fn put_pixel(
+        &mut self,
+        registers: &LineRegisters,
+        x: usize,
+        background: BgPixel,
+        object: Option<ObjectPixel>,
+    ) {
+        let index = self.ly as usize * SCREEN_WIDTH + x;
+        if !self.cgb_mode {
+            (self.layers[index], self.framebuffer[index]) =
+                registers.dmg_pixel(background.color, object);
+            return;
+        }
+
+        self.color_framebuffer[index] = match object {
+            Some(object) if registers.cgb_object_visible(background, object) => {
+                self.obj_palettes.color(object.palette, object.color)
+            }
+            _ => self.bg_palettes.color(background.palette, background.color),
+        };
+    }
+
+    /// Interrupts requested since the last call, as IF bits
+    pub fn take_interrupts(&mut self) -> u8 {
+        std::mem::take(&mut self.interrupts)
+    }
+
+    fn update_stat_line(&mut self) {
+        let enabled_source = |bit: u8| self.stat & bit != 0;
+        let stat_line = self.enabled()
+            && ((self.ly == self.lyc && enabled_source(STAT_LYC_BIT))
+                || match self.mode {
+                    LcdMode::HBlank => enabled_source(STAT_HBLANK_BIT),
+                    LcdMode::VBlank => enabled_source(STAT_VBLANK_BIT),
+                    LcdMode::OamScan => enabled_source(STAT_OAM_BIT),
+                    LcdMode::PixelTransfer => false,
+                });
+        if stat_line && !self.stat_line {
+            self.interrupts |= STAT_INTERRUPT;
+        }
+        self.stat_line = stat_line;
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
+
+/// Shade a DMG palette register gives colour index `index`, two bits each
+fn shade(palette: u8, index: u8) -> u8 {
+    (palette >> (index * 2)) & 0b11
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    use super::dmg_palette::{PalettePreset, Rgb};
+
+    fn enabled_ppu() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC_REGISTER, LCD_ENABLE_BIT);
+        ppu
+    }
+
+    fn run(ppu: &mut Ppu, dots: u32) {
+        let vram = vec![0x00; 0x2000];
+        for _ in 0..dots {
+            ppu.step(&vram, &[]);
+        }
+    }
+
+    #[rstest]
+    #[case(0, LcdMode::OamScan)]
+    #[case(79, LcdMode::OamScan)]
+    #[case(80, LcdMode::PixelTransfer)]
+    #[case(251, LcdMode::PixelTransfer)]
+    #[case(252, LcdMode::HBlank)]
+    #[case(455, LcdMode::HBlank)]
+    #[case(456, LcdMode::OamScan)]
+    #[case(144 * 456 - 1, LcdMode::HBlank)]
+    #[case(144 * 456, LcdMode::VBlank)]
+    #[case(154 * 456 - 1, LcdMode::VBlank)]
+    #[case(154 * 456, LcdMode::OamScan)]
+    fn should_walk_through_modes(#[case] dots: u32, #[case] expected_mode: LcdMode) {
+        let mut ppu = enabled_ppu();
+
+        run(&mut ppu, dots);
+
+        assert_eq!(ppu.mode(), expected_mode);
+        assert_eq!(ppu.read(STAT_REGISTER) & 0b11, u8::from(expected_mode));
+        assert_eq!(ppu.read(LY_REGISTER) as u32, dots / 456 % 154);
+    }
+
+    #[test]
+    fn should_report_mode_changes() {
+        let mut ppu = enabled_ppu();
+
+        let vram = vec![0x00; 0x2000];
+
+        let changes: Vec<_> = (0..456).filter_map(|_| ppu.step(&vram, &[])).collect();
+
+        assert_eq!(
+            changes,
+            vec![LcdMode::PixelTransfer, LcdMode::HBlank, LcdMode::OamScan]
+        );
+    }
+
+    #[test]
+    fn should_request_vblank_once_per_frame() {
+        let mut ppu = enabled_ppu();
+
+        run(&mut ppu, 144 * 456 - 1);
+        assert_eq!(ppu.take_interrupts(), 0);
+        run(&mut ppu, 1);
+        assert_eq!(ppu.take_interrupts(), VBLANK_INTERRUPT);
+        assert_eq!(ppu.frames(), 1);
+
+        run(&mut ppu, 154 * 456);
+        assert_eq!(ppu.take_interrupts(), VBLANK_INTERRUPT);
+        assert_eq!(ppu.frames(), 2);
+    }
+
+    #[rstest]
+    #[case(STAT_HBLANK_BIT, 252)]
+    #[case(STAT_VBLANK_BIT, 144 * 456)]
+    #[case(STAT_OAM_BIT, 456)]
+    fn should_request_stat_interrupt_on_mode_source(#[case] source: u8, #[case] expected_dot: u32) {
+        let mut ppu = enabled_ppu();
+        ppu.write(STAT_REGISTER, source);
+        ppu.take_interrupts();
+
+        run(&mut ppu, expected_dot - 1);
+        assert_eq!(ppu.take_interrupts() & STAT_INTERRUPT, 0);
+        run(&mut ppu, 1);
+
+        assert_eq!(ppu.take_interrupts() & STAT_INTERRUPT, STAT_INTERRUPT);
+    }
+
+    #[test]
+    fn should_compare_ly_with_lyc() {
+        let mut ppu = enabled_ppu();
+        ppu.write(LYC_REGISTER, 3);
+        ppu.write(STAT_REGISTER, STAT_LYC_BIT);
+
+        run(&mut ppu, 3 * 456 - 1);
+        assert_eq!(ppu.read(STAT_REGISTER) & STAT_COINCIDENCE_BIT, 0);
+        assert_eq!(ppu.take_interrupts(), 0);
+
+        run(&mut ppu, 1);
+        assert_eq!(
+            ppu.read(STAT_REGISTER) & STAT_COINCIDENCE_BIT,
+            STAT_COINCIDENCE_BIT
+        );
+        assert_eq!(ppu.take_interrupts(), STAT_INTERRUPT);
+    }
+
+    #[test]
+    fn should_block_stat_interrupt_while_line_stays_high() {
+        let mut ppu = enabled_ppu();
+        ppu.write(STAT_REGISTER, STAT_HBLANK_BIT | STAT_OAM_BIT);
+        run(&mut ppu, 252);
+        ppu.take_interrupts();
+
+        // HBlank hands over to OAM scan without the line dropping
+        run(&mut ppu, 456 - 252);
+
+        assert_eq!(ppu.mode(), LcdMode::OamScan);
+        assert_eq!(ppu.take_interrupts(), 0);
+    }
+
+    #[test]
+    fn should_reset_when_lcd_is_switched_off() {
+        let mut ppu = enabled_ppu();
+        run(&mut ppu, 10 * 456 + 100);
+
+        ppu.write(LCDC_REGISTER, 0x00);
+        run(&mut ppu, 1000);
+
+        assert_eq!(ppu.read(LY_REGISTER), 0);
+        assert_eq!(ppu.mode(), LcdMode::HBlank);
+        assert_eq!(ppu.step(&[], &[]), None);
+
+        ppu.write(LCDC_REGISTER, LCD_ENABLE_BIT);
+
+        assert_eq!(ppu.mode(), LcdMode::OamScan);
+        run(&mut ppu, 456);
+        assert_eq!(ppu.read(LY_REGISTER), 1);
+    }
+
+    #[rstest]
+    #[case(STAT_REGISTER, 0xFF, 0xFC)]
+    #[case(LY_REGISTER, 0x42, 0x00)]
+    #[case(SCX_REGISTER, 0x42, 0x42)]
+    #[case(WX_REGISTER, 0x07, 0x07)]
+    fn should_keep_register_values(
+        #[case] address: u16,
+        #[case] new_value: u8,
+        #[case] expected: u8,
+    ) {
+        let mut ppu = Ppu::new();
+
+        ppu.write(address, new_value);
+
+        assert_eq!(ppu.read(address), expected);
+    }
+
+    /// Tile 1 is solid colour 3 and fills the top left corner of both maps
+    fn vram_with_corner_tile() -> Vec<u8> {
+        let mut vram = vec![0x00; 0x2000];
+        vram[0x0010..0x0020].fill(0xFF);
+        vram[0x1800] = 1;
+        vram[0x1C00] = 1;
+        vram
+    }
+
+    fn run_with(ppu: &mut Ppu, vram: &[u8], dots: u32) {
+        for _ in 0..dots {
+            ppu.step(vram, &[]);
+        }
+    }
+
+    #[rstest]
+    #[case(0x91, 0xE4, [3, 0])]
+    #[case(0x91, 0x1B, [0, 3])]
+    #[case(0x90, 0xE4, [0, 0])]
+    fn should_render_background_through_bgp(
+        #[case] lcdc: u8,
+        #[case] bgp: u8,
+        #[case] expected: [u8; 2],
+    ) {
+        let vram = vram_with_corner_tile();
+        let mut ppu = Ppu::new();
+        ppu.write(BGP_REGISTER, bgp);
+        ppu.write(LCDC_REGISTER, lcdc);
+
+        run_with(&mut ppu, &vram, 456);
+
+        assert_eq!(ppu.framebuffer()[0], expected[0]);
+        assert_eq!(ppu.framebuffer()[8], expected[1]);
+    }
+
+    #[test]
+    fn should_draw_window_below_wy() {
+        let vram = vram_with_corner_tile();
+        let mut ppu = Ppu::new();
+        ppu.write(BGP_REGISTER, 0xE4);
+        ppu.write(WY_REGISTER, 16);
+        ppu.write(WX_REGISTER, 7 + 80);
+        ppu.write(LCDC_REGISTER, 0xF1);
+
+        run_with(&mut ppu, &vram, 24 * 456);
+
+        let pixel = |x: usize, y: usize| ppu.framebuffer()[y * SCREEN_WIDTH + x];
+        assert_eq!(pixel(80, 15), 0);
+        assert_eq!(pixel(80, 16), 3);
+        assert_eq!(pixel(87, 23), 3);
+        assert_eq!(pixel(88, 16), 0);
+        assert_eq!(pixel(0, 16), 0);
+    }
+
+    #[test]
+    fn should_only_advance_window_line_when_window_is_drawn() {
+        let vram = vram_with_corner_tile();
+        let mut ppu = Ppu::new();
+        ppu.write(LCDC_REGISTER, 0xA1);
+        run_with(&mut ppu, &vram, 4 * 456);
+        assert_eq!(ppu.window_line, 4);
+
+        ppu.write(LCDC_REGISTER, 0x81);
+        run_with(&mut ppu, &vram, 10 * 456);
+        ppu.write(WX_REGISTER, 167);
+        ppu.write(LCDC_REGISTER, 0xA1);
+        run_with(&mut ppu, &vram, 10 * 456);
+        assert_eq!(ppu.window_line, 4);
+
+        ppu.write(WX_REGISTER, 7);
+        run_with(&mut ppu, &vram, 456);
+        assert_eq!(ppu.window_line, 5);
+
+        run_with(&mut ppu, &vram, (144 - 25) * 456);
+        assert_eq!(ppu.window_line, 0);
+    }
+
+    #[rstest]
+    #[case(0x00, 0, 2)]
+    #[case(0x80, 0, 2)]
+    #[case(0x00, 1, 2)]
+    #[case(0x80, 1, 3)]
+    #[case(0x10, 0, 1)]
+    fn should_draw_objects_over_background(
+        #[case] attributes: u8,
+        #[case] bg_tile: u8,
+        #[case] expected: u8,
+    ) {
+        let mut vram = vram_with_corner_tile();
+        vram[0x1800] = bg_tile;
+        for row in 0..8 {
+            vram[0x0100 + row * 2] = 0xFF;
+        }
+        let mut oam = vec![0x00; 0xA0];
+        oam[0..4].copy_from_slice(&[16, 8, 0x10, attributes]);
+        let mut ppu = Ppu::new();
+        ppu.write(BGP_REGISTER, 0xE4);
+        ppu.write(OBP0_REGISTER, 0b1000);
+        ppu.write(OBP1_REGISTER, 0b0100);
+        ppu.write(LCDC_REGISTER, 0x93);
+
+        for _ in 0..456 {
+            ppu.step(&vram, &oam);
+        }
+
+        assert_eq!(ppu.framebuffer()[0], expected);
+    }
+
+    #[rstest]
+    #[case(0x00, 0, [0x0F, 0x38, 0x0F])]
+    #[case(0x10, 0, [0x00, 0x63, 0xC5])]
+    #[case(0x00, 8, [0x9B, 0xBC, 0x0F])]
+    fn should_color_shades_by_layer(
+        #[case] attributes: u8,
+        #[case] x: usize,
+        #[case] expected: Rgb,
+    ) {
+        let mut vram = vec![0x00; 0x2000];
+        for row in 0..8 {
+            vram[0x0100 + row * 2] = 0xFF;
+        }
+        let mut oam = vec![0x00; 0xA0];
+        oam[0..4].copy_from_slice(&[16, 8, 0x10, attributes]);
+        let mut ppu = Ppu::new();
+        ppu.set_dmg_palette(DmgPalette {
+            obj1: [[0xFF; 3], [0x00, 0x63, 0xC5], [0x00; 3], [0x00; 3]],
+            ..DmgPalette::preset(PalettePreset::Green)
+        });
+        ppu.write(BGP_REGISTER, 0xE4);
+        ppu.write(OBP0_REGISTER, 0xFF);
+        ppu.write(OBP1_REGISTER, 0xE4);
+        ppu.write(LCDC_REGISTER, 0x93);
+
+        for _ in 0..456 {
+            ppu.step(&vram, &oam);
+        }
+
+        assert_eq!(ppu.rgb_framebuffer()[x * 3..x * 3 + 3], expected);
+    }
+
+    #[rstest]
+    #[case(Renderer::Scanline, 0, 172)]
+    #[case(Renderer::Scanline, 3, 172)]
+    #[case(Renderer::PixelFifo, 0, 172)]
+    #[case(Renderer::PixelFifo, 3, 175)]
+    fn should_time_pixel_transfer_by_renderer(
+        #[case] renderer: Renderer,
+        #[case] scx: u8,
+        #[case] expected_dots: u32,
+    ) {
+        let vram = vram_with_corner_tile();
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(renderer);
+        ppu.write(SCX_REGISTER, scx);
+        ppu.write(LCDC_REGISTER, 0x91);
+        run_with(&mut ppu, &vram, 79);
+
+        assert_eq!(ppu.step(&vram, &[]), Some(LcdMode::PixelTransfer));
+        let dots = 1 + (0..).take_while(|_| ppu.step(&vram, &[]).is_none()).count() as u32;
+
+        assert_eq!(dots, expected_dots);
+        assert_eq!(ppu.mode(), LcdMode::HBlank);
+    }
+
+    #[rstest]
+    #[case(Renderer::Scanline)]
+    #[case(Renderer::PixelFifo)]
+    fn should_survive_object_size_change_during_transfer(#[case] renderer: Renderer) {
+        let mut vram = vec![0x00; 0x2000];
+        vram[0x0100..0x0120].fill(0xFF);
+        let mut oam = vec![0x00; 0xA0];
+        oam[0..4].copy_from_slice(&[16, 8, 0x10, 0x40]);
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(renderer);
+        ppu.write(OBP0_REGISTER, 0xE4);
+        ppu.write(LCDC_REGISTER, 0x87);
+        for _ in 0..12 * 456 + 82 {
+            ppu.step(&vram, &oam);
+        }
+
+        ppu.write(LCDC_REGISTER, 0x83);
+        for _ in 0..456 {
+            ppu.step(&vram, &oam);
+        }
+
+        assert_eq!(ppu.framebuffer()[12 * SCREEN_WIDTH], 3);
+    }
+
+    #[test]
+    fn should_draw_same_frame_with_either_renderer() {
+        let mut vram = vram_with_corner_tile();
+        vram[0x1C21] = 1;
+        for row in 0..8 {
+            vram[0x0100 + row * 2] = 0xF0;
+        }
+        let mut oam = vec![0x00; 0xA0];
+        oam[0..4].copy_from_slice(&[20, 12, 0x10, 0x00]);
+        oam[4..8].copy_from_slice(&[40, 50, 0x10, 0x20]);
+        let frame = |renderer: Renderer| {
+            let mut ppu = Ppu::new();
+            ppu.set_renderer(renderer);
+            ppu.write(BGP_REGISTER, 0xE4);
+            ppu.write(OBP0_REGISTER, 0xD2);
+            ppu.write(SCX_REGISTER, 5);
+            ppu.write(SCY_REGISTER, 2);
+            ppu.write(WY_REGISTER, 30);
+            ppu.write(WX_REGISTER, 60);
+            ppu.write(LCDC_REGISTER, 0xF3);
+            for _ in 0..154 * 456 {
+                ppu.step(&vram, &oam);
+            }
+            ppu.framebuffer().to_vec()
+        };
+
+        assert_eq!(frame(Renderer::Scanline), frame(Renderer::PixelFifo));
+    }
+
+    #[test]
+    fn should_draw_same_cgb_frame_with_either_renderer() {
+        let mut vram = vec![0x00; 0x4000];
+        vram[0x0010..0x0020].fill(0xFF);
+        vram[0x2010] = 0xF0;
+        for entry in 0..0x400 {
+            vram[0x1800 + entry] = 1;
+            vram[0x3800 + entry] = (entry % 8) as u8 | (entry as u8 & 0x68);
+        }
+        let frame = |renderer: Renderer| {
+            let mut ppu = Ppu::new();
+            ppu.set_cgb_mode(true);
+            ppu.set_renderer(renderer);
+            for palette in 0..8 {
+                let color = palette as u16 * 0x0421;
+                write_palette(&mut ppu, BCPS_REGISTER, palette, [0, color, color << 1, 0]);
+            }
+            ppu.write(SCX_REGISTER, 3);
+            ppu.write(LCDC_REGISTER, 0x91);
+            for _ in 0..154 * 456 {
+                ppu.step(&vram, &[]);
+            }
+            ppu.color_framebuffer().to_vec()
+        };
+
+        assert_eq!(frame(Renderer::Scanline), frame(Renderer::PixelFifo));
+    }
+
+    /// Writes RGB555 colours to a palette through the CGB registers
+    fn write_palette(ppu: &mut Ppu, spec_register: u16, palette: u8, colors: [u16; 4]) {
+        ppu.write(spec_register, 0x80 | (palette * 8));
+        for color in colors {
+            for byte in color.to_le_bytes() {
+                ppu.write(spec_register + 1, byte);
+            }
+        }
+    }
+
+    #[rstest]
+    // Attributes pick BG palette 2, the object stays behind colour 3
+    #[case(0x02, 0x80, 0x93, 0x0003)]
+    // The object beats colour 1-3 of an ordinary tile
+    #[case(0x00, 0x00, 0x93, 0x001F)]
+    // Tile priority keeps the background on top
+    #[case(0x80, 0x00, 0x93, 0x0001)]
+    // Object priority keeps the background on top
+    #[case(0x00, 0x80, 0x93, 0x0001)]
+    // LCDC bit 0 clear puts the object over everything
+    #[case(0x80, 0x80, 0x92, 0x001F)]
+    fn should_render_cgb_colors_and_priorities(
+        #[case] tile_attributes: u8,
+        #[case] object_attributes: u8,
+        #[case] lcdc: u8,
+        #[case] expected: u16,
+    ) {
+        let mut vram = vec![0x00; 0x4000];
+        vram[0x0010..0x0020].fill(0xFF);
+        vram[0x1800] = 1;
+        vram[0x3800] = tile_attributes;
+        for row in 0..8 {
+            vram[0x0100 + row * 2] = 0xFF;
+        }
+        let mut oam = vec![0x00; 0xA0];
+        oam[0..4].copy_from_slice(&[16, 8, 0x10, object_attributes | 0x01]);
+        let mut ppu = Ppu::new();
+        ppu.set_cgb_mode(true);
+        write_palette(&mut ppu, BCPS_REGISTER, 0, [0, 0, 0, 0x0001]);
+        write_palette(&mut ppu, BCPS_REGISTER, 2, [0, 0, 0, 0x0003]);
+        write_palette(&mut ppu, OCPS_REGISTER, 1, [0, 0x001F, 0, 0]);
+        ppu.write(LCDC_REGISTER, lcdc);
+
+        for _ in 0..456 {
+            ppu.step(&vram, &oam);
+        }
+
+        assert_eq!(ppu.color_framebuffer()[0], expected);
+        assert_eq!(ppu.framebuffer()[0], 0);
+    }
+
+    #[rstest]
+    #[case(false, ColorCorrection::Cgb, [0xFF, 0xFF, 0xFF])]
+    #[case(true, ColorCorrection::Raw, [0xFF, 0x00, 0x00])]
+    #[case(true, ColorCorrection::Cgb, [201, 0, 46])]
+    fn should_convert_framebuffer_to_rgb(
+        #[case] cgb_mode: bool,
+        #[case] color_correction: ColorCorrection,
+        #[case] expected: [u8; 3],
+    ) {
+        let vram = vec![0x00; 0x4000];
+        let mut ppu = Ppu::new();
+        ppu.set_cgb_mode(cgb_mode);
+        ppu.set_color_correction(color_correction);
+        write_palette(&mut ppu, BCPS_REGISTER, 0, [0x001F; 4]);
+        ppu.write(LCDC_REGISTER, 0x91);
+
+        run_with(&mut ppu, &vram, 456);
+
+        assert_eq!(ppu.rgb_framebuffer()[0..3], expected);
+        assert_eq!(
+            ppu.rgb_framebuffer().len(),
+            SCREEN_WIDTH * SCREEN_HEIGHT * 3
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::cpu::memory_bus::VRAM_BANK_SIZE;
+
+use super::{
+    tile::{tile_row, TILE_SIZE},
+    LineRegisters, BG_TILE_MAP_BIT, SCREEN_WIDTH, TILE_DATA_BIT, WINDOW_TILE_MAP_BIT,
+};
+
+const TILE_MAP_0: usize = 0x1800;
+const TILE_MAP_1: usize = 0x1C00;
+const TILES_PER_MAP_ROW: usize = 32;
+/// WX holds the window's screen position plus 7
+pub const WINDOW_X_OFFSET: i16 = 7;
+
+const PALETTE_BITS: u8 = 0b0000_0111;
+const BANK_BIT: u8 = 0b0000_1000;
+const X_FLIP_BIT: u8 = 0b0010_0000;
+const Y_FLIP_BIT: u8 = 0b0100_0000;
+const PRIORITY_BIT: u8 = 0b1000_0000;
+
+/// A background or window pixel with what its CGB tile attributes say
+/// about it. Outside CGB mode only the colour is set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BgPixel {
+    pub color: u8,
+    pub palette: u8,
+    /// Colours 1-3 are drawn over every object
+    pub priority: bool,
+}
+
+/// Background pixels of the current line, scrolled by SCX and SCY and
+/// wrapping around the 256x256 map
+pub fn render_background(vram: &[u8], registers: &LineRegisters, line: &mut [BgPixel]) {
+    let y = registers.ly.wrapping_add(registers.scy);
+    for (x, pixel) in line.iter_mut().enumerate().take(SCREEN_WIDTH) {
+        *pixel = map_pixel(
+            vram,
+            registers,
+            false,
+            (x as u8).wrapping_add(registers.scx),
+            y,
+        );
+    }
+}
+
+/// Draws the window's current row over the background, from WX - 7 to the
+/// right edge. Returns whether any of it ended up on screen, which is what
+/// moves the window on to its next row.
+pub fn render_window(vram: &[u8], registers: &LineRegisters, line: &mut [BgPixel]) -> bool {
+    let start = registers.wx as i16 - WINDOW_X_OFFSET;
+    if start >= SCREEN_WIDTH as i16 {
+        return false;
+    }
+
+    for x in start.max(0)..SCREEN_WIDTH as i16 {
+        line[x as usize] = map_pixel(
+            vram,
+            registers,
+            true,
+            (x - start) as u8,
+            registers.window_line,
+        );
+    }
+    true
+}
+
+/// CGB attributes of a map entry, kept in VRAM bank 1 at the same spot as
+/// the tile number. Outside CGB mode there are none.
+pub fn map_attributes(vram: &[u8], registers: &LineRegisters, entry: usize) -> u8 {
+    if registers.cgb_mode {
+        vram[VRAM_BANK_SIZE + entry]
+    } else {
+        0
+    }
+}
+
+/// Row `y % 8` of a background or window tile, with the bank, flips,
+/// palette and priority its attributes ask for
+pub fn tile_pixels(vram: &[u8], lcdc: u8, tile: u8, attributes: u8, y: u8) -> [BgPixel; 8] {
+    let bank = if attributes & BANK_BIT != 0 {
+        VRAM_BANK_SIZE
+    } else {
+        0
+    };
+    let row = if attributes & Y_FLIP_BIT != 0 {
+        7 - y % 8
+    } else {
+        y % 8
+    };
+    let mut colors = tile_row(vram, bank + tile_address(lcdc, tile), row);
+    if attributes & X_FLIP_BIT != 0 {
+        colors.reverse();
+    }
+
+    colors.map(|color| BgPixel {
+        color,
+        palette: attributes & PALETTE_BITS,
+        priority: attributes & PRIORITY_BIT != 0,
+    })
+}
+
+/// Tile data at 0x8000 takes tile numbers as unsigned, tile data at 0x8800
+/// as signed around 0x9000
+pub fn tile_address(lcdc: u8, tile: u8) -> usize {
+    if lcdc & TILE_DATA_BIT != 0 {
+        tile as usize * TILE_SIZE
+    } else {
+        (0x1000 + tile as i8 as isize * TILE_SIZE as isize) as usize
+    }
+}
+
+/// VRAM index of the tile map entry covering pixel (x, y) of the
+/// background or window
+pub fn map_entry(lcdc: u8, window: bool, x: u8, y: u8) -> usize {
+    let select_bit = if window {
+        WINDOW_TILE_MAP_BIT
+    } else {
+        BG_TILE_MAP_BIT
+    };
+    let map = if lcdc & select_bit != 0 {
+        TILE_MAP_1
+    } else {
+        TILE_MAP_0
+    };
+    map + (y as usize / 8) * TILES_PER_MAP_ROW + x as usize / 8
+}
+
+fn map_pixel(vram: &[u8], registers: &LineRegisters, window: bool, x: u8, y: u8) -> BgPixel {
+    let entry = map_entry(registers.lcdc, window, x, y);
+    let attributes = map_attributes(vram, registers, entry);
+    tile_pixels(vram, registers.lcdc, vram[entry], attributes, y)[x as usize % 8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn registers(lcdc: u8, scx: u8, scy: u8, ly: u8) -> LineRegisters {
+        LineRegisters {
+            lcdc,
+            scx,
+            scy,
+            ly,
+            ..LineRegisters::default()
+        }
+    }
+
+    fn colors(line: &[BgPixel]) -> Vec<u8> {
+        line.iter().map(|pixel| pixel.color).collect()
+    }
+
+    /// Tile 1 is solid colour 3, every map entry points at tile 0 except
+    /// for the given ones
+    fn vram_with_tiles(entries: &[(usize, u8)]) -> Vec<u8> {
+        let mut vram = vec![0x00; 0x2000];
+        vram[TILE_SIZE..2 * TILE_SIZE].fill(0xFF);
+        for (entry, tile) in entries {
+            vram[*entry] = *tile;
+        }
+        vram
+    }
+
+    #[rstest]
+    #[case(0, 0, 0, 3)]
+    #[case(8, 0, 0, 0)]
+    #[case(0, 8, 0, 0)]
+    #[case(4, 0, 3, 3)]
+    #[case(4, 0, 4, 0)]
+    #[case(0, 252, 0, 3)]
+    fn should_scroll_background(
+        #[case] scx: u8,
+        #[case] scy: u8,
+        #[case] x: usize,
+        #[case] expected: u8,
+    ) {
+        let vram = vram_with_tiles(&[(TILE_MAP_0, 1)]);
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+
+        render_background(&vram, &registers(TILE_DATA_BIT, scx, scy, 4), &mut line);
+
+        assert_eq!(line[x].color, expected);
+    }
+
+    #[test]
+    fn should_wrap_around_tile_map() {
+        let vram = vram_with_tiles(&[(TILE_MAP_0 + 31, 1)]);
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+
+        render_background(&vram, &registers(TILE_DATA_BIT, 248, 0, 0), &mut line);
+
+        assert_eq!(colors(&line[0..8]), vec![3; 8]);
+        assert_eq!(colors(&line[8..16]), vec![0; 8]);
+    }
+
+    #[test]
+    fn should_select_tile_map() {
+        let vram = vram_with_tiles(&[(TILE_MAP_1, 1)]);
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+
+        render_background(
+            &vram,
+            &registers(TILE_DATA_BIT | BG_TILE_MAP_BIT, 0, 0, 0),
+            &mut line,
+        );
+
+        assert_eq!(line[0].color, 3);
+    }
+
+    #[rstest]
+    #[case(TILE_DATA_BIT, 0x00, 0x0000)]
+    #[case(TILE_DATA_BIT, 0x80, 0x0800)]
+    #[case(0, 0x00, 0x1000)]
+    #[case(0, 0x7F, 0x17F0)]
+    #[case(0, 0x80, 0x0800)]
+    #[case(0, 0xFF, 0x0FF0)]
+    fn should_address_tile_data(#[case] lcdc: u8, #[case] tile: u8, #[case] expected: usize) {
+        assert_eq!(tile_address(lcdc, tile), expected);
+    }
+
+    #[rstest]
+    #[case(7, 0, 3)]
+    #[case(7, 8, 0)]
+    #[case(87, 79, 2)]
+    #[case(87, 80, 3)]
+    #[case(0, 0, 3)]
+    #[case(0, 1, 0)]
+    fn should_draw_window_from_wx(#[case] wx: u8, #[case] x: usize, #[case] expected: u8) {
+        let vram = vram_with_tiles(&[(TILE_MAP_1 + 32, 1)]);
+        let mut line = [BgPixel {
+            color: 2,
+            ..BgPixel::default()
+        }; SCREEN_WIDTH];
+        let registers = LineRegisters {
+            wx,
+            window_line: 8,
+            ..registers(TILE_DATA_BIT | WINDOW_TILE_MAP_BIT, 0, 0, 0)
+        };
+
+        let drawn = render_window(&vram, &registers, &mut line);
+
+        assert!(drawn);
+        assert_eq!(line[x].color, expected);
+    }
+
+    #[test]
+    fn should_not_draw_window_past_right_edge() {
+        let vram = vram_with_tiles(&[(TILE_MAP_0, 1)]);
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+        let registers = LineRegisters {
+            wx: 167,
+            ..registers(TILE_DATA_BIT, 0, 0, 0)
+        };
+
+        assert!(!render_window(&vram, &registers, &mut line));
+        assert_eq!(line, [BgPixel::default(); SCREEN_WIDTH]);
+    }
+
+    #[rstest]
+    #[case(0x00, [3, 2, 1, 0, 0, 0, 0, 0], 0, false)]
+    #[case(X_FLIP_BIT, [0, 0, 0, 0, 0, 1, 2, 3], 0, false)]
+    #[case(Y_FLIP_BIT, [0; 8], 0, false)]
+    #[case(BANK_BIT, [1; 8], 0, false)]
+    #[case(PRIORITY_BIT | 0x05, [3, 2, 1, 0, 0, 0, 0, 0], 5, true)]
+    fn should_apply_cgb_tile_attributes(
+        #[case] attributes: u8,
+        #[case] expected_colors: [u8; 8],
+        #[case] expected_palette: u8,
+        #[case] expected_priority: bool,
+    ) {
+        // Row 0 of tile 0 counts down from 3 to 0, row 7 is blank and bank
+        // 1 has the same tile in solid colour 1
+        let mut vram = vec![0x00; 2 * VRAM_BANK_SIZE];
+        vram[0] = 0b1010_0000;
+        vram[1] = 0b1100_0000;
+        vram[VRAM_BANK_SIZE] = 0xFF;
+        vram[VRAM_BANK_SIZE + TILE_MAP_0] = attributes;
+        let registers = LineRegisters {
+            cgb_mode: true,
+            ..registers(TILE_DATA_BIT, 0, 0, 0)
+        };
+
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+
+        render_background(&vram, &registers, &mut line);
+
+        assert_eq!(colors(&line[0..8]), expected_colors[..]);
+        assert_eq!(line[0].palette, expected_palette);
+        assert_eq!(line[0].priority, expected_priority);
+    }
+
+    #[test]
+    fn should_ignore_attributes_outside_cgb_mode() {
+        let mut vram = vec![0x00; 2 * VRAM_BANK_SIZE];
+        vram[0] = 0xFF;
+        vram[VRAM_BANK_SIZE + TILE_MAP_0] = X_FLIP_BIT | BANK_BIT | 0x03;
+
+        let mut line = [BgPixel::default(); SCREEN_WIDTH];
+
+        render_background(&vram, &registers(TILE_DATA_BIT, 0, 0, 0), &mut line);
+
+        assert_eq!(
+            &line[0..8],
+            &[BgPixel {
+                color: 1,
+                ..BgPixel::default()
+            }; 8]
+        );
+    }
+}
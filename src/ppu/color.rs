@@ -0,0 +1,71 @@
+const CHANNEL_MASK: u16 = 0x1F;
+
+/// How RGB555 colours from CGB palette RAM become 24-bit RGB. The CGB LCD
+/// never shows what the raw values say: its colours bleed into each other
+/// and nothing gets fully saturated, which games were drawn to look right
+/// on.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorCorrection {
+    /// Every channel scaled to 8 bits as it is, far too vivid next to the
+    /// real screen
+    #[default]
+    Raw,
+    /// Channel mixing modelled on the CGB LCD, as done by higan
+    Cgb,
+    /// A lighter mix with brighter whites, as done by Gambatte
+    Gambatte,
+}
+
+impl ColorCorrection {
+    pub fn apply(&self, color: u16) -> [u8; 3] {
+        let r = (color & CHANNEL_MASK) as u32;
+        let g = ((color >> 5) & CHANNEL_MASK) as u32;
+        let b = ((color >> 10) & CHANNEL_MASK) as u32;
+
+        match self {
+            ColorCorrection::Raw => [r, g, b].map(|channel| (channel << 3 | channel >> 2) as u8),
+            ColorCorrection::Cgb => [
+                r * 26 + g * 4 + b * 2,
+                g * 24 + b * 8,
+                r * 6 + g * 4 + b * 22,
+            ]
+            .map(|channel| (channel.min(960) >> 2) as u8),
+            ColorCorrection::Gambatte => [
+                (r * 13 + g * 2 + b) >> 1,
+                (g * 3 + b) << 1,
+                (r * 3 + g * 2 + b * 11) >> 1,
+            ]
+            .map(|channel| channel as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(ColorCorrection::Raw, 0x7FFF, [255, 255, 255])]
+    #[case(ColorCorrection::Raw, 0x001F, [255, 0, 0])]
+    #[case(ColorCorrection::Raw, 0x0210, [132, 132, 0])]
+    #[case(ColorCorrection::Cgb, 0x7FFF, [240, 240, 240])]
+    #[case(ColorCorrection::Cgb, 0x001F, [201, 0, 46])]
+    #[case(ColorCorrection::Gambatte, 0x7FFF, [248, 248, 248])]
+    #[case(ColorCorrection::Gambatte, 0x001F, [201, 0, 46])]
+    fn should_convert_colors(
+        #[case] correction: ColorCorrection,
+        #[case] color: u16,
+        #[case] expected: [u8; 3],
+    ) {
+        assert_eq!(correction.apply(color), expected);
+    }
+
+    #[rstest]
+    #[case(ColorCorrection::Raw)]
+    #[case(ColorCorrection::Cgb)]
+    #[case(ColorCorrection::Gambatte)]
+    fn should_keep_black_black(#[case] correction: ColorCorrection) {
+        assert_eq!(correction.apply(0x0000), [0, 0, 0]);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{fs, path::Path};
+
+use crate::emulator_error::EmulatorError;
+
+pub type Rgb = [u8; 3];
+
+/// What drew a DMG pixel, each one has its own four colours
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum DmgLayer {
+    #[default]
+    Background,
+    Obj0,
+    Obj1,
+}
+
+/// Built-in colour sets for monochrome output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PalettePreset {
+    /// The original DMG's green tinted screen
+    Green,
+    /// The Game Boy Pocket's grey screen
+    Pocket,
+    /// The Game Boy Light's backlit blue-green screen
+    Light,
+    Greyscale,
+    /// Pulls the two middle shades apart, which are the ones that get
+    /// mixed up on most screens
+    HighContrast,
+    /// Orange and blue middle shades from the Okabe-Ito set, told apart
+    /// with every common form of colour blindness
+    ColorBlind,
+}
+
+/// Colours the four shades of the background, OBP0 and OBP1 end up as,
+/// lightest first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DmgPalette {
+    pub background: [Rgb; 4],
+    pub obj0: [Rgb; 4],
+    pub obj1: [Rgb; 4],
+}
+
+impl DmgPalette {
+    pub fn preset(preset: PalettePreset) -> DmgPalette {
+        let colors = match preset {
+            PalettePreset::Green => [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F],
+            PalettePreset::Pocket => [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+            PalettePreset::Light => [0x00B581, 0x009A71, 0x00694A, 0x004F3B],
+            PalettePreset::Greyscale => [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000],
+            PalettePreset::HighContrast => [0xFFFFFF, 0xC0C0C0, 0x404040, 0x000000],
+            PalettePreset::ColorBlind => [0xFFFFFF, 0xE69F00, 0x0072B2, 0x000000],
+        }
+        .map(|color: u32| {
+            let [_, r, g, b] = color.to_be_bytes();
+            [r, g, b]
+        });
+        DmgPalette {
+            background: colors,
+            obj0: colors,
+            obj1: colors,
+        }
+    }
+
+    /// Reads a palette file, one line per layer with four hex colours from
+    /// lightest to darkest:
+    ///
+    /// ```text
+    /// # Lines starting with a # are comments
+    /// bg   = FFFFFF AAAAAA 555555 000000
+    /// obj0 = FFFFFF FF8484 943A3A 000000
+    /// obj1 = #FFFFFF #7BFF31 #0063C5 #000000
+    /// ```
+    ///
+    /// `obj0` and `obj1` default to the background colours when left out.
+    pub fn parse(text: &str) -> Result<DmgPalette, EmulatorError> {
+        let mut background = None;
+        let mut obj0 = None;
+        let mut obj1 = None;
+
+        for line in text.lines() {
+            let content = line.trim();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || EmulatorError::InvalidPalette(content.to_string());
+            let (key, colors) = content.split_once('=').ok_or_else(invalid)?;
+            let colors: Vec<Rgb> = colors
+                .split_whitespace()
+                .map(parse_color)
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?;
+            let colors: [Rgb; 4] = colors.try_into().map_err(|_| invalid())?;
+            match key.trim().to_ascii_lowercase().as_str() {
+                "bg" => background = Some(colors),
+                "obj0" => obj0 = Some(colors),
+                "obj1" => obj1 = Some(colors),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let background =
+            background.ok_or_else(|| EmulatorError::InvalidPalette("bg".to_string()))?;
+        Ok(DmgPalette {
+            background,
+            obj0: obj0.unwrap_or(background),
+            obj1: obj1.unwrap_or(background),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<DmgPalette, EmulatorError> {
+        DmgPalette::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn color(&self, layer: DmgLayer, shade: u8) -> Rgb {
+        let colors = match layer {
+            DmgLayer::Background => &self.background,
+            DmgLayer::Obj0 => &self.obj0,
+            DmgLayer::Obj1 => &self.obj1,
+        };
+        colors[shade as usize & 0b11]
+    }
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        DmgPalette::preset(PalettePreset::Greyscale)
+    }
+}
+
+/// `RRGGBB` with an optional leading `#`
+fn parse_color(text: &str) -> Option<Rgb> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    if digits.len() != 6 || !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        return None;
+    }
+    let color = u32::from_str_radix(digits, 16).ok()?;
+    let [_, r, g, b] = color.to_be_bytes();
+    Some([r, g, b])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    use crate::cartridge::save_file::tests::scratch_dir;
+
+    #[rstest]
+    #[case(PalettePreset::Green, [0x9B, 0xBC, 0x0F], [0x0F, 0x38, 0x0F])]
+    #[case(PalettePreset::Pocket, [0xC4, 0xCF, 0xA1], [0x1F, 0x1F, 0x1F])]
+    #[case(PalettePreset::Greyscale, [0xFF, 0xFF, 0xFF], [0x00, 0x00, 0x00])]
+    fn should_map_shades_through_presets(
+        #[case] preset: PalettePreset,
+        #[case] lightest: Rgb,
+        #[case] darkest: Rgb,
+    ) {
+        let palette = DmgPalette::preset(preset);
+
+        assert_eq!(palette.color(DmgLayer::Background, 0), lightest);
+        assert_eq!(palette.color(DmgLayer::Obj1, 3), darkest);
+    }
+
+    #[test]
+    fn should_parse_separate_layer_colors() {
+        let text = "\
+            # Custom palette
+            bg   = FFFFFF AAAAAA 555555 000000
+
+            OBJ0 = #FFFFFF #FF8484 #943A3A #000000
+            obj1 = ffffff 7bff31 0063c5 000000
+        ";
+
+        let palette = DmgPalette::parse(text).unwrap();
+
+        assert_eq!(palette.color(DmgLayer::Background, 1), [0xAA, 0xAA, 0xAA]);
+        assert_eq!(palette.color(DmgLayer::Obj0, 1), [0xFF, 0x84, 0x84]);
+        assert_eq!(palette.color(DmgLayer::Obj1, 2), [0x00, 0x63, 0xC5]);
+    }
+
+    #[test]
+    fn should_default_object_colors_to_background() {
+        let palette = DmgPalette::parse("bg = 9BBC0F 8BAC0F 306230 0F380F").unwrap();
+
+        assert_eq!(palette, DmgPalette::preset(PalettePreset::Green));
+    }
+
+    #[rstest]
+    #[case("bg = FFFFFF AAAAAA 555555")]
+    #[case("bg = FFFFFF AAAAAA 555555 00000G")]
+    #[case("bg = FFFFFF AAAAAA 555555 +00000")]
+    #[case("obj2 = FFFFFF AAAAAA 555555 000000")]
+    #[case("bg FFFFFF AAAAAA 555555 000000")]
+    fn should_reject_invalid_lines(#[case] line: &str) {
+        assert_eq!(
+            DmgPalette::parse(line),
+            Err(EmulatorError::InvalidPalette(line.to_string()))
+        );
+    }
+
+    #[test]
+    fn should_require_background_colors() {
+        assert_eq!(
+            DmgPalette::parse("obj0 = FFFFFF AAAAAA 555555 000000"),
+            Err(EmulatorError::InvalidPalette("bg".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_load_palette_file() {
+        let path = scratch_dir("palette").join("light.txt");
+        fs::write(&path, "bg = 00B581 009A71 00694A 004F3B\n").unwrap();
+
+        assert_eq!(
+            DmgPalette::load(&path),
+            Ok(DmgPalette::preset(PalettePreset::Light))
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+
+use super::{
+    background::{map_attributes, map_entry, tile_pixels, BgPixel, WINDOW_X_OFFSET},
+    objects::{object_row, Object, ObjectPixel},
+    LineRegisters, SCREEN_WIDTH,
+};
+
+/// Dots spent on each of reading the tile number, the low and the high
+/// bitplane
+const FETCH_STEP_DOTS: u8 = 2;
+/// The first tile of every line is fetched twice, the first time for nothing
+const STARTUP_DOTS: u8 = 6;
+const OBJECT_FETCH_DOTS: u8 = 6;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum FetchStep {
+    #[default]
+    TileNumber,
+    DataLow,
+    DataHigh,
+    /// Waiting for the background FIFO to run empty
+    Push,
+}
+
+/// Background fetcher. It reads a tile row from VRAM in three steps of two
+/// dots each, then pushes its eight pixels as soon as the FIFO is empty.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Fetcher {
+    step: FetchStep,
+    dots: u8,
+    /// Tiles fetched so far on this line, or since the window started
+    tile_x: u8,
+    tile_number: u8,
+    /// CGB attributes read along with the tile number
+    attributes: u8,
+    row: [BgPixel; 8],
+}
+
+impl Fetcher {
+    /// Partway through reading a tile, as opposed to waiting to push or
+    /// about to start the next one
+    fn fetching(&self) -> bool {
+        match self.step {
+            FetchStep::TileNumber => self.dots > 0,
+            FetchStep::DataLow | FetchStep::DataHigh => true,
+            FetchStep::Push => false,
+        }
+    }
+}
+
+/// Pixel transfer as the hardware does it. The background FIFO feeds the
+/// LCD one pixel per dot while the fetcher refills it, the first SCX % 8
+/// pixels are thrown away, the window restarts the fetcher and every object
+/// pauses the output while its row is fetched into the object FIFO.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PixelFifo {
+    background: VecDeque<BgPixel>,
+    /// Lines up with the front of the background FIFO, the OAM index is
+    /// kept for CGB priority
+    objects: VecDeque<Option<(ObjectPixel, usize)>>,
+    fetcher: Fetcher,
+    /// Pixels sent to the LCD on this line
+    x: u8,
+    /// Pixels still to be dropped from the front of the background FIFO
+    discard: u8,
+    startup: u8,
+    window: bool,
+    /// Objects the OAM scan found that haven't been reached yet
+    pending_objects: Vec<Object>,
+    /// Object being fetched, with the dots left on it
+    object_fetch: Option<(Object, u8)>,
+}
+
+impl PixelFifo {
+    pub fn new() -> PixelFifo {
+        PixelFifo::default()
+    }
+
+    pub fn start_line(&mut self, registers: &LineRegisters, objects: &[Object]) {
+        *self = PixelFifo {
+            discard: registers.scx % 8,
+            startup: STARTUP_DOTS,
+            pending_objects: objects.to_vec(),
+            ..PixelFifo::default()
+        };
+    }
+
+    /// Every pixel of the line reached the LCD
+    pub fn done(&self) -> bool {
+        self.x as usize >= SCREEN_WIDTH
+    }
+
+    pub fn window_drawn(&self) -> bool {
+        self.window
+    }
+
+    /// Runs one dot of pixel transfer, returning the X of the pixel sent to
+    /// the LCD during it, if any, with its background pixel and the object
+    /// pixel on top of it
+    pub fn step(
+        &mut self,
+        registers: &LineRegisters,
+        vram: &[u8],
+    ) -> Option<(usize, BgPixel, Option<ObjectPixel>)> {
+        if self.done() {
+            return None;
+        }
+        if self.startup > 0 {
+            self.startup -= 1;
+            return None;
+        }
+
+        if !self.window
+            && registers.window_enabled()
+            && self.x as i16 >= registers.wx as i16 - WINDOW_X_OFFSET
+        {
+            self.start_window(registers);
+        }
+        if self.object_fetch.is_none() && self.discard == 0 && registers.objects_enabled() {
+            self.object_fetch = self.next_object().map(|object| (object, OBJECT_FETCH_DOTS));
+        }
+        if let Some((object, dots)) = self.object_fetch {
+            self.step_object_fetch(registers, vram, object, dots);
+            return None;
+        }
+
+        let pixel = self.pop_pixel();
+        self.step_fetcher(registers, vram);
+        pixel
+    }
+
+    /// The window replaces the background from here on, fetched from its
+    /// first tile. A WX below 7 hides the window's leftmost pixels.
+    fn start_window(&mut self, registers: &LineRegisters) {
+        self.window = true;
+        self.background.clear();
+        self.fetcher = Fetcher::default();
+        self.discard = (WINDOW_X_OFFSET as u8).saturating_sub(registers.wx);
+    }
+
+    /// Objects whose left edge the output reached, the ones hanging off the
+    /// left of the screen are reached at X 0
+    fn next_object(&mut self) -> Option<Object> {
+        let position = self
+            .pending_objects
+            .iter()
+            .position(|object| object.x as i16 - 8 <= self.x as i16)?;
+        Some(self.pending_objects.remove(position))
+    }
+
+    /// The background fetcher gets to finish the tile it's on first, only
+    /// then the object's row is read
+    fn step_object_fetch(
+        &mut self,
+        registers: &LineRegisters,
+        vram: &[u8],
+        object: Object,
+        dots: u8,
+    ) {
+        if self.fetcher.fetching() {
+            self.step_fetcher(registers, vram);
+            return;
+        }
+        if dots > 1 {
+            self.object_fetch = Some((object, dots - 1));
+            return;
+        }
+
+        self.object_fetch = None;
+        let row = object_row(
+            vram,
+            &object,
+            registers.ly,
+            registers.object_height(),
+            registers.cgb_mode,
+        );
+        let left = object.x as i16 - 8;
+        for (column, color) in row.into_iter().enumerate() {
+            let x = left + column as i16;
+            if x < self.x as i16 || color == 0 {
+                continue;
+            }
+            let slot = (x - self.x as i16) as usize;
+            while self.objects.len() <= slot {
+                self.objects.push_back(None);
+            }
+            // Earlier objects keep their pixels on DMG, the lower OAM index
+            // does on CGB
+            let replace = match self.objects[slot] {
+                None => true,
+                Some((_, index)) => registers.cgb_mode && object.index < index,
+            };
+            if replace {
+                self.objects[slot] = Some((object.pixel(color), object.index));
+            }
+        }
+    }
+
+    fn pop_pixel(&mut self) -> Option<(usize, BgPixel, Option<ObjectPixel>)> {
+        let pixel = self.background.pop_front()?;
+        if self.discard > 0 {
+            self.discard -= 1;
+            return None;
+        }
+
+        let object = self.objects.pop_front().flatten().map(|(pixel, _)| pixel);
+        let x = self.x as usize;
+        self.x += 1;
+        Some((x, pixel, object))
+    }
+
+    fn step_fetcher(&mut self, registers: &LineRegisters, vram: &[u8]) {
+        let fetcher = &mut self.fetcher;
+        if fetcher.step != FetchStep::Push {
+            fetcher.dots += 1;
+            if fetcher.dots < FETCH_STEP_DOTS {
+                return;
+            }
+            fetcher.dots = 0;
+        }
+
+        let (x, y) = if self.window {
+            (fetcher.tile_x * 8, registers.window_line)
+        } else {
+            (
+                (registers.scx / 8)
+                    .wrapping_add(fetcher.tile_x)
+                    .wrapping_mul(8),
+                registers.ly.wrapping_add(registers.scy),
+            )
+        };
+        match fetcher.step {
+            FetchStep::TileNumber => {
+                let entry = map_entry(registers.lcdc, self.window, x, y);
+                fetcher.tile_number = vram[entry];
+                fetcher.attributes = map_attributes(vram, registers, entry);
+                fetcher.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => fetcher.step = FetchStep::DataHigh,
+            FetchStep::DataHigh => {
+                fetcher.row = tile_pixels(
+                    vram,
+                    registers.lcdc,
+                    fetcher.tile_number,
+                    fetcher.attributes,
+                    y,
+                );
+                fetcher.step = FetchStep::Push;
+                self.push();
+            }
+            FetchStep::Push => self.push(),
+        }
+    }
+
+    fn push(&mut self) {
+        if !self.background.is_empty() {
+            return;
+        }
+        self.background.extend(self.fetcher.row);
+        self.fetcher.tile_x = self.fetcher.tile_x.wrapping_add(1);
+        self.fetcher.step = FetchStep::TileNumber;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn registers() -> LineRegisters {
+        LineRegisters {
+            lcdc: 0x93,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            bgp: 0xE4,
+            obp0: 0xE4,
+            obp1: 0xE4,
+            wx: 0,
+            window_line: 0,
+            window_y_reached: false,
+            cgb_mode: false,
+        }
+    }
+
+    /// Background tile n has colour n % 4 in every pixel and the map counts
+    /// up 0, 1, 2, ... along the first row. Tile 4 is an object tile with
+    /// its left column in colour 1. Bank 1 is left blank.
+    fn vram() -> Vec<u8> {
+        let mut vram = vec![0x00; 0x4000];
+        for tile in 0..32 {
+            let color = tile % 4;
+            for row in 0..8 {
+                vram[tile * 16 + row * 2] = if color & 1 != 0 { 0xFF } else { 0x00 };
+                vram[tile * 16 + row * 2 + 1] = if color & 2 != 0 { 0xFF } else { 0x00 };
+            }
+            vram[0x1800 + tile] = tile as u8;
+        }
+        for row in 0..8 {
+            vram[0x0200 + row * 2] = 0x80;
+            vram[0x0200 + row * 2 + 1] = 0x00;
+        }
+        vram
+    }
+
+    /// Runs a whole line, returning the dots it took and the shades drawn
+    fn run_line(
+        registers: &LineRegisters,
+        vram: &[u8],
+        objects: &[Object],
+    ) -> (u32, [u8; SCREEN_WIDTH]) {
+        let mut fifo = PixelFifo::new();
+        fifo.start_line(registers, objects);
+        let mut line = [0xFF; SCREEN_WIDTH];
+        let mut dots = 0;
+        while !fifo.done() {
+            if let Some((x, pixel, object)) = fifo.step(registers, vram) {
+                line[x] = registers.shade(pixel.color, object);
+            }
+            dots += 1;
+        }
+        (dots, line)
+    }
+
+    fn object(x: u8, index: usize) -> Object {
+        Object {
+            y: 16,
+            x,
+            tile: 0x20,
+            attributes: 0,
+            index,
+        }
+    }
+
+    #[test]
+    fn should_take_172_dots_for_plain_line() {
+        let (dots, line) = run_line(&registers(), &vram(), &[]);
+
+        assert_eq!(dots, 172);
+        assert_eq!(&line[0..8], &[0; 8]);
+        assert_eq!(&line[8..16], &[1; 8]);
+        assert_eq!(&line[152..160], &[3; 8]);
+    }
+
+    #[rstest]
+    #[case(1, 173)]
+    #[case(7, 179)]
+    #[case(8, 172)]
+    #[case(13, 177)]
+    fn should_discard_fine_scroll_pixels(#[case] scx: u8, #[case] expected_dots: u32) {
+        let registers = LineRegisters { scx, ..registers() };
+
+        let (dots, line) = run_line(&registers, &vram(), &[]);
+
+        assert_eq!(dots, expected_dots);
+        let first_tile = scx / 8;
+        let expected: Vec<u8> = (0..8u8)
+            .map(|x| (first_tile + (x + scx % 8) / 8) % 4)
+            .collect();
+        assert_eq!(&line[0..8], &expected[..]);
+    }
+
+    #[test]
+    fn should_restart_fetch_for_window() {
+        let registers = LineRegisters {
+            lcdc: 0xF3,
+            wx: 7 + 20,
+            window_y_reached: true,
+            ..registers()
+        };
+        let mut vram = vram();
+        vram.copy_within(0x1800..0x1820, 0x1C00);
+        vram[0x1800..0x1820].fill(3);
+
+        let (dots, line) = run_line(&registers, &vram, &[]);
+
+        assert_eq!(dots, 172 + 6);
+        assert_eq!(&line[16..20], &[3; 4]);
+        assert_eq!(&line[20..28], &[0; 8]);
+        assert_eq!(&line[28..36], &[1; 8]);
+    }
+
+    #[rstest]
+    #[case(0, 172 + 6)]
+    #[case(8, 172 + 6)]
+    #[case(13, 172 + 7)]
+    #[case(168, 172)]
+    fn should_stall_for_objects(#[case] x: u8, #[case] expected_dots: u32) {
+        let (dots, _) = run_line(&registers(), &vram(), &[object(x, 0)]);
+
+        assert_eq!(dots, expected_dots);
+    }
+
+    #[test]
+    fn should_mix_objects_into_output() {
+        let mut vram = vram();
+        vram[0x1800..0x1820].fill(0);
+
+        let (_, line) = run_line(&registers(), &vram, &[object(10, 0)]);
+
+        assert_eq!(line[1], 0);
+        assert_eq!(line[2], 1);
+        assert_eq!(line[3], 0);
+    }
+
+    #[rstest]
+    #[case(false, 2)]
+    #[case(true, 3)]
+    fn should_resolve_overlapping_objects_by_model(#[case] cgb_mode: bool, #[case] expected: u8) {
+        let registers = LineRegisters {
+            cgb_mode,
+            obp0: 0b1000,
+            obp1: 0b1100,
+            ..registers()
+        };
+        let mut vram = vram();
+        vram[0x1800..0x1820].fill(0);
+        let first = object(10, 1);
+        let second = Object {
+            attributes: 0x10,
+            index: 0,
+            ..object(10, 0)
+        };
+
+        // The DMG keeps the object fetched first, the CGB the lower index
+        let (_, line) = run_line(&registers, &vram, &[first, second]);
+
+        assert_eq!(line[2], expected);
+    }
+
+    #[test]
+    fn should_pick_up_palette_changes_mid_line() {
+        let mut fifo = PixelFifo::new();
+        let mut registers = registers();
+        let vram = vram();
+        fifo.start_line(&registers, &[]);
+        let mut line = [0xFF; SCREEN_WIDTH];
+
+        while !fifo.done() {
+            if let Some((x, pixel, object)) = fifo.step(&registers, &vram) {
+                line[x] = registers.shade(pixel.color, object);
+                if x == 11 {
+                    registers.bgp = 0x00;
+                }
+            }
+        }
+
+        assert_eq!(&line[8..12], &[1; 4]);
+        assert_eq!(&line[12..16], &[0; 4]);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::cpu::memory_bus::VRAM_BANK_SIZE;
+
+use super::{
+    tile::{tile_row, TILE_SIZE},
+    SCREEN_WIDTH,
+};
+
+pub const MAX_OBJECTS_PER_LINE: usize = 10;
+const OAM_ENTRY_SIZE: usize = 4;
+const OBJECT_COUNT: usize = 40;
+/// OAM coordinates put the top left corner of the screen at (8, 16)
+const X_OFFSET: i16 = 8;
+const Y_OFFSET: i16 = 16;
+
+const BG_PRIORITY_BIT: u8 = 0b1000_0000;
+const Y_FLIP_BIT: u8 = 0b0100_0000;
+const X_FLIP_BIT: u8 = 0b0010_0000;
+const DMG_PALETTE_BIT: u8 = 0b0001_0000;
+const CGB_BANK_BIT: u8 = 0b0000_1000;
+const CGB_PALETTE_BITS: u8 = 0b0000_0111;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Object {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    /// Position in OAM, which breaks ties between overlapping objects
+    pub index: usize,
+}
+
+impl Object {
+    pub fn pixel(&self, color: u8) -> ObjectPixel {
+        ObjectPixel {
+            color,
+            obp1: self.attributes & DMG_PALETTE_BIT != 0,
+            palette: self.attributes & CGB_PALETTE_BITS,
+            behind_background: self.attributes & BG_PRIORITY_BIT != 0,
+        }
+    }
+}
+
+/// An object pixel that won over the other objects on its dot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectPixel {
+    /// Colour index, never 0 since that one is transparent
+    pub color: u8,
+    /// Whether OBP1 is used instead of OBP0
+    pub obp1: bool,
+    /// CGB palette, OBP0 and OBP1 are only used on DMG
+    pub palette: u8,
+    /// Background colours 1-3 are drawn over this pixel
+    pub behind_background: bool,
+}
+
+/// What the OAM scan finds: the first ten objects in OAM that cover line
+/// `ly`, whether they are on screen horizontally or not
+pub fn select_objects(oam: &[u8], ly: u8, height: u8) -> Vec<Object> {
+    oam.chunks(OAM_ENTRY_SIZE)
+        .take(OBJECT_COUNT)
+        .enumerate()
+        .map(|(index, entry)| Object {
+            y: entry[0],
+            x: entry[1],
+            tile: entry[2],
+            attributes: entry[3],
+            index,
+        })
+        .filter(|object| {
+            let top = object.y as i16 - Y_OFFSET;
+            (top..top + height as i16).contains(&(ly as i16))
+        })
+        .take(MAX_OBJECTS_PER_LINE)
+        .collect()
+}
+
+/// Picks the visible object pixel for every dot of line `ly`. Where objects
+/// overlap the one with the smaller X wins on DMG, ties and every overlap
+/// on CGB going to the one earlier in OAM. Transparent pixels let the next
+/// object through.
+pub fn render_objects(
+    vram: &[u8],
+    objects: &[Object],
+    ly: u8,
+    height: u8,
+    cgb_mode: bool,
+) -> [Option<ObjectPixel>; SCREEN_WIDTH] {
+    let mut ordered = objects.to_vec();
+    if !cgb_mode {
+        ordered.sort_by_key(|object| (object.x, object.index));
+    }
+
+    let mut pixels = [None; SCREEN_WIDTH];
+    // Lowest priority first so better objects draw over it
+    for object in ordered.iter().rev() {
+        let left = object.x as i16 - X_OFFSET;
+        let row = object_row(vram, object, ly, height, cgb_mode);
+        for (column, color) in row.into_iter().enumerate() {
+            let x = left + column as i16;
+            if color == 0 || !(0..SCREEN_WIDTH as i16).contains(&x) {
+                continue;
+            }
+            pixels[x as usize] = Some(object.pixel(color));
+        }
+    }
+    pixels
+}
+
+/// Colour indices of the object's row on line `ly`, flips applied. Tall
+/// objects ignore the lowest bit of their tile number. On CGB the tile can
+/// come from either VRAM bank. The row wraps within `height`, as objects
+/// picked while tall can be drawn after LCDC switched to short ones.
+pub fn object_row(vram: &[u8], object: &Object, ly: u8, height: u8, cgb_mode: bool) -> [u8; 8] {
+    let mut row = (ly as i16 - (object.y as i16 - Y_OFFSET)) as u8 & (height - 1);
+    if object.attributes & Y_FLIP_BIT != 0 {
+        row = height - 1 - row;
+    }
+    let tile = if height == 16 {
+        (object.tile & 0xFE) + row / 8
+    } else {
+        object.tile
+    };
+
+    let bank = if cgb_mode && object.attributes & CGB_BANK_BIT != 0 {
+        VRAM_BANK_SIZE
+    } else {
+        0
+    };
+    let mut pixels = tile_row(vram, bank + tile as usize * TILE_SIZE, row % 8);
+    if object.attributes & X_FLIP_BIT != 0 {
+        pixels.reverse();
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn oam_with(objects: &[(u8, u8, u8, u8)]) -> Vec<u8> {
+        let mut oam = vec![0x00; OBJECT_COUNT * OAM_ENTRY_SIZE];
+        for (index, (y, x, tile, attributes)) in objects.iter().enumerate() {
+            oam[index * OAM_ENTRY_SIZE..(index + 1) * OAM_ENTRY_SIZE].copy_from_slice(&[
+                *y,
+                *x,
+                *tile,
+                *attributes,
+            ]);
+        }
+        oam
+    }
+
+    /// Tile n has every pixel set to colour n, tile 4 has only its left
+    /// column and top row set to colour 1
+    fn vram_with_tiles() -> Vec<u8> {
+        let mut vram = vec![0x00; 0x2000];
+        for tile in 1..4 {
+            for row in 0..8 {
+                let address = tile * TILE_SIZE + row * 2;
+                vram[address] = if tile & 1 != 0 { 0xFF } else { 0x00 };
+                vram[address + 1] = if tile & 2 != 0 { 0xFF } else { 0x00 };
+            }
+        }
+        vram[4 * TILE_SIZE] = 0xFF;
+        for row in 1..8 {
+            vram[4 * TILE_SIZE + row * 2] = 0x80;
+        }
+        vram
+    }
+
+    fn colors(pixels: &[Option<ObjectPixel>]) -> Vec<u8> {
+        pixels
+            .iter()
+            .map(|pixel| pixel.map_or(0, |pixel| pixel.color))
+            .collect()
+    }
+
+    #[rstest]
+    #[case(0, 8, 1)]
+    #[case(7, 8, 1)]
+    #[case(8, 8, 0)]
+    #[case(15, 16, 1)]
+    #[case(16, 16, 0)]
+    fn should_select_objects_covering_line(
+        #[case] ly: u8,
+        #[case] height: u8,
+        #[case] expected: usize,
+    ) {
+        let oam = oam_with(&[(16, 8, 1, 0)]);
+
+        assert_eq!(select_objects(&oam, ly, height).len(), expected);
+    }
+
+    #[test]
+    fn should_select_at_most_ten_objects_per_line() {
+        let entries: Vec<_> = (0..12).map(|index| (16, index * 8, 1, 0)).collect();
+        let oam = oam_with(&entries);
+
+        let objects = select_objects(&oam, 0, 8);
+
+        assert_eq!(objects.len(), MAX_OBJECTS_PER_LINE);
+        assert_eq!(objects[9].index, 9);
+        // Off-screen objects still use up a slot
+        assert_eq!(objects[0].x, 0);
+        let pixels = render_objects(&vram_with_tiles(), &objects, 0, 8, false);
+        assert!(pixels[72..].iter().all(|pixel| pixel.is_none()));
+    }
+
+    #[rstest]
+    #[case(0, [1, 0, 0, 0, 0, 0, 0, 0])]
+    #[case(X_FLIP_BIT, [0, 0, 0, 0, 0, 0, 0, 1])]
+    fn should_flip_horizontally(#[case] attributes: u8, #[case] expected: [u8; 8]) {
+        let oam = oam_with(&[(16, 8, 4, attributes)]);
+        let objects = select_objects(&oam, 1, 8);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, 1, 8, false);
+
+        assert_eq!(colors(&pixels[0..8]), expected[..]);
+        let top = render_objects(&vram_with_tiles(), &objects, 0, 8, false);
+        assert_eq!(colors(&top[0..8]), vec![1; 8]);
+    }
+
+    #[rstest]
+    #[case(0, 0, 1)]
+    #[case(0, 1, 0)]
+    #[case(Y_FLIP_BIT, 7, 1)]
+    #[case(Y_FLIP_BIT, 6, 0)]
+    fn should_flip_vertically(#[case] attributes: u8, #[case] ly: u8, #[case] expected: u8) {
+        let oam = oam_with(&[(16, 8, 4, attributes)]);
+        let objects = select_objects(&oam, ly, 8);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, ly, 8, false);
+
+        assert_eq!(colors(&pixels[4..5]), vec![expected]);
+    }
+
+    #[rstest]
+    #[case(0, 0, 2)]
+    #[case(0, 8, 3)]
+    #[case(Y_FLIP_BIT, 0, 3)]
+    #[case(Y_FLIP_BIT, 15, 2)]
+    fn should_use_tile_pair_for_tall_objects(
+        #[case] attributes: u8,
+        #[case] ly: u8,
+        #[case] expected: u8,
+    ) {
+        let oam = oam_with(&[(16, 8, 3, attributes)]);
+        let objects = select_objects(&oam, ly, 16);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, ly, 16, false);
+
+        assert_eq!(colors(&pixels[0..1]), vec![expected]);
+    }
+
+    #[rstest]
+    #[case(0, 12, 4)]
+    #[case(Y_FLIP_BIT, 12, 3)]
+    #[case(Y_FLIP_BIT, 15, 0)]
+    fn should_wrap_rows_of_objects_selected_while_tall(
+        #[case] attributes: u8,
+        #[case] ly: u8,
+        #[case] expected_row: u8,
+    ) {
+        let mut vram = vram_with_tiles();
+        vram[4 * TILE_SIZE + expected_row as usize * 2] = 0x01;
+        let oam = oam_with(&[(16, 8, 4, attributes)]);
+        let objects = select_objects(&oam, ly, 16);
+
+        let pixels = render_objects(&vram, &objects, ly, 8, false);
+
+        assert_eq!(colors(&pixels[7..8]), vec![1]);
+    }
+
+    #[rstest]
+    // DMG: the smaller X wins wherever the objects overlap
+    #[case(false, [(16, 12, 1, 0), (16, 8, 2, 0)], [2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1])]
+    // Same X: the first in OAM wins
+    #[case(false, [(16, 8, 1, 0), (16, 8, 2, 0)], [1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0])]
+    // CGB: always the first in OAM
+    #[case(true, [(16, 12, 1, 0), (16, 8, 2, 0)], [2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1])]
+    fn should_resolve_overlapping_objects(
+        #[case] oam_priority: bool,
+        #[case] entries: [(u8, u8, u8, u8); 2],
+        #[case] expected: [u8; 12],
+    ) {
+        let oam = oam_with(&entries);
+        let objects = select_objects(&oam, 0, 8);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, 0, 8, oam_priority);
+
+        assert_eq!(colors(&pixels[0..12]), expected[..]);
+    }
+
+    #[test]
+    fn should_let_objects_behind_show_through_transparent_pixels() {
+        let oam = oam_with(&[(16, 8, 4, 0), (16, 8, 2, 0)]);
+        let objects = select_objects(&oam, 1, 8);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, 1, 8, false);
+
+        assert_eq!(colors(&pixels[0..8]), vec![1, 2, 2, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn should_carry_palette_and_priority_attributes() {
+        let oam = oam_with(&[(16, 8, 1, DMG_PALETTE_BIT | BG_PRIORITY_BIT)]);
+        let objects = select_objects(&oam, 0, 8);
+
+        let pixels = render_objects(&vram_with_tiles(), &objects, 0, 8, false);
+
+        assert_eq!(
+            pixels[0],
+            Some(ObjectPixel {
+                color: 1,
+                obp1: true,
+                palette: 0,
+                behind_background: true,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(false, 1)]
+    #[case(true, 3)]
+    fn should_use_cgb_bank_and_palette(#[case] cgb_mode: bool, #[case] expected_color: u8) {
+        let mut vram = vram_with_tiles();
+        vram.resize(2 * VRAM_BANK_SIZE, 0x00);
+        vram.copy_within(3 * TILE_SIZE..4 * TILE_SIZE, VRAM_BANK_SIZE + TILE_SIZE);
+        let oam = oam_with(&[(16, 8, 1, CGB_BANK_BIT | 0x05)]);
+        let objects = select_objects(&oam, 0, 8);
+
+        let pixels = render_objects(&vram, &objects, 0, 8, cgb_mode);
+
+        let pixel = pixels[0].unwrap();
+        assert_eq!(pixel.color, expected_color);
+        assert_eq!(pixel.palette, 5);
+    }
+}
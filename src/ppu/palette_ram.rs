@@ -0,0 +1,122 @@
+const PALETTE_RAM_SIZE: usize = 64;
+const BYTES_PER_PALETTE: usize = 8;
+const BYTES_PER_COLOR: usize = 2;
+const INDEX_BITS: u8 = 0b0011_1111;
+const AUTO_INCREMENT_BIT: u8 = 0b1000_0000;
+const SPEC_UNUSED_BIT: u8 = 0b0100_0000;
+
+/// CGB colour palette memory, reached through a specification register
+/// (BCPS or OCPS) that selects a byte and a data register (BCPD or OCPD)
+/// that reads or writes it. Eight palettes of four little endian RGB555
+/// colours each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteRam {
+    data: [u8; PALETTE_RAM_SIZE],
+    index: u8,
+    /// Writes through the data register move on to the next byte
+    auto_increment: bool,
+}
+
+impl PaletteRam {
+    /// Every colour starts out white
+    pub fn new() -> PaletteRam {
+        PaletteRam {
+            data: [0xFF; PALETTE_RAM_SIZE],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+
+    pub fn read_spec(&self) -> u8 {
+        let auto_increment = if self.auto_increment {
+            AUTO_INCREMENT_BIT
+        } else {
+            0
+        };
+        auto_increment | SPEC_UNUSED_BIT | self.index
+    }
+
+    pub fn write_spec(&mut self, new_value: u8) {
+        self.index = new_value & INDEX_BITS;
+        self.auto_increment = new_value & AUTO_INCREMENT_BIT != 0;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    /// Reads never increment the index, only writes do
+    pub fn write_data(&mut self, new_value: u8) {
+        self.data[self.index as usize] = new_value;
+        if self.auto_increment {
+            self.index = (self.index + 1) & INDEX_BITS;
+        }
+    }
+
+    /// RGB555 colour `index` of palette `palette`, red in the lowest bits
+    pub fn color(&self, palette: u8, index: u8) -> u16 {
+        let offset = palette as usize * BYTES_PER_PALETTE + index as usize * BYTES_PER_COLOR;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]]) & 0x7FFF
+    }
+}
+
+impl Default for PaletteRam {
+    fn default() -> Self {
+        PaletteRam::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[test]
+    fn should_auto_increment_on_data_writes() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(AUTO_INCREMENT_BIT | 0x08);
+
+        palettes.write_data(0x1F);
+        palettes.write_data(0x00);
+        palettes.write_data(0xE0);
+        palettes.write_data(0x03);
+
+        assert_eq!(palettes.read_spec(), 0xC0 | 0x0C);
+        assert_eq!(palettes.color(1, 0), 0x001F);
+        assert_eq!(palettes.color(1, 1), 0x03E0);
+        assert_eq!(palettes.color(0, 0), 0x7FFF);
+    }
+
+    #[test]
+    fn should_wrap_index_around_palette_ram() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(AUTO_INCREMENT_BIT | 0x3F);
+
+        palettes.write_data(0x12);
+        palettes.write_data(0x34);
+
+        assert_eq!(palettes.read_spec(), 0xC1);
+        palettes.write_spec(0x3F);
+        assert_eq!(palettes.read_data(), 0x12);
+        palettes.write_spec(0x00);
+        assert_eq!(palettes.read_data(), 0x34);
+    }
+
+    #[rstest]
+    #[case(false, 0x05)]
+    #[case(true, 0x06)]
+    fn should_only_increment_when_enabled(#[case] auto_increment: bool, #[case] expected: u8) {
+        let mut palettes = PaletteRam::new();
+        let flag = if auto_increment {
+            AUTO_INCREMENT_BIT
+        } else {
+            0
+        };
+        palettes.write_spec(flag | 0x05);
+
+        palettes.read_data();
+        palettes.write_data(0x00);
+
+        assert_eq!(palettes.read_spec() & INDEX_BITS, expected);
+    }
+}
@@ -0,0 +1,33 @@
+/// Bytes per 8x8 tile, two bitplanes per row
+pub const TILE_SIZE: usize = 16;
+
+/// Colour indices of one row of the tile at `address`, leftmost pixel first.
+/// The first byte of a row holds the low bits, the second the high bits.
+pub fn tile_row(data: &[u8], address: usize, row: u8) -> [u8; 8] {
+    let low = data[address + row as usize * 2];
+    let high = data[address + row as usize * 2 + 1];
+    let mut pixels = [0; 8];
+    for (x, pixel) in pixels.iter_mut().enumerate() {
+        let bit = 7 - x;
+        *pixel = ((high >> bit) & 0x01) << 1 | ((low >> bit) & 0x01);
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case([0x00, 0x00], [0, 0, 0, 0, 0, 0, 0, 0])]
+    #[case([0xFF, 0x00], [1, 1, 1, 1, 1, 1, 1, 1])]
+    #[case([0x00, 0xFF], [2, 2, 2, 2, 2, 2, 2, 2])]
+    #[case([0x3C, 0x7E], [0, 2, 3, 3, 3, 3, 2, 0])]
+    fn should_combine_bitplanes(#[case] bytes: [u8; 2], #[case] expected: [u8; 8]) {
+        let mut data = vec![0x00; TILE_SIZE];
+        data[6..8].copy_from_slice(&bytes);
+
+        assert_eq!(tile_row(&data, 0, 3), expected);
+    }
+}
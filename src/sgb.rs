@@ -0,0 +1,590 @@
+pub mod attributes;
+pub mod border;
+pub mod packet;
+
+use crate::ppu::{color::ColorCorrection, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use self::{
+    attributes::{
+        apply_attribute_file, apply_blocks, apply_cells, apply_division, apply_lines, AttributeMap,
+        ATTRIBUTE_FILE_SIZE, CELLS_WIDE, CELL_COUNT,
+    },
+    border::{
+        border_pixel, BORDER_TILES, BORDER_TILE_SIZE, COLORS_PER_PALETTE, FIRST_BORDER_PALETTE,
+        MAP_SIZE,
+    },
+    packet::{PacketReceiver, PACKET_SIZE},
+};
+
+/// The SNES picture, with the Game Boy screen in the middle of the border
+pub const SGB_WIDTH: usize = 256;
+pub const SGB_HEIGHT: usize = 224;
+const SCREEN_LEFT: usize = 48;
+const SCREEN_TOP: usize = 40;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const ATTR_LIN: u8 = 0x05;
+const ATTR_DIV: u8 = 0x06;
+const ATTR_CHR: u8 = 0x07;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const ATTR_TRN: u8 = 0x15;
+const ATTR_SET: u8 = 0x16;
+const MASK_EN: u8 = 0x17;
+
+/// VRAM transfers copy what the Game Boy shows on its next frame: 256
+/// tiles, laid out left to right and top to bottom
+const TRANSFER_SIZE: usize = 0x1000;
+const SYSTEM_PALETTES: usize = 512;
+const ATTRIBUTE_FILES: usize = 45;
+const APPLY_ATTRIBUTE_FILE_BIT: u8 = 0b1000_0000;
+const CANCEL_MASK_BIT: u8 = 0b0100_0000;
+const ATTRIBUTE_FILE_BITS: u8 = 0b0011_1111;
+/// Reading P1 with both lines high answers with the controller ID instead
+/// of buttons while more than one controller is connected
+const SELECT_BITS: u8 = 0b0011_0000;
+const P15_BIT: u8 = 0b0010_0000;
+
+/// Greys from white to black until the game sets its own colours
+const DEFAULT_PALETTE: [u16; 4] = [0x7FFF, 0x5294, 0x294A, 0x0000];
+
+/// What MASK_EN shows in place of the game screen while it prepares the
+/// next one
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Mask {
+    #[default]
+    None,
+    /// Keeps showing the picture from when the mask went on
+    Freeze,
+    Black,
+    /// Colour 0, the backdrop
+    Color0,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transfer {
+    SystemPalettes,
+    /// CHR_TRN loads the lower or upper half of the border tiles
+    BorderTiles(usize),
+    BorderMap,
+    AttributeFiles,
+}
+
+/// Super Game Boy side of the console. Games talk to it through command
+/// packets sent over P1 and bulk data copied off the screen, and it
+/// colours the Game Boy picture with four palettes chosen per 8x8 cell,
+/// inside a border of SNES tiles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sgb {
+    receiver: PacketReceiver,
+    /// Packets of a command that spans several, which only the first one
+    /// gives the length of
+    command: Vec<u8>,
+    packets_left: usize,
+    /// RGB555 colours, colour 0 of palette 0 is shared by all four
+    palettes: [[u16; 4]; 4],
+    system_palettes: Vec<[u16; 4]>,
+    attributes: AttributeMap,
+    attribute_files: Vec<u8>,
+    border_tiles: Vec<u8>,
+    border_map: Vec<u8>,
+    border_palettes: [[u16; COLORS_PER_PALETTE]; 4],
+    mask: Mask,
+    frozen: Option<Vec<u8>>,
+    transfer: Option<Transfer>,
+    /// 1, 2 or 4 after MLT_REQ
+    players: u8,
+    player: u8,
+    /// Last value written to P1
+    p1: u8,
+}
+
+impl Sgb {
+    pub fn new() -> Sgb {
+        Sgb {
+            receiver: PacketReceiver::new(),
+            command: Vec::new(),
+            packets_left: 0,
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: vec![DEFAULT_PALETTE; SYSTEM_PALETTES],
+            attributes: [0; CELL_COUNT],
+            attribute_files: vec![0x00; ATTRIBUTE_FILES * ATTRIBUTE_FILE_SIZE],
+            border_tiles: vec![0x00; BORDER_TILES * BORDER_TILE_SIZE],
+            border_map: vec![0x00; MAP_SIZE],
+            border_palettes: [[0x0000; COLORS_PER_PALETTE]; 4],
+            mask: Mask::None,
+            frozen: None,
+            transfer: None,
+            players: 1,
+            player: 0,
+            p1: SELECT_BITS,
+        }
+    }
+
+    pub fn mask(&self) -> Mask {
+        self.mask
+    }
+
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    /// Takes a write to P1. Packets come in through the select lines and
+    /// every time P15 goes back high the next controller is selected.
+    pub fn write_p1(&mut self, new_value: u8) {
+        let previous = std::mem::replace(&mut self.p1, new_value);
+        if previous & P15_BIT == 0 && new_value & P15_BIT != 0 {
+            self.player = (self.player + 1) % self.players;
+        }
+        if let Some(packet) = self.receiver.write(new_value) {
+            self.receive(packet);
+        }
+    }
+
+    /// Low nibble of P1 to read instead of the buttons, if any. That is
+    /// 0xF minus the selected controller, with both lines high and more
+    /// than one controller connected.
+    pub fn joypad_id(&self, p1: u8) -> Option<u8> {
+        (self.players > 1 && p1 & SELECT_BITS == SELECT_BITS).then_some(0x0F - self.player)
+    }
+
+    /// Call once per frame with the finished Game Boy picture, as shades
+    /// 0-3. A pending VRAM transfer reads its data from it.
+    pub fn end_frame(&mut self, framebuffer: &[u8]) {
+        if self.mask == Mask::Freeze && self.frozen.is_none() {
+            self.frozen = Some(framebuffer.to_vec());
+        }
+        let Some(transfer) = self.transfer.take() else {
+            return;
+        };
+
+        let data = transfer_data(framebuffer);
+        match transfer {
+            Transfer::SystemPalettes => {
+                for (palette, colors) in self.system_palettes.iter_mut().zip(data.chunks(8)) {
+                    *palette = [0, 1, 2, 3].map(|index| color_at(colors, index));
+                }
+            }
+            Transfer::BorderTiles(half) => {
+                let start = half * TRANSFER_SIZE;
+                self.border_tiles[start..start + TRANSFER_SIZE].copy_from_slice(&data);
+            }
+            Transfer::BorderMap => {
+                self.border_map.copy_from_slice(&data[..MAP_SIZE]);
+                let colors = &data[MAP_SIZE..];
+                for (palette, colors) in self.border_palettes.iter_mut().zip(colors.chunks(32)) {
+                    for (index, color) in palette.iter_mut().enumerate() {
+                        *color = color_at(colors, index);
+                    }
+                }
+            }
+            Transfer::AttributeFiles => {
+                let size = self.attribute_files.len();
+                self.attribute_files.copy_from_slice(&data[..size]);
+            }
+        }
+    }
+
+    fn receive(&mut self, packet: [u8; PACKET_SIZE]) {
+        if self.packets_left == 0 {
+            self.command.clear();
+            self.packets_left = (packet[0] & 0b111) as usize;
+            if self.packets_left == 0 {
+                return;
+            }
+        }
+        self.command.extend(packet);
+        self.packets_left -= 1;
+        if self.packets_left == 0 {
+            let command = std::mem::take(&mut self.command);
+            self.execute(&command);
+        }
+    }
+
+    fn execute(&mut self, command: &[u8]) {
+        match command[0] >> 3 {
+            PAL01 => self.set_palette_pair(0, 1, command),
+            PAL23 => self.set_palette_pair(2, 3, command),
+            PAL03 => self.set_palette_pair(0, 3, command),
+            PAL12 => self.set_palette_pair(1, 2, command),
+            ATTR_BLK => apply_blocks(&mut self.attributes, command),
+            ATTR_LIN => apply_lines(&mut self.attributes, command),
+            ATTR_DIV => apply_division(&mut self.attributes, command),
+            ATTR_CHR => apply_cells(&mut self.attributes, command),
+            PAL_SET => self.set_system_palettes(command),
+            PAL_TRN => self.transfer = Some(Transfer::SystemPalettes),
+            MLT_REQ => {
+                self.players = match command[1] & 0b11 {
+                    1 => 2,
+                    3 => 4,
+                    _ => 1,
+                };
+                self.player = 0;
+            }
+            CHR_TRN => self.transfer = Some(Transfer::BorderTiles(command[1] as usize & 0x01)),
+            PCT_TRN => self.transfer = Some(Transfer::BorderMap),
+            ATTR_TRN => self.transfer = Some(Transfer::AttributeFiles),
+            ATTR_SET => self.set_attribute_file(command[1]),
+            MASK_EN => self.set_mask(command[1]),
+            // Sound, SNES programs and the rest have nothing to do with
+            // the picture
+            _ => (),
+        }
+    }
+
+    /// PALxy: colour 0 for every palette, then colours 1-3 of x and y
+    fn set_palette_pair(&mut self, first: usize, second: usize, command: &[u8]) {
+        let colors = &command[1..];
+        let shared = color_at(colors, 0);
+        for palette in self.palettes.iter_mut() {
+            palette[0] = shared;
+        }
+        for index in 1..4 {
+            self.palettes[first][index] = color_at(colors, index);
+            self.palettes[second][index] = color_at(colors, index + 3);
+        }
+    }
+
+    /// PAL_SET: four palettes out of the ones PAL_TRN loaded, optionally
+    /// with an attribute file and lifting the mask
+    fn set_system_palettes(&mut self, command: &[u8]) {
+        for palette in 0..4 {
+            let number = u16::from_le_bytes([command[1 + palette * 2], command[2 + palette * 2]]);
+            self.palettes[palette] = self.system_palettes[number as usize % SYSTEM_PALETTES];
+        }
+        let shared = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = shared;
+        }
+
+        if command[9] & APPLY_ATTRIBUTE_FILE_BIT != 0 {
+            self.set_attribute_file(command[9]);
+        } else if command[9] & CANCEL_MASK_BIT != 0 {
+            self.set_mask(0);
+        }
+    }
+
+    /// ATTR_SET, also used by PAL_SET
+    fn set_attribute_file(&mut self, settings: u8) {
+        let file = (settings & ATTRIBUTE_FILE_BITS) as usize % ATTRIBUTE_FILES;
+        let start = file * ATTRIBUTE_FILE_SIZE;
+        apply_attribute_file(
+            &mut self.attributes,
+            &self.attribute_files[start..start + ATTRIBUTE_FILE_SIZE],
+        );
+        if settings & CANCEL_MASK_BIT != 0 {
+            self.set_mask(0);
+        }
+    }
+
+    fn set_mask(&mut self, mode: u8) {
+        self.mask = match mode & 0b11 {
+            1 => Mask::Freeze,
+            2 => Mask::Black,
+            3 => Mask::Color0,
+            _ => Mask::None,
+        };
+        self.frozen = None;
+    }
+
+    /// The 256x224 SNES picture as 24-bit RGB: the Game Boy screen coloured
+    /// through the palettes of its cells, inside the border. Transparent
+    /// border pixels show colour 0.
+    pub fn render(&self, framebuffer: &[u8]) -> Vec<u8> {
+        let backdrop = self.palettes[0][0];
+        let screen = match (self.mask, &self.frozen) {
+            (Mask::Freeze, Some(frozen)) => Some(frozen.as_slice()),
+            (Mask::None | Mask::Freeze, _) => Some(framebuffer),
+            (Mask::Black | Mask::Color0, _) => None,
+        };
+
+        let mut picture = Vec::with_capacity(SGB_WIDTH * SGB_HEIGHT * 3);
+        for y in 0..SGB_HEIGHT {
+            for x in 0..SGB_WIDTH {
+                let on_screen = (SCREEN_LEFT..SCREEN_LEFT + SCREEN_WIDTH).contains(&x)
+                    && (SCREEN_TOP..SCREEN_TOP + SCREEN_HEIGHT).contains(&y);
+                let color = if on_screen {
+                    let (x, y) = (x - SCREEN_LEFT, y - SCREEN_TOP);
+                    match (screen, self.mask) {
+                        (Some(screen), _) => self.screen_color(screen, x, y),
+                        (None, Mask::Black) => 0x0000,
+                        (None, _) => backdrop,
+                    }
+                } else {
+                    match border_pixel(&self.border_tiles, &self.border_map, x, y) {
+                        (_, 0) => backdrop,
+                        (palette, index) => {
+                            let palette = palette.wrapping_sub(FIRST_BORDER_PALETTE) as usize;
+                            self.border_palettes[palette % 4][index as usize]
+                        }
+                    }
+                };
+                picture.extend(ColorCorrection::Raw.apply(color));
+            }
+        }
+        picture
+    }
+
+    fn screen_color(&self, screen: &[u8], x: usize, y: usize) -> u16 {
+        let shade = screen[y * SCREEN_WIDTH + x] as usize & 0b11;
+        if shade == 0 {
+            return self.palettes[0][0];
+        }
+        let palette = self.attributes[(y / 8) * CELLS_WIDE + x / 8] as usize;
+        self.palettes[palette][shade]
+    }
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Sgb::new()
+    }
+}
+
+/// Little endian RGB555 colour `index` of a run of colours
+fn color_at(colors: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([colors[index * 2], colors[index * 2 + 1]]) & 0x7FFF
+}
+
+/// Turns the shades on screen back into the 2 bits per pixel tiles that
+/// produced them, one 8x8 cell per tile
+fn transfer_data(framebuffer: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x00; TRANSFER_SIZE];
+    for (offset, byte) in data.iter_mut().enumerate() {
+        let tile = offset / 16;
+        let row = (offset % 16) / 2;
+        let plane = offset % 2;
+        let y = (tile / CELLS_WIDE) * 8 + row;
+        let left = (tile % CELLS_WIDE) * 8;
+        for column in 0..8 {
+            let shade = framebuffer[y * SCREEN_WIDTH + left + column];
+            *byte |= ((shade >> plane) & 0x01) << (7 - column);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    use self::packet::tests::pulses;
+
+    /// Sends a command, split over as many packets as its length says
+    fn send(sgb: &mut Sgb, command: &[u8]) {
+        let packets = (command[0] & 0b111) as usize;
+        let mut bytes = command.to_vec();
+        bytes.resize(packets * PACKET_SIZE, 0x00);
+        for packet in bytes.chunks(PACKET_SIZE) {
+            for write in pulses(packet.try_into().unwrap()) {
+                sgb.write_p1(write);
+            }
+        }
+    }
+
+    /// A screen showing `data` the way a VRAM transfer expects it
+    fn screen_with(data: &[u8]) -> Vec<u8> {
+        let mut screen = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for (offset, byte) in data.iter().enumerate() {
+            let (tile, row, plane) = (offset / 16, (offset % 16) / 2, offset % 2);
+            let y = (tile / CELLS_WIDE) * 8 + row;
+            let left = (tile % CELLS_WIDE) * 8;
+            for column in 0..8 {
+                let bit = (byte >> (7 - column)) & 0x01;
+                screen[y * SCREEN_WIDTH + left + column] |= bit << plane;
+            }
+        }
+        screen
+    }
+
+    fn pixel(picture: &[u8], x: usize, y: usize) -> [u8; 3] {
+        let offset = (y * SGB_WIDTH + x) * 3;
+        picture[offset..offset + 3].try_into().unwrap()
+    }
+
+    fn on_screen(picture: &[u8], x: usize, y: usize) -> [u8; 3] {
+        pixel(picture, SCREEN_LEFT + x, SCREEN_TOP + y)
+    }
+
+    fn rgb(color: u16) -> [u8; 3] {
+        ColorCorrection::Raw.apply(color)
+    }
+
+    #[rstest]
+    #[case(PAL01, 0, 1)]
+    #[case(PAL23, 2, 3)]
+    #[case(PAL03, 0, 3)]
+    #[case(PAL12, 1, 2)]
+    fn should_set_palette_pairs(#[case] command: u8, #[case] first: usize, #[case] second: usize) {
+        let mut sgb = Sgb::new();
+        let colors: [u16; 7] = [0x1111, 0x0001, 0x0002, 0x0003, 0x0004, 0x0005, 0x0006];
+        let mut packet = vec![command << 3 | 1];
+        packet.extend(colors.iter().flat_map(|color| color.to_le_bytes()));
+
+        send(&mut sgb, &packet);
+
+        assert_eq!(sgb.palettes[first], [0x1111, 0x0001, 0x0002, 0x0003]);
+        assert_eq!(sgb.palettes[second], [0x1111, 0x0004, 0x0005, 0x0006]);
+        assert!(sgb.palettes.iter().all(|palette| palette[0] == 0x1111));
+    }
+
+    #[test]
+    fn should_colour_screen_by_cell_attributes() {
+        let mut sgb = Sgb::new();
+        let mut packet = vec![PAL01 << 3 | 1, 0x00, 0x00];
+        for color in [0x001F, 0x001F, 0x001F, 0x03E0, 0x03E0, 0x03E0] {
+            packet.extend(u16::to_le_bytes(color));
+        }
+        send(&mut sgb, &packet);
+        // Palette 1 inside the cells from (1, 0) to (1, 0)
+        send(&mut sgb, &[ATTR_BLK << 3 | 1, 1, 0b001, 0b01, 1, 0, 1, 0]);
+        let mut screen = vec![3; SCREEN_WIDTH * SCREEN_HEIGHT];
+        screen[0] = 0;
+
+        let picture = sgb.render(&screen);
+
+        assert_eq!(on_screen(&picture, 0, 0), rgb(0x0000));
+        assert_eq!(on_screen(&picture, 7, 0), rgb(0x001F));
+        assert_eq!(on_screen(&picture, 8, 0), rgb(0x03E0));
+        assert_eq!(on_screen(&picture, 8, 8), rgb(0x001F));
+    }
+
+    #[test]
+    fn should_assemble_commands_over_several_packets() {
+        let mut sgb = Sgb::new();
+        let mut command = vec![ATTR_BLK << 3 | 2, 3];
+        for (palette, x) in [(1, 0), (2, 5), (3, 10)] {
+            command.extend([0b001, palette, x, 0, x, 0]);
+        }
+
+        send(&mut sgb, &command);
+
+        assert_eq!(&sgb.attributes[..11], &[1, 0, 0, 0, 0, 2, 0, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn should_load_system_palettes_from_screen() {
+        let mut sgb = Sgb::new();
+        let mut data = vec![0x00; TRANSFER_SIZE];
+        // System palette 3 and 511
+        data[24..32].copy_from_slice(&[0x1F, 0x00, 0xE0, 0x03, 0x00, 0x7C, 0xFF, 0x7F]);
+        data[4088..4096].copy_from_slice(&[0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        send(&mut sgb, &[PAL_TRN << 3 | 1]);
+        sgb.end_frame(&screen_with(&data));
+
+        send(
+            &mut sgb,
+            &[PAL_SET << 3 | 1, 3, 0, 0xFF, 0x01, 3, 0, 3, 0, 0x00],
+        );
+
+        assert_eq!(sgb.palettes[0], [0x001F, 0x03E0, 0x7C00, 0x7FFF]);
+        assert_eq!(sgb.palettes[1], [0x001F, 0x0000, 0x0000, 0x0000]);
+        assert_eq!(sgb.palettes[3], sgb.palettes[0]);
+    }
+
+    #[test]
+    fn should_only_transfer_on_request() {
+        let mut sgb = Sgb::new();
+
+        sgb.end_frame(&vec![3; SCREEN_WIDTH * SCREEN_HEIGHT]);
+
+        assert_eq!(sgb.system_palettes[0], DEFAULT_PALETTE);
+    }
+
+    #[test]
+    fn should_apply_attribute_files() {
+        let mut sgb = Sgb::new();
+        let mut data = vec![0x00; TRANSFER_SIZE];
+        data[2 * ATTRIBUTE_FILE_SIZE] = 0b10_00_00_11;
+        send(&mut sgb, &[ATTR_TRN << 3 | 1]);
+        sgb.end_frame(&screen_with(&data));
+        send(&mut sgb, &[MASK_EN << 3 | 1, 2]);
+
+        send(&mut sgb, &[ATTR_SET << 3 | 1, CANCEL_MASK_BIT | 2]);
+
+        assert_eq!(&sgb.attributes[..4], &[2, 0, 0, 3]);
+        assert_eq!(sgb.mask(), Mask::None);
+    }
+
+    #[test]
+    fn should_draw_border_around_screen() {
+        let mut sgb = Sgb::new();
+        // Tile 0x81 is solid colour 1, the map puts it in the top left
+        // corner with palette 5
+        let mut tiles = vec![0x00; TRANSFER_SIZE];
+        for row in 0..8 {
+            tiles[BORDER_TILE_SIZE + row * 2] = 0xFF;
+        }
+        let mut map = vec![0x00; TRANSFER_SIZE];
+        map[0..2].copy_from_slice(&(0x0081u16 | 5 << 10).to_le_bytes());
+        map[MAP_SIZE + 32 + 2..MAP_SIZE + 32 + 4].copy_from_slice(&0x7C00u16.to_le_bytes());
+
+        send(&mut sgb, &[CHR_TRN << 3 | 1, 1]);
+        sgb.end_frame(&screen_with(&tiles));
+        send(&mut sgb, &[PCT_TRN << 3 | 1]);
+        sgb.end_frame(&screen_with(&map));
+        let picture = sgb.render(&vec![0; SCREEN_WIDTH * SCREEN_HEIGHT]);
+
+        assert_eq!(picture.len(), SGB_WIDTH * SGB_HEIGHT * 3);
+        assert_eq!(pixel(&picture, 7, 7), rgb(0x7C00));
+        assert_eq!(pixel(&picture, 8, 0), rgb(DEFAULT_PALETTE[0]));
+        assert_eq!(on_screen(&picture, 0, 0), rgb(DEFAULT_PALETTE[0]));
+    }
+
+    #[rstest]
+    #[case(1, DEFAULT_PALETTE[3])]
+    #[case(2, 0x0000)]
+    #[case(3, DEFAULT_PALETTE[0])]
+    #[case(0, DEFAULT_PALETTE[2])]
+    fn should_mask_screen(#[case] mode: u8, #[case] expected: u16) {
+        let mut sgb = Sgb::new();
+        send(&mut sgb, &[MASK_EN << 3 | 1, mode]);
+        // Freezing keeps this frame, the next one has moved on
+        sgb.end_frame(&vec![3; SCREEN_WIDTH * SCREEN_HEIGHT]);
+
+        let picture = sgb.render(&vec![2; SCREEN_WIDTH * SCREEN_HEIGHT]);
+
+        assert_eq!(on_screen(&picture, 80, 72), rgb(expected));
+    }
+
+    #[rstest]
+    #[case(0, None, None)]
+    #[case(1, Some(0x0F), Some(0x0E))]
+    #[case(3, Some(0x0F), Some(0x0E))]
+    fn should_cycle_through_controllers(
+        #[case] mode: u8,
+        #[case] first: Option<u8>,
+        #[case] second: Option<u8>,
+    ) {
+        let mut sgb = Sgb::new();
+        send(&mut sgb, &[MLT_REQ << 3 | 1, mode]);
+
+        assert_eq!(sgb.joypad_id(0x30), first);
+        assert_eq!(sgb.joypad_id(0x20), None);
+        sgb.write_p1(0x10);
+        sgb.write_p1(0x30);
+        assert_eq!(sgb.joypad_id(0x30), second);
+    }
+
+    #[test]
+    fn should_wrap_around_connected_controllers() {
+        let mut sgb = Sgb::new();
+        send(&mut sgb, &[MLT_REQ << 3 | 1, 1]);
+
+        sgb.write_p1(0x10);
+        sgb.write_p1(0x30);
+        sgb.write_p1(0x10);
+        sgb.write_p1(0x30);
+
+        assert_eq!(sgb.players(), 2);
+        assert_eq!(sgb.joypad_id(0x30), Some(0x0F));
+    }
+}
@@ -0,0 +1,244 @@
+/// The screen in 8x8 cells, each one coloured by one of the four palettes
+pub const CELLS_WIDE: usize = 20;
+pub const CELLS_HIGH: usize = 18;
+pub const CELL_COUNT: usize = CELLS_WIDE * CELLS_HIGH;
+/// An attribute file packs a whole map at two bits per cell
+pub const ATTRIBUTE_FILE_SIZE: usize = CELL_COUNT / 4;
+
+const BLOCK_SIZE: usize = 6;
+const INSIDE_BIT: u8 = 0b001;
+const BORDER_BIT: u8 = 0b010;
+const OUTSIDE_BIT: u8 = 0b100;
+const LINE_HORIZONTAL_BIT: u8 = 0b1000_0000;
+const DIVISION_HORIZONTAL_BIT: u8 = 0b0100_0000;
+
+pub type AttributeMap = [u8; CELL_COUNT];
+
+/// ATTR_BLK: rectangles colouring the cells inside them, on their edges
+/// and outside them. Setting only the inside or only the outside colours
+/// the edges along with it.
+pub fn apply_blocks(map: &mut AttributeMap, data: &[u8]) {
+    let count = data.get(1).map_or(0, |count| *count as usize & 0x1F);
+    for block in data
+        .get(2..)
+        .unwrap_or_default()
+        .chunks_exact(BLOCK_SIZE)
+        .take(count)
+    {
+        let control = block[0] & 0b111;
+        let inside = block[1] & 0b11;
+        let outside = (block[1] >> 4) & 0b11;
+        let border = match control {
+            INSIDE_BIT => Some(inside),
+            OUTSIDE_BIT => Some(outside),
+            _ if control & BORDER_BIT != 0 => Some((block[1] >> 2) & 0b11),
+            _ => None,
+        };
+        let (left, top, right, bottom) = (block[2], block[3], block[4], block[5]);
+
+        for (index, palette) in map.iter_mut().enumerate() {
+            let (x, y) = ((index % CELLS_WIDE) as u8, (index / CELLS_WIDE) as u8);
+            let within = (left..=right).contains(&x) && (top..=bottom).contains(&y);
+            let on_edge = within && (x == left || x == right || y == top || y == bottom);
+            if on_edge {
+                if let Some(border) = border {
+                    *palette = border;
+                }
+            } else if within {
+                if control & INSIDE_BIT != 0 {
+                    *palette = inside;
+                }
+            } else if control & OUTSIDE_BIT != 0 {
+                *palette = outside;
+            }
+        }
+    }
+}
+
+/// ATTR_LIN: whole rows or columns, one byte each
+pub fn apply_lines(map: &mut AttributeMap, data: &[u8]) {
+    let count = data.get(1).map_or(0, |count| *count as usize);
+    for line in data.get(2..).unwrap_or_default().iter().take(count) {
+        let position = (line & 0x1F) as usize;
+        let palette = (line >> 5) & 0b11;
+        for (index, cell) in map.iter_mut().enumerate() {
+            let (x, y) = (index % CELLS_WIDE, index / CELLS_WIDE);
+            let covered = if line & LINE_HORIZONTAL_BIT != 0 {
+                y == position
+            } else {
+                x == position
+            };
+            if covered {
+                *cell = palette;
+            }
+        }
+    }
+}
+
+/// ATTR_DIV: splits the screen along a row or a column, with one palette
+/// on each side and one on the line itself
+pub fn apply_division(map: &mut AttributeMap, data: &[u8]) {
+    let (Some(palettes), Some(position)) = (data.get(1), data.get(2)) else {
+        return;
+    };
+    for (index, cell) in map.iter_mut().enumerate() {
+        let (x, y) = (index % CELLS_WIDE, index / CELLS_WIDE);
+        let coordinate = if palettes & DIVISION_HORIZONTAL_BIT != 0 {
+            y
+        } else {
+            x
+        };
+        let shift = match coordinate.cmp(&(*position as usize)) {
+            std::cmp::Ordering::Less => 2,
+            std::cmp::Ordering::Equal => 4,
+            std::cmp::Ordering::Greater => 0,
+        };
+        *cell = (palettes >> shift) & 0b11;
+    }
+}
+
+/// ATTR_CHR: individual cells from a starting one onwards, left to right
+/// or top to bottom, two bits each
+pub fn apply_cells(map: &mut AttributeMap, data: &[u8]) {
+    if data.len() < 6 {
+        return;
+    }
+    let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+    let count = u16::from_le_bytes([data[3], data[4]]) as usize;
+    let vertical = data[5] & 0x01 != 0;
+
+    for cell in 0..count.min(CELL_COUNT) {
+        let Some(byte) = data.get(6 + cell / 4) else {
+            return;
+        };
+        if x >= CELLS_WIDE || y >= CELLS_HIGH {
+            return;
+        }
+        map[y * CELLS_WIDE + x] = (byte >> (6 - 2 * (cell % 4))) & 0b11;
+
+        if vertical {
+            y += 1;
+            if y == CELLS_HIGH {
+                y = 0;
+                x += 1;
+            }
+        } else {
+            x += 1;
+            if x == CELLS_WIDE {
+                x = 0;
+                y += 1;
+            }
+        }
+    }
+}
+
+/// Unpacks an attribute file from ATTR_TRN, leftmost cell in the highest
+/// bits
+pub fn apply_attribute_file(map: &mut AttributeMap, file: &[u8]) {
+    for (index, cell) in map.iter_mut().enumerate() {
+        *cell = (file[index / 4] >> (6 - 2 * (index % 4))) & 0b11;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn cell(map: &AttributeMap, x: usize, y: usize) -> u8 {
+        map[y * CELLS_WIDE + x]
+    }
+
+    #[rstest]
+    // Inside, edge and outside each get their own palette
+    #[case(0b111, [1, 2, 3])]
+    // Only the inside also takes the edge
+    #[case(INSIDE_BIT, [1, 1, 0])]
+    // Only the outside also takes the edge
+    #[case(OUTSIDE_BIT, [0, 3, 3])]
+    #[case(BORDER_BIT, [0, 2, 0])]
+    fn should_colour_blocks(#[case] control: u8, #[case] expected: [u8; 3]) {
+        let mut map = [0; CELL_COUNT];
+
+        apply_blocks(&mut map, &[0x21, 1, control, 0b11_10_01, 2, 2, 6, 5]);
+
+        assert_eq!(cell(&map, 4, 3), expected[0]);
+        assert_eq!(cell(&map, 2, 4), expected[1]);
+        assert_eq!(cell(&map, 6, 5), expected[1]);
+        assert_eq!(cell(&map, 7, 3), expected[2]);
+        assert_eq!(cell(&map, 0, 0), expected[2]);
+    }
+
+    #[test]
+    fn should_colour_several_blocks_in_order() {
+        let mut map = [0; CELL_COUNT];
+        let data = [
+            0x22, 2, //
+            INSIDE_BIT, 0b01, 0, 0, 9, 9, //
+            INSIDE_BIT, 0b10, 5, 5, 19, 17,
+        ];
+
+        apply_blocks(&mut map, &data);
+
+        assert_eq!(cell(&map, 1, 1), 1);
+        assert_eq!(cell(&map, 5, 5), 2);
+        assert_eq!(cell(&map, 19, 17), 2);
+        assert_eq!(cell(&map, 15, 0), 0);
+    }
+
+    #[test]
+    fn should_colour_lines() {
+        let mut map = [0; CELL_COUNT];
+
+        apply_lines(&mut map, &[0x29, 2, 0x03 | 0x20, 0x80 | 0x05 | 0x40]);
+
+        assert_eq!(cell(&map, 3, 0), 1);
+        assert_eq!(cell(&map, 3, 17), 1);
+        assert_eq!(cell(&map, 0, 5), 2);
+        assert_eq!(cell(&map, 3, 5), 2);
+        assert_eq!(cell(&map, 4, 4), 0);
+    }
+
+    #[rstest]
+    #[case(0b00_11_10_01, [(3, 0, 2), (4, 17, 3), (5, 9, 1)])]
+    #[case(0b01_11_10_01, [(19, 3, 2), (0, 4, 3), (0, 5, 1)])]
+    fn should_divide_screen(#[case] palettes: u8, #[case] expected: [(usize, usize, u8); 3]) {
+        let mut map = [0; CELL_COUNT];
+
+        apply_division(&mut map, &[0x31, palettes, 4]);
+
+        for (x, y, palette) in expected {
+            assert_eq!(cell(&map, x, y), palette);
+        }
+    }
+
+    #[rstest]
+    #[case(0, [(18, 2), (19, 2), (0, 3), (1, 3)])]
+    #[case(1, [(18, 2), (18, 3), (18, 4), (18, 5)])]
+    fn should_colour_cells_in_direction(
+        #[case] direction: u8,
+        #[case] expected_cells: [(usize, usize); 4],
+    ) {
+        let mut map = [0; CELL_COUNT];
+
+        apply_cells(&mut map, &[0x39, 18, 2, 4, 0, direction, 0b01_10_11_01]);
+
+        for ((x, y), palette) in expected_cells.into_iter().zip([1, 2, 3, 1]) {
+            assert_eq!(cell(&map, x, y), palette);
+        }
+        assert_eq!(map.iter().filter(|cell| **cell != 0).count(), 4);
+    }
+
+    #[test]
+    fn should_unpack_attribute_file() {
+        let mut map = [0; CELL_COUNT];
+        let mut file = [0x00; ATTRIBUTE_FILE_SIZE];
+        file[0] = 0b11_10_01_00;
+        file[ATTRIBUTE_FILE_SIZE - 1] = 0b00_00_00_10;
+
+        apply_attribute_file(&mut map, &file);
+
+        assert_eq!(&map[0..4], &[3, 2, 1, 0]);
+        assert_eq!(map[CELL_COUNT - 1], 2);
+    }
+}
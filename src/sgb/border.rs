@@ -0,0 +1,91 @@
+/// SNES 4 bits per pixel tile: bitplanes 0 and 1 interleaved row by row,
+/// then bitplanes 2 and 3 the same way
+pub const BORDER_TILE_SIZE: usize = 32;
+pub const BORDER_TILES: usize = 256;
+/// The border's tile map is 32 entries wide, of which 28 rows show
+pub const MAP_WIDTH: usize = 32;
+pub const MAP_SIZE: usize = MAP_WIDTH * MAP_WIDTH * 2;
+pub const COLORS_PER_PALETTE: usize = 16;
+/// PCT_TRN fills palettes 4-7, the four game screen palettes come first
+pub const FIRST_BORDER_PALETTE: u8 = 4;
+
+const TILE_BITS: u16 = 0x00FF;
+const X_FLIP_BIT: u16 = 0x4000;
+const Y_FLIP_BIT: u16 = 0x8000;
+
+/// Palette and colour index of border pixel (x, y). Colour 0 is
+/// transparent.
+pub fn border_pixel(tiles: &[u8], map: &[u8], x: usize, y: usize) -> (u8, u8) {
+    let offset = ((y / 8) * MAP_WIDTH + x / 8) * 2;
+    let entry = u16::from_le_bytes([map[offset], map[offset + 1]]);
+    let palette = ((entry >> 10) & 0b111) as u8;
+
+    let column = if entry & X_FLIP_BIT != 0 {
+        7 - x % 8
+    } else {
+        x % 8
+    };
+    let row = if entry & Y_FLIP_BIT != 0 {
+        7 - y % 8
+    } else {
+        y % 8
+    };
+    let tile = (entry & TILE_BITS) as usize;
+    (palette, tile_pixel(tiles, tile, column, row))
+}
+
+fn tile_pixel(tiles: &[u8], tile: usize, x: usize, y: usize) -> u8 {
+    let row = tile * BORDER_TILE_SIZE + y * 2;
+    let bit = 7 - x;
+    [row, row + 1, row + 16, row + 17]
+        .iter()
+        .enumerate()
+        .fold(0, |color, (plane, address)| {
+            color | ((tiles[*address] >> bit) & 0x01) << plane
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Tile 1 has its top left pixel in colour 0b1011 and its top right
+    /// one in colour 0b0100, every map entry points at it
+    fn border(attributes: u16) -> (Vec<u8>, Vec<u8>) {
+        let mut tiles = vec![0x00; BORDER_TILES * BORDER_TILE_SIZE];
+        tiles[BORDER_TILE_SIZE] = 0x80;
+        tiles[BORDER_TILE_SIZE + 1] = 0x80;
+        tiles[BORDER_TILE_SIZE + 17] = 0x80;
+        tiles[BORDER_TILE_SIZE + 16] = 0x01;
+        let map = (0..MAP_SIZE / 2)
+            .flat_map(|_| (0x0001 | attributes).to_le_bytes())
+            .collect();
+        (tiles, map)
+    }
+
+    #[rstest]
+    #[case(0x0000, 0, 0, 0b1011)]
+    #[case(0x0000, 7, 0, 0b0100)]
+    #[case(0x0000, 0, 1, 0)]
+    #[case(X_FLIP_BIT, 7, 0, 0b1011)]
+    #[case(Y_FLIP_BIT, 0, 7, 0b1011)]
+    #[case(X_FLIP_BIT | Y_FLIP_BIT, 15, 15, 0b1011)]
+    fn should_decode_border_tiles(
+        #[case] attributes: u16,
+        #[case] x: usize,
+        #[case] y: usize,
+        #[case] expected: u8,
+    ) {
+        let (tiles, map) = border(attributes);
+
+        assert_eq!(border_pixel(&tiles, &map, x, y).1, expected);
+    }
+
+    #[test]
+    fn should_read_palette_from_map_entry() {
+        let (tiles, map) = border(6 << 10);
+
+        assert_eq!(border_pixel(&tiles, &map, 0, 0), (6, 0b1011));
+    }
+}
@@ -0,0 +1,129 @@
+pub const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+
+/// P14 and P15 of the joypad register, low when selected
+const SELECT_BITS: u8 = 0b0011_0000;
+const RESET_PULSE: u8 = 0b0000_0000;
+const ZERO_PULSE: u8 = 0b0010_0000;
+const ONE_PULSE: u8 = 0b0001_0000;
+
+/// Picks SGB packets out of the P14 and P15 pulses written to P1. A packet
+/// starts with both lines low, then every bit is a pulse on one of them,
+/// P14 for a 0 and P15 for a 1, with both lines high in between. 128 bits
+/// come least significant first and a 0 stop bit ends the packet.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PacketReceiver {
+    packet: [u8; PACKET_SIZE],
+    /// Bits received since the reset pulse, `None` when no packet started
+    bits: Option<usize>,
+    /// Select lines as last written
+    lines: u8,
+}
+
+impl PacketReceiver {
+    pub fn new() -> PacketReceiver {
+        PacketReceiver {
+            lines: SELECT_BITS,
+            ..PacketReceiver::default()
+        }
+    }
+
+    /// Takes a write to P1, returning the packet it completed, if any
+    pub fn write(&mut self, p1: u8) -> Option<[u8; PACKET_SIZE]> {
+        let lines = p1 & SELECT_BITS;
+        let pulse = self.lines == SELECT_BITS && lines != SELECT_BITS;
+        self.lines = lines;
+        if !pulse {
+            return None;
+        }
+
+        if lines == RESET_PULSE {
+            self.packet = [0x00; PACKET_SIZE];
+            self.bits = Some(0);
+            return None;
+        }
+
+        let bits = self.bits?;
+        if bits == PACKET_BITS {
+            self.bits = None;
+            return (lines == ZERO_PULSE).then_some(self.packet);
+        }
+        if lines == ONE_PULSE {
+            self.packet[bits / 8] |= 1 << (bits % 8);
+        }
+        self.bits = Some(bits + 1);
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// P1 writes that send `packet`, stop bit included
+    pub(crate) fn pulses(packet: &[u8; PACKET_SIZE]) -> Vec<u8> {
+        let mut writes = vec![RESET_PULSE, SELECT_BITS];
+        for bit in 0..PACKET_BITS {
+            let one = packet[bit / 8] & (1 << (bit % 8)) != 0;
+            writes.push(if one { ONE_PULSE } else { ZERO_PULSE });
+            writes.push(SELECT_BITS);
+        }
+        writes.extend([ZERO_PULSE, SELECT_BITS]);
+        writes
+    }
+
+    fn receive(writes: &[u8]) -> Vec<[u8; PACKET_SIZE]> {
+        let mut receiver = PacketReceiver::new();
+        writes
+            .iter()
+            .filter_map(|write| receiver.write(*write))
+            .collect()
+    }
+
+    fn packet() -> [u8; PACKET_SIZE] {
+        let mut packet = [0x00; PACKET_SIZE];
+        for (index, byte) in packet.iter_mut().enumerate() {
+            *byte = (index as u8).wrapping_mul(0x1D) ^ 0xA5;
+        }
+        packet
+    }
+
+    #[test]
+    fn should_receive_packet_least_significant_bit_first() {
+        assert_eq!(receive(&pulses(&packet())), vec![packet()]);
+    }
+
+    #[test]
+    fn should_drop_packet_without_zero_stop_bit() {
+        let mut writes = pulses(&packet());
+        let stop = writes.len() - 2;
+        writes[stop] = ONE_PULSE;
+
+        assert!(receive(&writes).is_empty());
+    }
+
+    #[test]
+    fn should_ignore_pulses_outside_packets() {
+        let mut writes = vec![ONE_PULSE, SELECT_BITS, ZERO_PULSE, SELECT_BITS];
+        writes.extend(pulses(&packet()));
+
+        assert_eq!(receive(&writes), vec![packet()]);
+    }
+
+    #[test]
+    fn should_only_count_pulses_starting_from_idle_lines() {
+        // Joypad polling switches straight from one line to the other
+        let mut writes = pulses(&packet());
+        writes.insert(3, ZERO_PULSE);
+
+        assert_eq!(receive(&writes), vec![packet()]);
+    }
+
+    #[test]
+    fn should_restart_on_reset_pulse() {
+        let mut writes = pulses(&[0xFF; PACKET_SIZE])[..40].to_vec();
+        writes.extend(pulses(&packet()));
+
+        assert_eq!(receive(&writes), vec![packet()]);
+    }
+}
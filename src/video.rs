@@ -0,0 +1,137 @@
+use crate::{
+    cpu::{cpu_impl::CPU, memory_bus::MemoryBus},
+    ppu::{DOTS_PER_LINE, LINES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH},
+    sgb::{SGB_HEIGHT, SGB_WIDTH},
+};
+
+pub mod blend;
+pub mod png;
+pub mod scale;
+
+const RGB_BYTES: usize = 3;
+const RGBA_BYTES: usize = 4;
+const OPAQUE: u8 = 0xFF;
+
+/// 32-bit RGBA image, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Transparent black
+    pub fn new(width: usize, height: usize) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: vec![0x00; width * height * RGBA_BYTES],
+        }
+    }
+
+    /// Opaque copy of a 24-bit RGB picture like the ones from
+    /// `Ppu::rgb_framebuffer` and `Sgb::render`
+    pub fn from_rgb(width: usize, height: usize, rgb: &[u8]) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: rgb
+                .chunks_exact(RGB_BYTES)
+                .take(width * height)
+                .flat_map(|color| [color[0], color[1], color[2], OPAQUE])
+                .collect(),
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let offset = (y * self.width + x) * RGBA_BYTES;
+        self.pixels[offset..offset + RGBA_BYTES].try_into().unwrap()
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let offset = (y * self.width + x) * RGBA_BYTES;
+        self.pixels[offset..offset + RGBA_BYTES].copy_from_slice(&color);
+    }
+}
+
+/// What is on screen right now: the SGB picture with its border when an
+/// SGB is attached, the Game Boy screen otherwise
+pub fn capture(bus: &MemoryBus) -> RgbaImage {
+    let framebuffer = bus.ppu().framebuffer();
+    match bus.sgb() {
+        Some(sgb) => RgbaImage::from_rgb(SGB_WIDTH, SGB_HEIGHT, &sgb.render(framebuffer)),
+        None => RgbaImage::from_rgb(SCREEN_WIDTH, SCREEN_HEIGHT, &bus.ppu().rgb_framebuffer()),
+    }
+}
+
+/// Runs `cpu` headless until the PPU has finished `frame` frames since
+/// power on and captures the screen. Time spent with the LCD off counts as
+/// a frame per frame's worth of cycles, so games that never turn it on
+/// still get their screenshot.
+pub fn screenshot(cpu: &mut CPU, frame: u64) -> RgbaImage {
+    let cycles_per_frame = (DOTS_PER_LINE * LINES_PER_FRAME as u32) as u64;
+    let mut lcd_off_cycles = 0;
+    while cpu.bus.ppu().frames() + lcd_off_cycles / cycles_per_frame < frame {
+        let lcd_off = !cpu.bus.ppu().enabled();
+        let cycles = cpu.step() as u64;
+        if lcd_off {
+            lcd_off_cycles += cycles;
+        }
+    }
+    capture(&cpu.bus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::LCDC_REGISTER;
+
+    #[test]
+    fn should_add_opaque_alpha_to_rgb() {
+        let image = RgbaImage::from_rgb(2, 1, &[1, 2, 3, 4, 5, 6]);
+
+        assert_eq!(image.pixels, vec![1, 2, 3, 0xFF, 4, 5, 6, 0xFF]);
+        assert_eq!(image.pixel(1, 0), [4, 5, 6, 0xFF]);
+    }
+
+    /// Spins on JR -2 at the reset vector
+    fn idle_cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.bus.write_byte(0x0000, 0x18);
+        cpu.bus.write_byte(0x0001, 0xFE);
+        cpu
+    }
+
+    #[test]
+    fn should_run_until_requested_frame() {
+        let mut cpu = idle_cpu();
+        cpu.bus.write_byte(LCDC_REGISTER, 0x91);
+
+        let image = screenshot(&mut cpu, 3);
+
+        assert_eq!(cpu.bus.ppu().frames(), 3);
+        assert_eq!(cpu.bus.ppu().ly(), 144);
+        assert_eq!((image.width, image.height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+    }
+
+    #[test]
+    fn should_count_frames_with_lcd_off() {
+        let mut cpu = idle_cpu();
+
+        let image = screenshot(&mut cpu, 2);
+
+        assert_eq!(cpu.bus.ppu().frames(), 0);
+        assert_eq!(image.pixel(80, 72), [0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn should_capture_sgb_picture() {
+        let mut bus = MemoryBus::new();
+        bus.set_sgb_mode(true);
+
+        let image = capture(&bus);
+
+        assert_eq!((image.width, image.height), (SGB_WIDTH, SGB_HEIGHT));
+    }
+}
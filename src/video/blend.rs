@@ -0,0 +1,145 @@
+/// How much of the previous frames stays on screen. The DMG and CGB LCDs
+/// are slow to change, so objects drawn every other frame look see-through
+/// instead of flickering, which some games count on.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Blending {
+    /// Every frame as it is
+    #[default]
+    Off,
+    /// Half of this frame, half of the previous one
+    Mix,
+    /// Every pixel moves from what was shown towards the new frame, keeping
+    /// this fraction of the old value each frame. Anything outside 0-1 is
+    /// clamped.
+    Decay(f32),
+}
+
+/// Blends frames of any byte-per-channel format, e.g. the 24-bit RGB from
+/// `Ppu::rgb_framebuffer`, with the ones before them
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrameBlender {
+    blending: Blending,
+    /// The last input frame for `Mix`, the last output for `Decay`
+    history: Vec<f32>,
+}
+
+impl FrameBlender {
+    pub fn new(blending: Blending) -> FrameBlender {
+        FrameBlender {
+            blending,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn blending(&self) -> Blending {
+        self.blending
+    }
+
+    /// Starts over from the next frame
+    pub fn set_blending(&mut self, blending: Blending) {
+        self.blending = blending;
+        self.reset();
+    }
+
+    /// Forgets the previous frames, e.g. after loading a state
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// The frame to show in place of `frame`. The first frame, and any
+    /// frame of another size than the previous one, is shown as it is.
+    pub fn blend(&mut self, frame: &[u8]) -> Vec<u8> {
+        if self.history.len() != frame.len() {
+            self.history = frame.iter().map(|channel| *channel as f32).collect();
+            return frame.to_vec();
+        }
+
+        match self.blending {
+            Blending::Off => frame.to_vec(),
+            Blending::Mix => frame
+                .iter()
+                .zip(self.history.iter_mut())
+                .map(|(channel, previous)| {
+                    let mixed = (*channel as f32 + *previous) / 2.0;
+                    *previous = *channel as f32;
+                    mixed.round() as u8
+                })
+                .collect(),
+            Blending::Decay(persistence) => {
+                let persistence = persistence.clamp(0.0, 1.0);
+                frame
+                    .iter()
+                    .zip(self.history.iter_mut())
+                    .map(|(channel, shown)| {
+                        *shown = *shown * persistence + *channel as f32 * (1.0 - persistence);
+                        shown.round() as u8
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn blend_all(blending: Blending, frames: &[[u8; 2]]) -> Vec<Vec<u8>> {
+        let mut blender = FrameBlender::new(blending);
+        frames.iter().map(|frame| blender.blend(frame)).collect()
+    }
+
+    #[rstest]
+    #[case(Blending::Off, [255, 0])]
+    #[case(Blending::Mix, [128, 128])]
+    #[case(Blending::Decay(0.5), [128, 128])]
+    #[case(Blending::Decay(0.0), [255, 0])]
+    #[case(Blending::Decay(1.0), [0, 255])]
+    #[case(Blending::Decay(7.0), [0, 255])]
+    fn should_blend_with_previous_frame(#[case] blending: Blending, #[case] expected: [u8; 2]) {
+        let blended = blend_all(blending, &[[0, 255], [255, 0]]);
+
+        assert_eq!(blended[0], vec![0, 255]);
+        assert_eq!(blended[1], expected);
+    }
+
+    #[test]
+    fn should_only_mix_two_frames() {
+        let blended = blend_all(Blending::Mix, &[[0, 0], [100, 200], [100, 200]]);
+
+        assert_eq!(blended[2], vec![100, 200]);
+    }
+
+    #[test]
+    fn should_decay_towards_new_frame() {
+        let mut frames = vec![[255, 0]];
+        frames.extend([[0, 255]; 40]);
+
+        let blended = blend_all(Blending::Decay(0.8), &frames);
+
+        assert_eq!(blended[1], vec![204, 51]);
+        assert_eq!(blended[2], vec![163, 92]);
+        assert!(blended[1..].windows(2).all(|pair| pair[1][0] <= pair[0][0]));
+        assert_eq!(blended[40], vec![0, 255]);
+    }
+
+    #[test]
+    fn should_start_over_when_frame_size_changes() {
+        let mut blender = FrameBlender::new(Blending::Mix);
+        blender.blend(&[0; 4]);
+
+        assert_eq!(blender.blend(&[200; 6]), vec![200; 6]);
+        assert_eq!(blender.blend(&[100; 6]), vec![150; 6]);
+    }
+
+    #[test]
+    fn should_forget_history_on_reset() {
+        let mut blender = FrameBlender::new(Blending::Decay(0.9));
+        blender.blend(&[0; 3]);
+
+        blender.reset();
+
+        assert_eq!(blender.blend(&[90; 3]), vec![90; 3]);
+    }
+}
@@ -0,0 +1,175 @@
+pub mod deflate;
+
+use std::{fs, path::Path};
+
+use crate::{crc32, emulator_error::EmulatorError};
+
+use super::RgbaImage;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGBA: u8 = 6;
+/// Every row starts with its filter, this encoder never filters
+const FILTER_NONE: u8 = 0;
+/// Deflate with a 32K window and no preset dictionary, the check bits
+/// making the header a multiple of 31
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+const ADLER_MODULO: u32 = 65521;
+
+/// PNG file for `image`, 8 bits per RGBA channel. PNG has no room for an
+/// image without pixels, so those are refused.
+pub fn encode(image: &RgbaImage) -> Result<Vec<u8>, EmulatorError> {
+    if image.width == 0 || image.height == 0 {
+        return Err(EmulatorError::InvalidImageSize(image.width, image.height));
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // No compression method, filter method or interlacing to choose from
+    header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGBA, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity((image.width * 4 + 1) * image.height);
+    for row in image
+        .pixels
+        .chunks_exact(image.width * 4)
+        .take(image.height)
+    {
+        scanlines.push(FILTER_NONE);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+pub fn save(image: &RgbaImage, path: &Path) -> Result<(), EmulatorError> {
+    fs::write(path, encode(image)?)?;
+    Ok(())
+}
+
+/// Length, type, data and the CRC-32 of type and data
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    let crc = crc32::update(crc32::update(crc32::start(), chunk_type), data);
+    png.extend_from_slice(&crc32::finish(crc).to_be_bytes());
+}
+
+/// zlib stream: header, deflate data and the Adler-32 of `data`
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut stream = ZLIB_HEADER.to_vec();
+    stream.extend_from_slice(&deflate::compress(data));
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1, 0), |(a, b), byte| {
+        let a = (a + *byte as u32) % ADLER_MODULO;
+        (a, (b + a) % ADLER_MODULO)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::save_file::tests::scratch_dir;
+    use rstest::*;
+
+    /// Type and data of every chunk, checking their CRCs on the way
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = Vec::new();
+        let mut offset = 8;
+        while offset < png.len() {
+            let length = u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap()) as usize;
+            let body = &png[offset + 4..offset + 8 + length];
+            let crc_offset = offset + 8 + length;
+            let crc = u32::from_be_bytes(png[crc_offset..crc_offset + 4].try_into().unwrap());
+            assert_eq!(crc32::crc32(body), crc);
+            chunks.push((
+                String::from_utf8(body[..4].to_vec()).unwrap(),
+                body[4..].to_vec(),
+            ));
+            offset += length + 12;
+        }
+        chunks
+    }
+
+    /// Undoes `zlib`, checking the checksum
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        assert_eq!(stream[..2], ZLIB_HEADER);
+        let data = deflate::tests::inflate(&stream[2..stream.len() - 4]);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+        data
+    }
+
+    #[rstest]
+    #[case(b"", 0x0000_0001)]
+    #[case(b"a", 0x0062_0062)]
+    #[case(b"Wikipedia", 0x11E6_0398)]
+    fn should_compute_adler32(#[case] data: &[u8], #[case] expected: u32) {
+        assert_eq!(adler32(data), expected);
+    }
+
+    #[test]
+    fn should_encode_header_and_pixels() {
+        let mut image = RgbaImage::new(2, 2);
+        image.set_pixel(1, 0, [0xFF, 0x00, 0x00, 0xFF]);
+        image.set_pixel(0, 1, [0x00, 0x00, 0xFF, 0x80]);
+
+        let chunks = chunks(&encode(&image).unwrap());
+
+        let types: Vec<_> = chunks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, vec![0, 0, 0, 2, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+        assert_eq!(
+            inflate(&chunks[1].1),
+            vec![
+                0, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, //
+                0, 0x00, 0x00, 0xFF, 0x80, 0x00, 0x00, 0x00, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn should_compress_scaled_screen() {
+        let image = RgbaImage::from_rgb(320, 288, &vec![0x5A; 320 * 288 * 3]);
+
+        let chunks = chunks(&encode(&image).unwrap());
+        let scanlines = inflate(&chunks[1].1);
+
+        assert!(chunks[1].1.len() < 320 * 288 / 10);
+        assert_eq!(scanlines.len(), (320 * 4 + 1) * 288);
+        assert_eq!(scanlines[..5], [FILTER_NONE, 0x5A, 0x5A, 0x5A, 0xFF]);
+    }
+
+    #[rstest]
+    #[case(0, 144)]
+    #[case(160, 0)]
+    fn should_reject_empty_images(#[case] width: usize, #[case] height: usize) {
+        let image = RgbaImage::new(width, height);
+
+        assert_eq!(
+            encode(&image),
+            Err(EmulatorError::InvalidImageSize(width, height))
+        );
+        assert!(save(&image, Path::new("empty.png")).is_err());
+    }
+
+    #[test]
+    fn should_save_png_file() {
+        let path = scratch_dir("png").join("screen.png");
+        let image = RgbaImage::new(3, 1);
+
+        save(&image, &path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), encode(&image).unwrap());
+    }
+}
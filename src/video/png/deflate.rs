@@ -0,0 +1,377 @@
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+const WINDOW_SIZE: usize = 0x8000;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash are tried for a match
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+const NO_POSITION: usize = usize::MAX;
+
+const STORED_BLOCK: u32 = 0b00;
+const FIXED_BLOCK: u32 = 0b01;
+const END_OF_BLOCK: u16 = 256;
+const FIRST_LENGTH_SYMBOL: u16 = 257;
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Raw deflate stream for `data`: one block with the fixed Huffman codes,
+/// or stored blocks when those come out smaller, e.g. for noise
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let fixed = compress_fixed(data);
+    if fixed.len() < stored_size(data) {
+        fixed
+    } else {
+        store(data)
+    }
+}
+
+/// Bits go out least significant first, filling every byte from its lowest
+/// bit up
+#[derive(Debug, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are the exception, most significant bit first
+    fn write_code(&mut self, code: u16, bits: u32) {
+        self.write((code as u32).reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn stored_size(data: &[u8]) -> usize {
+    data.len() + data.len().div_ceil(MAX_STORED_BLOCK).max(1) * 5
+}
+
+/// Blocks of up to 64K kept as they are, each with its length and the
+/// length's complement in front
+fn store(data: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(stored_size(data));
+    let blocks = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    for block in 0..blocks {
+        let start = block * MAX_STORED_BLOCK;
+        let end = (start + MAX_STORED_BLOCK).min(data.len());
+        let length = (end - start) as u16;
+        let last = (block == blocks - 1) as u8;
+        stream.push(last | (STORED_BLOCK as u8) << 1);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(&data[start..end]);
+    }
+    stream
+}
+
+/// One block with the fixed Huffman codes, every match found by `Matcher`
+fn compress_fixed(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.write(1, 1);
+    writer.write(FIXED_BLOCK, 2);
+
+    let mut matcher = Matcher::new();
+    let mut position = 0;
+    while position < data.len() {
+        let (length, distance) = matcher.longest_match(data, position);
+        let length = if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+            length
+        } else {
+            write_symbol(&mut writer, data[position] as u16);
+            1
+        };
+        for skipped in position..position + length {
+            matcher.insert(data, skipped);
+        }
+        position += length;
+    }
+
+    write_symbol(&mut writer, END_OF_BLOCK);
+    writer.finish()
+}
+
+/// Hash chains over every three byte sequence in the last 32K
+struct Matcher {
+    /// Latest position of every hash
+    head: Vec<usize>,
+    /// Position before this one with the same hash, by position in the
+    /// window
+    previous: Vec<usize>,
+}
+
+impl Matcher {
+    fn new() -> Matcher {
+        Matcher {
+            head: vec![NO_POSITION; 1 << HASH_BITS],
+            previous: vec![NO_POSITION; WINDOW_SIZE],
+        }
+    }
+
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH > data.len() {
+            return;
+        }
+        let hash = hash(&data[position..]);
+        self.previous[position % WINDOW_SIZE] = self.head[hash];
+        self.head[hash] = position;
+    }
+
+    /// Length and distance of the longest earlier copy of the bytes at
+    /// `position`
+    fn longest_match(&self, data: &[u8], position: usize) -> (usize, usize) {
+        if position + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let limit = MAX_MATCH.min(data.len() - position);
+        let mut best = (0, 0);
+        let mut candidate = self.head[hash(&data[position..])];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NO_POSITION || position - candidate > WINDOW_SIZE {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[position..position + limit])
+                .take_while(|(earlier, byte)| earlier == byte)
+                .count();
+            if length > best.0 {
+                best = (length, position - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+            // Slots of positions that left the window get reused by newer ones
+            let older = self.previous[candidate % WINDOW_SIZE];
+            if older >= candidate {
+                break;
+            }
+            candidate = older;
+        }
+        best
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let hash = (bytes[0] as usize) << 10 ^ (bytes[1] as usize) << 5 ^ bytes[2] as usize;
+    hash & ((1 << HASH_BITS) - 1)
+}
+
+/// Literal bytes, the end of block and match lengths share one alphabet
+fn write_symbol(writer: &mut BitWriter, symbol: u16) {
+    let (code, bits) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + symbol - 280, 8),
+    };
+    writer.write_code(code, bits);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASES
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap();
+    write_symbol(writer, FIRST_LENGTH_SYMBOL + index as u16);
+    writer.write(
+        (length - LENGTH_BASES[index] as usize) as u32,
+        LENGTH_EXTRA_BITS[index] as u32,
+    );
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASES
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap();
+    writer.write_code(index as u16, 5);
+    writer.write(
+        (distance - DISTANCE_BASES[index] as usize) as u32,
+        DISTANCE_EXTRA_BITS[index] as u32,
+    );
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rstest::*;
+
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        bit: usize,
+    }
+
+    impl BitReader<'_> {
+        fn read(&mut self, bits: u32) -> u32 {
+            (0..bits).fold(0, |value, index| {
+                let bit = (self.bytes[self.bit / 8] >> (self.bit % 8)) & 1;
+                self.bit += 1;
+                value | (bit as u32) << index
+            })
+        }
+
+        fn read_code(&mut self, bits: u32) -> u32 {
+            (0..bits).fold(0, |code, _| code << 1 | self.read(1))
+        }
+
+        fn align(&mut self) {
+            self.bit = self.bit.div_ceil(8) * 8;
+        }
+    }
+
+    /// Decodes stored and fixed Huffman blocks, which is all `compress`
+    /// writes
+    pub(crate) fn inflate(stream: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader {
+            bytes: stream,
+            bit: 0,
+        };
+        let mut data = Vec::new();
+        loop {
+            let last = reader.read(1) == 1;
+            match reader.read(2) {
+                STORED_BLOCK => {
+                    reader.align();
+                    let length = reader.read(16);
+                    assert_eq!(reader.read(16), !length & 0xFFFF);
+                    for _ in 0..length {
+                        data.push(reader.read(8) as u8);
+                    }
+                }
+                FIXED_BLOCK => inflate_fixed(&mut reader, &mut data),
+                block => panic!("unexpected block type {}", block),
+            }
+            if last {
+                return data;
+            }
+        }
+    }
+
+    fn inflate_fixed(reader: &mut BitReader, data: &mut Vec<u8>) {
+        loop {
+            let mut code = reader.read_code(7);
+            let symbol = if code <= 0b001_0111 {
+                code + 256
+            } else {
+                code = code << 1 | reader.read(1);
+                match code {
+                    0x30..=0xBF => code - 0x30,
+                    0xC0..=0xC7 => code - 0xC0 + 280,
+                    _ => (code << 1 | reader.read(1)) - 0x190 + 144,
+                }
+            };
+            match symbol {
+                0..=255 => data.push(symbol as u8),
+                256 => return,
+                _ => {
+                    let index = (symbol - FIRST_LENGTH_SYMBOL as u32) as usize;
+                    let length = LENGTH_BASES[index] as usize
+                        + reader.read(LENGTH_EXTRA_BITS[index] as u32) as usize;
+                    let index = reader.read_code(5) as usize;
+                    let distance = DISTANCE_BASES[index] as usize
+                        + reader.read(DISTANCE_EXTRA_BITS[index] as u32) as usize;
+                    for _ in 0..length {
+                        data.push(data[data.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Bytes that never repeat in a way worth a match
+    fn noise(length: usize) -> Vec<u8> {
+        let mut state: u32 = 0x1234_5678;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn screen_like(length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|index| [0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x38, 0x0F, 0xFF][(index / 40) % 8])
+            .collect()
+    }
+
+    #[rstest]
+    #[case(vec![])]
+    #[case(b"a".to_vec())]
+    #[case(b"abcabcabcabcabcabc, every literal from 0 to 255".to_vec())]
+    #[case((0..=255).collect())]
+    #[case(vec![0x00; 1000])]
+    #[case(screen_like(92_160))]
+    #[case(noise(70_000))]
+    fn should_round_trip(#[case] data: Vec<u8>) {
+        assert_eq!(inflate(&compress(&data)), data);
+        assert_eq!(inflate(&compress_fixed(&data)), data);
+        assert_eq!(inflate(&store(&data)), data);
+    }
+
+    #[test]
+    fn should_compress_repeats_with_fixed_codes() {
+        let data = screen_like(92_160);
+
+        let stream = compress(&data);
+
+        assert_eq!(stream[0] & 0b111, 1 | (FIXED_BLOCK as u8) << 1);
+        assert!(stream.len() < data.len() / 20);
+    }
+
+    #[test]
+    fn should_store_data_that_does_not_compress() {
+        let data = noise(70_000);
+
+        let stream = compress(&data);
+
+        assert_eq!(stream.len(), stored_size(&data));
+        assert_eq!(stream[0] & 0b110, (STORED_BLOCK as u8) << 1);
+    }
+
+    #[rstest]
+    #[case(3)]
+    #[case(258)]
+    #[case(259)]
+    #[case(40_000)]
+    fn should_copy_matches_up_to_window(#[case] period: usize) {
+        let mut data = noise(period);
+        data.extend_from_within(..);
+
+        assert_eq!(inflate(&compress_fixed(&data)), data);
+    }
+}
@@ -0,0 +1,284 @@
+use super::RgbaImage;
+
+type Color = [u8; 4];
+
+/// Software upscaling for frontends drawing the screen without a GPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaler {
+    /// Every pixel repeated into a square of this size
+    Nearest(usize),
+    /// EPX/AdvMAME2x, rounding off staircases at twice the size
+    Scale2x,
+    /// AdvMAME3x, the same at three times the size
+    Scale3x,
+    /// Edge-directed xBR at twice the size, blending the corners of pixels
+    /// that sit on a diagonal edge
+    Xbr,
+    /// Every pixel turned into a dot of this size, with the gaps between
+    /// dots at half brightness like a DMG LCD
+    DotMatrix(usize),
+}
+
+impl Scaler {
+    /// How many output pixels each input pixel turns into, across and down
+    pub fn factor(&self) -> usize {
+        match self {
+            Scaler::Nearest(factor) | Scaler::DotMatrix(factor) => (*factor).max(1),
+            Scaler::Scale2x | Scaler::Xbr => 2,
+            Scaler::Scale3x => 3,
+        }
+    }
+
+    pub fn apply(&self, image: &RgbaImage) -> RgbaImage {
+        let factor = self.factor();
+        let mut output = RgbaImage::new(image.width * factor, image.height * factor);
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let block = match self {
+                    Scaler::Nearest(_) => vec![image.pixel(x, y); factor * factor],
+                    Scaler::Scale2x => scale2x(image, x, y),
+                    Scaler::Scale3x => scale3x(image, x, y),
+                    Scaler::Xbr => xbr(image, x, y),
+                    Scaler::DotMatrix(_) => dot(image.pixel(x, y), factor),
+                };
+                for (index, color) in block.into_iter().enumerate() {
+                    output.set_pixel(
+                        x * factor + index % factor,
+                        y * factor + index / factor,
+                        color,
+                    );
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Pixel (x + dx, y + dy), repeating the edges outwards
+fn neighbour(image: &RgbaImage, x: usize, y: usize, dx: isize, dy: isize) -> Color {
+    let x = x.saturating_add_signed(dx).min(image.width - 1);
+    let y = y.saturating_add_signed(dy).min(image.height - 1);
+    image.pixel(x, y)
+}
+
+fn scale2x(image: &RgbaImage, x: usize, y: usize) -> Vec<Color> {
+    let e = image.pixel(x, y);
+    let b = neighbour(image, x, y, 0, -1);
+    let d = neighbour(image, x, y, -1, 0);
+    let f = neighbour(image, x, y, 1, 0);
+    let h = neighbour(image, x, y, 0, 1);
+    if b == h || d == f {
+        return vec![e; 4];
+    }
+
+    vec![
+        if d == b { d } else { e },
+        if b == f { f } else { e },
+        if d == h { d } else { e },
+        if h == f { f } else { e },
+    ]
+}
+
+fn scale3x(image: &RgbaImage, x: usize, y: usize) -> Vec<Color> {
+    let [[a, b, c], [d, e, f], [g, h, i]] =
+        [-1, 0, 1].map(|dy| [-1, 0, 1].map(|dx| neighbour(image, x, y, dx, dy)));
+    if b == h || d == f {
+        return vec![e; 9];
+    }
+
+    vec![
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        },
+        e,
+        if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        },
+        if h == f { f } else { e },
+    ]
+}
+
+fn xbr(image: &RgbaImage, x: usize, y: usize) -> Vec<Color> {
+    let e = image.pixel(x, y);
+    // Top left, top right, bottom left, bottom right, as mirror images of
+    // the bottom right corner
+    [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .into_iter()
+        .map(|(sx, sy)| {
+            let at = |dx: isize, dy: isize| neighbour(image, x, y, dx * sx, dy * sy);
+            xbr_corner(e, at)
+        })
+        .collect()
+}
+
+/// The bottom right quarter of `e`, with `at` giving the pixels around it:
+///
+/// ```text
+///          B
+///       D  E  F  F4
+///       G  H  I  I4
+///          H5 I5
+/// ```
+///
+/// plus C above F. When the H-F diagonal is a stronger edge than the E-I
+/// one, the corner takes half of whichever of F and H is closer to E.
+fn xbr_corner(e: Color, at: impl Fn(isize, isize) -> Color) -> Color {
+    let (b, c, d, f, g, h, i) = (
+        at(0, -1),
+        at(1, -1),
+        at(-1, 0),
+        at(1, 0),
+        at(-1, 1),
+        at(0, 1),
+        at(1, 1),
+    );
+    let (f4, i4, h5, i5) = (at(2, 0), at(2, 1), at(0, 2), at(1, 2));
+    if e == f || e == h {
+        return e;
+    }
+
+    let across =
+        distance(e, c) + distance(e, g) + distance(i, f4) + distance(i, h5) + 4 * distance(h, f);
+    let along =
+        distance(h, d) + distance(h, i5) + distance(f, i4) + distance(f, b) + 4 * distance(e, i);
+    if across >= along {
+        return e;
+    }
+
+    let closer = if distance(e, f) <= distance(e, h) {
+        f
+    } else {
+        h
+    };
+    mix(e, closer)
+}
+
+/// How different two colours look, weighing brightness over hue like xBR's
+/// YUV comparison
+fn distance(first: Color, second: Color) -> u32 {
+    let [y1, u1, v1] = yuv(first);
+    let [y2, u2, v2] = yuv(second);
+    48 * y1.abs_diff(y2) + 7 * u1.abs_diff(u2) + 6 * v1.abs_diff(v2)
+}
+
+fn yuv([r, g, b, _]: Color) -> [i32; 3] {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = (299 * r + 587 * g + 114 * b) / 1000;
+    [y, (b - y) * 492 / 1000, (r - y) * 877 / 1000]
+}
+
+fn mix(first: Color, second: Color) -> Color {
+    std::array::from_fn(|channel| {
+        (first[channel] as u16 + second[channel] as u16).div_ceil(2) as u8
+    })
+}
+
+fn dot(color: Color, factor: usize) -> Vec<Color> {
+    let [r, g, b, a] = color;
+    let gap = [r / 2, g / 2, b / 2, a];
+    (0..factor * factor)
+        .map(|index| {
+            let last = factor - 1;
+            if factor > 1 && (index % factor == last || index / factor == last) {
+                gap
+            } else {
+                color
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const WHITE: Color = [0xFF, 0xFF, 0xFF, 0xFF];
+    const BLACK: Color = [0x00, 0x00, 0x00, 0xFF];
+    const GREY: Color = [0x80, 0x80, 0x80, 0xFF];
+    const DIM: Color = [0x7F, 0x7F, 0x7F, 0xFF];
+
+    /// Reference image from rows of `.` for white, `#` for black, `x` for
+    /// the two mixed and `-` for white at half brightness
+    fn image(rows: &[&str]) -> RgbaImage {
+        let mut image = RgbaImage::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                let color = match pixel {
+                    '.' => WHITE,
+                    '#' => BLACK,
+                    'x' => GREY,
+                    '-' => DIM,
+                    _ => unreachable!(),
+                };
+                image.set_pixel(x, y, color);
+            }
+        }
+        image
+    }
+
+    #[rstest]
+    #[case(Scaler::Nearest(2), &["#.", ".#"], &["##..", "##..", "..##", "..##"])]
+    #[case(Scaler::Nearest(3), &["#."], &["###...", "###...", "###..."])]
+    #[case(
+        Scaler::Scale2x,
+        &["#..", ".#.", "..#"],
+        &["##....", "#.#...", ".###..", "..###.", "...#.#", "....##"]
+    )]
+    #[case(
+        Scaler::Scale3x,
+        &["##", "#."],
+        &["######", "######", "######", "#####.", "####..", "###..."]
+    )]
+    #[case(Scaler::Xbr, &["##", "#."], &["####", "####", "##x.", "##.."])]
+    #[case(Scaler::DotMatrix(3), &["."], &["..-", "..-", "---"])]
+    #[case(Scaler::DotMatrix(1), &["#."], &["#."])]
+    fn should_match_reference_image(
+        #[case] scaler: Scaler,
+        #[case] input: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(scaler.apply(&image(input)), image(expected));
+    }
+
+    #[rstest]
+    #[case(Scaler::Scale2x)]
+    #[case(Scaler::Scale3x)]
+    #[case(Scaler::Xbr)]
+    fn should_keep_straight_edges(#[case] scaler: Scaler) {
+        let factor = scaler.factor();
+        let input = image(&["##..", "##..", "##.."]);
+
+        let scaled = scaler.apply(&input);
+
+        assert_eq!(scaled, Scaler::Nearest(factor).apply(&input));
+    }
+
+    #[test]
+    fn should_scale_whole_screen() {
+        let input = RgbaImage::from_rgb(160, 144, &vec![0x40; 160 * 144 * 3]);
+
+        let scaled = Scaler::Xbr.apply(&input);
+
+        assert_eq!((scaled.width, scaled.height), (320, 288));
+        assert_eq!(scaled.pixels.len(), 320 * 288 * 4);
+    }
+}